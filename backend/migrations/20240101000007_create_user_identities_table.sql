@@ -0,0 +1,15 @@
+-- Accounts at external OpenID Connect providers linked to a user. The
+-- provider's subject identifier is stable; the email is kept for display.
+CREATE TABLE IF NOT EXISTS user_identities (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    provider TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    email TEXT,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_login_at TEXT,
+    UNIQUE (provider, subject)
+);
+
+-- Create indexes for better query performance
+CREATE INDEX IF NOT EXISTS idx_user_identities_user_id ON user_identities(user_id);
@@ -0,0 +1,13 @@
+-- Roles are 'user', 'curator' or 'admin' (users.role already exists).
+-- Disabled accounts can't sign in and their API keys stop working.
+ALTER TABLE users ADD COLUMN disabled_at TEXT;
+
+-- Saved routes curators have added to the shared public corpus
+CREATE TABLE IF NOT EXISTS public_routes (
+    route_id INTEGER PRIMARY KEY REFERENCES saved_routes(id) ON DELETE CASCADE,
+    published_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    published_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Create indexes for better query performance
+CREATE INDEX IF NOT EXISTS idx_public_routes_published_at ON public_routes(published_at);
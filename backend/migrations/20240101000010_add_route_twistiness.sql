@@ -0,0 +1,9 @@
+-- Twistiness of each saved route, measured when it is saved. NULL for
+-- routes saved before this migration until the server backfills them.
+ALTER TABLE saved_routes ADD COLUMN curvature_per_km REAL;
+ALTER TABLE saved_routes ADD COLUMN tight_bends INTEGER;
+ALTER TABLE saved_routes ADD COLUMN medium_bends INTEGER;
+ALTER TABLE saved_routes ADD COLUMN sweeping_bends INTEGER;
+
+-- Create indexes for better query performance
+CREATE INDEX IF NOT EXISTS idx_saved_routes_curvature ON saved_routes(curvature_per_km);
@@ -0,0 +1,158 @@
+use axum::{
+    extract::State,
+    middleware,
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::Duration;
+use serde::Deserialize;
+use crate::{
+    auth::{
+        audit::{self, AuditEntry, AuditEvent},
+        middleware::{auth, AuthUser},
+        password::hash_password,
+        session::end_all_sessions,
+        tokens::{consume_token, find_token_user, issue_token, TokenPurpose},
+    },
+    db::models::DbUser,
+    db::queries::users::{find_user_by_email, find_user_by_id, mark_email_verified, update_password},
+    error::AppError,
+    mail::Email,
+    state::AppState,
+};
+use super::auth::AuthResponse;
+
+#[derive(Debug, Deserialize)]
+pub struct ForgotPasswordRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResetPasswordRequest {
+    pub token: String,
+    pub password: String,
+    #[serde(rename = "confirmPassword")]
+    pub confirm_password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
+pub fn routes(state: AppState) -> Router<AppState> {
+    let signed_in = Router::new()
+        .route("/me", get(current_user))
+        .route("/verify-email/resend", post(resend_verification))
+        .route_layer(middleware::from_fn_with_state(state, auth));
+    
+    Router::new()
+        .route("/password/forgot", post(forgot_password))
+        .route("/password/reset", post(reset_password))
+        .route("/verify-email", post(verify_email))
+        .merge(signed_in)
+}
+
+/// The signed-in user, for clients that signed in through a redirect
+async fn current_user(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let user = find_user_by_id(&state.pool, user.id)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+    
+    Ok(Json(AuthResponse { user: user.into() }))
+}
+
+/// Email a verification link to a user
+pub(super) async fn send_verification_email(state: &AppState, user: &DbUser) -> Result<(), AppError> {
+    let ttl_hours = state.config.auth.email_verification_ttl_hours;
+    let token = issue_token(&state.pool, user.id, TokenPurpose::EmailVerification, Duration::hours(ttl_hours)).await?;
+    let link = format!("{}/verify-email?token={}", state.config.mail.link_base_url.trim_end_matches('/'), token);
+    
+    state.mailer
+        .send(&Email::email_verification(&user.email, &user.username, &link, ttl_hours))
+        .await
+}
+
+/// Always succeeds so the response doesn't reveal which emails have accounts
+async fn forgot_password(
+    State(state): State<AppState>,
+    Json(payload): Json<ForgotPasswordRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    if let Some(user) = find_user_by_email(&state.pool, payload.email.trim()).await? {
+        let ttl_minutes = state.config.auth.password_reset_ttl_minutes;
+        let token = issue_token(&state.pool, user.id, TokenPurpose::PasswordReset, Duration::minutes(ttl_minutes)).await?;
+        let link = format!("{}/reset-password?token={}", state.config.mail.link_base_url.trim_end_matches('/'), token);
+        
+        let email = Email::password_reset(&user.email, &user.username, &link, ttl_minutes);
+        if let Err(e) = state.mailer.send(&email).await {
+            tracing::error!("Failed to send password reset email to user {}: {}", user.id, e);
+        }
+    }
+    
+    Ok(Json(serde_json::json!({ "success": true })))
+}
+
+async fn reset_password(
+    State(state): State<AppState>,
+    Json(payload): Json<ResetPasswordRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    if payload.password != payload.confirm_password {
+        return Err(AppError::BadRequest("Passwords do not match".to_string()));
+    }
+    // Check the password against the account before using up the token
+    let user_id = find_token_user(&state.pool, TokenPurpose::PasswordReset, &payload.token).await?;
+    let user = find_user_by_id(&state.pool, user_id)
+        .await?
+        .ok_or_else(|| AppError::BadRequest("This link is invalid or has expired".to_string()))?;
+    state.password_policy.check(&payload.password, &[&user.email, &user.username])?;
+    
+    let user_id = consume_token(&state.pool, TokenPurpose::PasswordReset, &payload.token).await?;
+    
+    let (password_salt, password_hash) = hash_password(&payload.password)?;
+    update_password(&state.pool, user_id, &password_salt, &password_hash).await?;
+    
+    // The reset link was delivered to the inbox, which proves ownership
+    mark_email_verified(&state.pool, user_id).await?;
+    
+    // Sign out every device that used the old password
+    end_all_sessions(&state.pool, &state.revoked_tokens, user_id).await?;
+    
+    audit::record(
+        &state.pool,
+        AuditEntry { user_id: Some(user_id), email: Some(&user.email), ..AuditEntry::new(AuditEvent::PasswordReset) },
+    )
+    .await;
+    
+    Ok(Json(serde_json::json!({ "success": true })))
+}
+
+async fn verify_email(
+    State(state): State<AppState>,
+    Json(payload): Json<VerifyEmailRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let user_id = consume_token(&state.pool, TokenPurpose::EmailVerification, &payload.token).await?;
+    mark_email_verified(&state.pool, user_id).await?;
+    
+    Ok(Json(serde_json::json!({ "success": true })))
+}
+
+async fn resend_verification(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let user = find_user_by_id(&state.pool, user.id)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+    
+    if user.email_verified_at.is_some() {
+        return Err(AppError::BadRequest("Email address is already verified".to_string()));
+    }
+    
+    send_verification_email(&state, &user).await?;
+    
+    Ok(Json(serde_json::json!({ "success": true })))
+}
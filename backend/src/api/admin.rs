@@ -0,0 +1,203 @@
+use axum::{
+    extract::{Path, Query, State},
+    middleware,
+    response::IntoResponse,
+    routing::{get, post, put},
+    Json, Router,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use crate::{
+    auth::{
+        audit::{self, AuditEntry, AuditEvent},
+        middleware::{auth, require_permission, AuthUser},
+        roles::{Permission, Role},
+        session::end_all_sessions,
+    },
+    db::models::DbUser,
+    db::queries::{
+        stats::get_platform_stats,
+        users::{list_users, set_user_disabled, set_user_role},
+    },
+    error::AppError,
+    models::user::User,
+    state::AppState,
+};
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+
+#[derive(Debug, Serialize)]
+pub struct AdminUser {
+    #[serde(flatten)]
+    pub user: User,
+    #[serde(rename = "disabledAt")]
+    pub disabled_at: Option<String>,
+}
+
+impl From<DbUser> for AdminUser {
+    fn from(user: DbUser) -> Self {
+        Self {
+            disabled_at: user.disabled_at.clone(),
+            user: user.into(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListUsersQuery {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateRoleRequest {
+    pub role: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlatformStats {
+    pub users: i64,
+    pub curators: i64,
+    pub admins: i64,
+    #[serde(rename = "disabledUsers")]
+    pub disabled_users: i64,
+    #[serde(rename = "savedRoutes")]
+    pub saved_routes: i64,
+    #[serde(rename = "publicRoutes")]
+    pub public_routes: i64,
+    /// Routes in the matching index
+    #[serde(rename = "indexedRoutes")]
+    pub indexed_routes: usize,
+    #[serde(rename = "activeSessions")]
+    pub active_sessions: i64,
+    #[serde(rename = "activeApiKeys")]
+    pub active_api_keys: i64,
+}
+
+/// Admin endpoints need a browser session; API keys can't reach them
+pub fn routes(state: AppState) -> Router<AppState> {
+    let users = Router::new()
+        .route("/admin/users", get(get_users))
+        .route("/admin/users/:id/role", put(update_role))
+        .route("/admin/users/:id/disable", post(disable_user))
+        .route("/admin/users/:id/enable", post(enable_user))
+        .route_layer(middleware::from_fn_with_state((state.clone(), Permission::ManageUsers), require_permission));
+    
+    let stats = Router::new()
+        .route("/admin/stats", get(get_stats))
+        .route_layer(middleware::from_fn_with_state((state.clone(), Permission::ViewPlatformStats), require_permission));
+    
+    users
+        .merge(stats)
+        .route_layer(middleware::from_fn_with_state(state, auth))
+}
+
+async fn get_users(
+    State(state): State<AppState>,
+    Query(query): Query<ListUsersQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let offset = query.offset.unwrap_or(0).max(0);
+    
+    let users: Vec<AdminUser> = list_users(&state.pool, limit, offset)
+        .await?
+        .into_iter()
+        .map(AdminUser::from)
+        .collect();
+    
+    Ok(Json(users))
+}
+
+async fn update_role(
+    State(state): State<AppState>,
+    admin: AuthUser,
+    Path(id): Path<i64>,
+    Json(payload): Json<UpdateRoleRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let role: Role = payload.role.parse()?;
+    // Stops the last admin from locking everyone out
+    if id == admin.id {
+        return Err(AppError::BadRequest("You can't change your own role".to_string()));
+    }
+    
+    let user = set_user_role(&state.pool, id, role.as_str())
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+    
+    let detail = format!("{} by user {}", role, admin.id);
+    audit::record(
+        &state.pool,
+        AuditEntry {
+            user_id: Some(user.id),
+            email: Some(&user.email),
+            detail: Some(&detail),
+            ..AuditEntry::new(AuditEvent::RoleChanged)
+        },
+    )
+    .await;
+    
+    Ok(Json(AdminUser::from(user)))
+}
+
+/// Disabling also ends every session; API keys stop working while disabled
+async fn disable_user(
+    State(state): State<AppState>,
+    admin: AuthUser,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, AppError> {
+    if id == admin.id {
+        return Err(AppError::BadRequest("You can't disable your own account".to_string()));
+    }
+    
+    let user = set_user_disabled(&state.pool, id, true)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+    end_all_sessions(&state.pool, &state.revoked_tokens, user.id).await?;
+    
+    record_account_change(&state, &admin, &user, AuditEvent::AccountDisabled).await;
+    Ok(Json(AdminUser::from(user)))
+}
+
+async fn enable_user(
+    State(state): State<AppState>,
+    admin: AuthUser,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, AppError> {
+    let user = set_user_disabled(&state.pool, id, false)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+    
+    record_account_change(&state, &admin, &user, AuditEvent::AccountEnabled).await;
+    Ok(Json(AdminUser::from(user)))
+}
+
+async fn record_account_change(state: &AppState, admin: &AuthUser, user: &DbUser, event: AuditEvent) {
+    let detail = format!("by user {}", admin.id);
+    audit::record(
+        &state.pool,
+        AuditEntry {
+            user_id: Some(user.id),
+            email: Some(&user.email),
+            detail: Some(&detail),
+            ..AuditEntry::new(event)
+        },
+    )
+    .await;
+}
+
+async fn get_stats(State(state): State<AppState>) -> Result<impl IntoResponse, AppError> {
+    let stats = get_platform_stats(&state.pool, Utc::now().timestamp()).await?;
+    
+    Ok(Json(PlatformStats {
+        users: stats.users,
+        curators: stats.curators,
+        admins: stats.admins,
+        disabled_users: stats.disabled_users,
+        saved_routes: stats.saved_routes,
+        public_routes: stats.public_routes,
+        indexed_routes: state.spatial_index.snapshot().len(),
+        active_sessions: stats.active_sessions,
+        active_api_keys: stats.active_api_keys,
+    }))
+}
@@ -0,0 +1,141 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    middleware,
+    response::IntoResponse,
+    routing::{delete, get},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use crate::{
+    auth::{
+        api_keys::{issue_api_key, parse_scopes, Scope},
+        audit::{self, AuditEntry, AuditEvent},
+        middleware::{auth, AuthUser},
+    },
+    db::models::DbApiKey,
+    db::queries::api_keys::{get_user_api_keys, revoke_api_key},
+    error::AppError,
+    state::AppState,
+};
+
+/// Most active keys one user may hold
+const MAX_KEYS_PER_USER: usize = 25;
+
+#[derive(Debug, Serialize)]
+pub struct ApiKeyInfo {
+    pub id: i64,
+    pub name: String,
+    /// First characters of the key, to tell keys apart
+    pub prefix: String,
+    pub scopes: Vec<Scope>,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    #[serde(rename = "lastUsedAt")]
+    pub last_used_at: Option<String>,
+}
+
+impl From<DbApiKey> for ApiKeyInfo {
+    fn from(key: DbApiKey) -> Self {
+        Self {
+            scopes: parse_scopes(&key.scopes),
+            id: key.id,
+            name: key.name,
+            prefix: key.prefix,
+            created_at: key.created_at,
+            last_used_at: key.last_used_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreatedApiKey {
+    /// The full key; only returned here
+    pub key: String,
+    #[serde(flatten)]
+    pub info: ApiKeyInfo,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    pub scopes: Vec<String>,
+}
+
+/// Managing keys needs a browser session; keys can't create or revoke keys
+pub fn routes(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/api-keys", get(list_api_keys).post(create_api_key))
+        .route("/api-keys/:id", delete(delete_api_key))
+        .route_layer(middleware::from_fn_with_state(state, auth))
+}
+
+async fn list_api_keys(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let keys: Vec<ApiKeyInfo> = get_user_api_keys(&state.pool, user.id)
+        .await?
+        .into_iter()
+        .map(ApiKeyInfo::from)
+        .collect();
+    
+    Ok(Json(keys))
+}
+
+async fn create_api_key(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Json(payload): Json<CreateApiKeyRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let name = payload.name.trim();
+    if name.is_empty() || name.chars().count() > 100 {
+        return Err(AppError::BadRequest("Key name must be 1 to 100 characters".to_string()));
+    }
+    
+    let requested = payload.scopes.iter()
+        .map(|scope| scope.parse())
+        .collect::<Result<Vec<Scope>, _>>()?;
+    let scopes: Vec<Scope> = Scope::ALL.into_iter().filter(|scope| requested.contains(scope)).collect();
+    if scopes.is_empty() {
+        return Err(AppError::BadRequest("Choose at least one scope".to_string()));
+    }
+    
+    if get_user_api_keys(&state.pool, user.id).await?.len() >= MAX_KEYS_PER_USER {
+        return Err(AppError::BadRequest(format!(
+            "You can have at most {} API keys; revoke one first", MAX_KEYS_PER_USER
+        )));
+    }
+    
+    let issued = issue_api_key(&state.pool, user.id, name, &scopes).await?;
+    let detail = format!("{} ({})", issued.record.prefix, issued.record.scopes);
+    audit::record(
+        &state.pool,
+        AuditEntry { user_id: Some(user.id), detail: Some(&detail), ..AuditEntry::new(AuditEvent::ApiKeyCreated) },
+    )
+    .await;
+    
+    Ok((
+        StatusCode::CREATED,
+        Json(CreatedApiKey { key: issued.key, info: issued.record.into() }),
+    ))
+}
+
+async fn delete_api_key(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, AppError> {
+    if !revoke_api_key(&state.pool, user.id, id).await? {
+        return Err(AppError::NotFound("API key not found".to_string()));
+    }
+    
+    let detail = format!("key {}", id);
+    audit::record(
+        &state.pool,
+        AuditEntry { user_id: Some(user.id), detail: Some(&detail), ..AuditEntry::new(AuditEvent::ApiKeyRevoked) },
+    )
+    .await;
+    
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,18 +1,35 @@
 use axum::{
-    extract::State,
-    http::StatusCode,
-    response::IntoResponse,
-    routing::{post},
+    extract::{ConnectInfo, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{AppendHeaders, IntoResponse, Response},
+    routing::{get, post},
     Json, Router,
 };
+use axum_extra::extract::CookieJar;
 use serde::{Deserialize, Serialize};
-use sqlx::SqlitePool;
+use std::net::{IpAddr, SocketAddr};
 use crate::{
-    auth::{jwt::create_token, password::{hash_password, verify_password}},
-    db::queries::users::{create_user, find_user_by_email},
+    auth::{
+        audit::{self, AuditEntry, AuditEvent},
+        csrf::{self, CSRF_COOKIE},
+        jwt::{
+            cleared_mfa_token_cookie, create_mfa_token, verify_mfa_token, verify_token,
+            MFA_TOKEN_COOKIE,
+        },
+        mfa::{verify_second_factor, SecondFactor},
+        password::{hash_password, verify_password},
+        session::{cleared_cookies, end_session, refresh_session, start_session, ACCESS_COOKIE, REFRESH_COOKIE},
+    },
+    db::models::DbUser,
+    db::queries::{
+        sessions::find_session,
+        users::{create_user, find_user_by_email, find_user_by_id},
+    },
     error::AppError,
     models::user::User,
+    state::AppState,
 };
+use super::account::send_verification_email;
 
 #[derive(Debug, Deserialize)]
 pub struct LoginRequest {
@@ -20,6 +37,15 @@ pub struct LoginRequest {
     pub password: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct MfaLoginRequest {
+    /// From the password login; after an external sign-in it is in a cookie
+    #[serde(rename = "mfaToken")]
+    pub mfa_token: Option<String>,
+    /// A TOTP code or a recovery code
+    pub code: String,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct SignupRequest {
     pub email: String,
@@ -35,54 +61,205 @@ pub struct AuthResponse {
     pub user: User,
 }
 
-pub fn routes() -> Router<SqlitePool> {
+/// Returned instead of a session when the account has two-factor enabled
+#[derive(Debug, Serialize)]
+pub struct MfaChallenge {
+    #[serde(rename = "mfaRequired")]
+    pub mfa_required: bool,
+    #[serde(rename = "mfaToken")]
+    pub mfa_token: String,
+}
+
+pub fn routes() -> Router<AppState> {
     Router::new()
         .route("/login", post(login))
+        .route("/login/mfa", post(login_mfa))
         .route("/signup", post(signup))
         .route("/logout", post(logout))
+        .route("/refresh", post(refresh))
+        .route("/csrf", get(csrf_token))
+}
+
+pub(super) fn user_agent(headers: &HeaderMap) -> Option<&str> {
+    headers.get(header::USER_AGENT).and_then(|value| value.to_str().ok())
+}
+
+/// Client address, from `X-Forwarded-For` only when configured to trust it.
+/// The proxy appends the address it saw, so only the last entry is
+/// trusted; anything before it came from the client.
+pub(super) fn client_ip(
+    state: &AppState,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: &HeaderMap,
+) -> Option<IpAddr> {
+    if state.config.login.trust_forwarded_for {
+        let forwarded = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .next_back()
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.rsplit(',').next())
+            .and_then(|value| value.trim().parse().ok());
+        if forwarded.is_some() {
+            return forwarded;
+        }
+    }
+    connect_info.map(|ConnectInfo(addr)| addr.ip())
 }
 
 async fn login(
-    State(pool): State<SqlitePool>,
+    State(state): State<AppState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
     Json(payload): Json<LoginRequest>,
-) -> Result<impl IntoResponse, AppError> {
-    // Find user by email
-    let user = find_user_by_email(&pool, &payload.email)
+) -> Result<Response, AppError> {
+    let email = payload.email.trim();
+    let ip = client_ip(&state, connect_info, &headers);
+    let entry = AuditEntry {
+        email: Some(email),
+        ip,
+        user_agent: user_agent(&headers),
+        ..AuditEntry::new(AuditEvent::LoginFailed)
+    };
+    
+    // Refuse locked accounts and addresses before touching the password
+    if let Err(e) = state.login_throttle.check(email, ip) {
+        audit::record(&state.pool, AuditEntry { event: AuditEvent::LoginThrottled, ..entry }).await;
+        return Err(e);
+    }
+    
+    // Find user by email and verify password
+    let user = find_user_by_email(&state.pool, email).await?;
+    let verified = match user {
+        Some(ref user) => verify_password(&payload.password, &user.password_hash)?,
+        None => false,
+    };
+    
+    let user = match user {
+        Some(user) if verified => user,
+        user => {
+            let user_id = user.map(|u| u.id);
+            let event = match state.login_throttle.record_failure(email, ip) {
+                Some(_) => AuditEvent::LoginLocked,
+                None => AuditEvent::LoginFailed,
+            };
+            let detail = if user_id.is_some() { "wrong password" } else { "unknown email" };
+            audit::record(&state.pool, AuditEntry { event, user_id, detail: Some(detail), ..entry }).await;
+            return Err(AppError::BadRequest("Invalid email or password".to_string()));
+        }
+    };
+    
+    // Only someone with the password learns that the account is disabled
+    if user.disabled_at.is_some() {
+        audit::record(&state.pool, AuditEntry { user_id: Some(user.id), detail: Some("account disabled"), ..entry }).await;
+        return Err(AppError::Forbidden);
+    }
+    
+    // Hold back the session until the second factor is provided. Failures
+    // are only cleared then, so a known password can't reset the count.
+    if user.totp_enabled_at.is_some() {
+        let mfa_token = create_mfa_token(&state.config.auth, user.id)?;
+        return Ok(Json(MfaChallenge { mfa_required: true, mfa_token }).into_response());
+    }
+    
+    state.login_throttle.record_success(email);
+    audit::record(
+        &state.pool,
+        AuditEntry { event: AuditEvent::LoginSucceeded, user_id: Some(user.id), ..entry },
+    )
+    .await;
+    
+    issue_session(&state, user, &headers).await
+}
+
+/// Second login step for accounts with two-factor enabled
+async fn login_mfa(
+    State(state): State<AppState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    jar: CookieJar,
+    Json(payload): Json<MfaLoginRequest>,
+) -> Result<Response, AppError> {
+    let mfa_token = payload.mfa_token.as_deref()
+        .or_else(|| jar.get(MFA_TOKEN_COOKIE).map(|cookie| cookie.value()))
+        .ok_or(AppError::Unauthorized)?;
+    let claims = verify_mfa_token(&state.config.auth, mfa_token)?;
+    let user = find_user_by_id(&state.pool, claims.sub)
         .await?
-        .ok_or_else(|| AppError::BadRequest("Invalid email or password".to_string()))?;
+        .filter(|user| user.totp_enabled_at.is_some() && user.disabled_at.is_none())
+        .ok_or(AppError::Unauthorized)?;
+    
+    let ip = client_ip(&state, connect_info, &headers);
+    let entry = AuditEntry {
+        user_id: Some(user.id),
+        email: Some(&user.email),
+        ip,
+        user_agent: user_agent(&headers),
+        ..AuditEntry::new(AuditEvent::MfaFailed)
+    };
     
-    // Verify password
-    if !verify_password(&payload.password, &user.password_hash)? {
-        return Err(AppError::BadRequest("Invalid email or password".to_string()));
+    if let Err(e) = state.login_throttle.check(&user.email, ip) {
+        audit::record(&state.pool, AuditEntry { event: AuditEvent::LoginThrottled, ..entry }).await;
+        return Err(e);
     }
     
-    // Create JWT token
-    let token = create_token(user.id)?;
+    let factor = match verify_second_factor(&state.pool, &user, &payload.code).await? {
+        Some(factor) => factor,
+        None => {
+            let event = match state.login_throttle.record_failure(&user.email, ip) {
+                Some(_) => AuditEvent::LoginLocked,
+                None => AuditEvent::MfaFailed,
+            };
+            audit::record(&state.pool, AuditEntry { event, ..entry }).await;
+            return Err(AppError::BadRequest("Invalid authentication code".to_string()));
+        }
+    };
     
-    // Return response with cookie
-    let cookie = format!(
-        "token={}; HttpOnly; Secure; SameSite=Strict; Path=/; Max-Age=604800",
-        token
-    );
+    let detail = match factor {
+        SecondFactor::Totp => "totp",
+        SecondFactor::RecoveryCode => "recovery code",
+    };
+    state.login_throttle.record_success(&user.email);
+    audit::record(
+        &state.pool,
+        AuditEntry { event: AuditEvent::LoginSucceeded, detail: Some(detail), ..entry },
+    )
+    .await;
+    
+    let session = issue_session(&state, user, &headers).await?;
+    Ok((AppendHeaders([cleared_mfa_token_cookie()]), session).into_response())
+}
+
+/// Start a session with a short-lived access token and a refresh token
+async fn issue_session(state: &AppState, user: DbUser, headers: &HeaderMap) -> Result<Response, AppError> {
+    let session = start_session(&state.pool, &state.config.auth, user.id, user_agent(headers)).await?;
     
     Ok((
         StatusCode::OK,
-        [("Set-Cookie", cookie)],
+        AppendHeaders(session.cookies(&state.config.auth)),
         Json(AuthResponse { user: user.into() }),
-    ))
+    )
+        .into_response())
 }
 
 async fn signup(
-    State(pool): State<SqlitePool>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
     Json(payload): Json<SignupRequest>,
 ) -> Result<impl IntoResponse, AppError> {
+    let email = payload.email.trim();
+    if !is_valid_email(email) {
+        return Err(AppError::BadRequest("Please enter a valid email address".to_string()));
+    }
+    
     // Validate passwords match
     if payload.password != payload.confirm_password {
         return Err(AppError::BadRequest("Passwords do not match".to_string()));
     }
+    state.password_policy.check(&payload.password, &[email, &payload.username])?;
     
     // Check if email already exists
-    if find_user_by_email(&pool, &payload.email).await?.is_some() {
+    if find_user_by_email(&state.pool, email).await?.is_some() {
         return Err(AppError::BadRequest("Email already exists".to_string()));
     }
     
@@ -96,36 +273,106 @@ async fn signup(
     
     // Create user
     let user = create_user(
-        &pool,
-        &payload.email,
+        &state.pool,
+        email,
         &payload.username,
         &password_salt,
         &password_hash,
     )
     .await?;
     
-    // Create JWT token
-    let token = create_token(user.id)?;
+    // The account works right away; a failed email can be resent later
+    if let Err(e) = send_verification_email(&state, &user).await {
+        tracing::error!("Failed to send verification email to user {}: {}", user.id, e);
+    }
     
-    // Return response with cookie
-    let cookie = format!(
-        "token={}; HttpOnly; Secure; SameSite=Strict; Path=/; Max-Age=604800",
-        token
-    );
+    // Start a session with a short-lived access token and a refresh token
+    let session = start_session(&state.pool, &state.config.auth, user.id, user_agent(&headers)).await?;
     
     Ok((
         StatusCode::OK,
-        [("Set-Cookie", cookie)],
+        AppendHeaders(session.cookies(&state.config.auth)),
         Json(AuthResponse { user: user.into() }),
     ))
 }
 
-async fn logout() -> impl IntoResponse {
-    let cookie = "token=; HttpOnly; Secure; SameSite=Strict; Path=/; Max-Age=0";
+/// Basic shape check; ownership is proven by the verification email
+fn is_valid_email(email: &str) -> bool {
+    let Some((local, domain)) = email.split_once('@') else {
+        return false;
+    };
+    !local.is_empty()
+        && email.len() <= 254
+        && !email.chars().any(|c| c.is_whitespace() || c.is_control())
+        && !domain.contains('@')
+        && domain.split('.').count() >= 2
+        && domain.split('.').all(|label| !label.is_empty())
+}
+
+/// Token to send as `X-CSRF-Token` on requests that change state; reuses
+/// the browser's current token while it's valid for the current session
+async fn csrf_token(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Response {
+    let session_id = csrf::request_session(&state.config.auth, &jar);
+    if let Some(token) = jar.get(CSRF_COOKIE).map(|c| c.value()) {
+        if csrf::is_valid_token(&state.config.auth, token, session_id.as_deref()) {
+            return Json(serde_json::json!({ "csrfToken": token })).into_response();
+        }
+    }
     
+    let token = csrf::issue_token(&state.config.auth, session_id.as_deref());
     (
+        AppendHeaders([csrf::cookie(&token)]),
+        Json(serde_json::json!({ "csrfToken": token })),
+    )
+        .into_response()
+}
+
+async fn refresh(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AppError> {
+    let refresh_token = jar
+        .get(REFRESH_COOKIE)
+        .map(|cookie| cookie.value())
+        .ok_or(AppError::Unauthorized)?;
+    
+    let session = refresh_session(&state.pool, &state.config.auth, &state.revoked_tokens, refresh_token).await?;
+    
+    Ok((
         StatusCode::OK,
-        [("Set-Cookie", cookie)],
+        AppendHeaders(session.cookies(&state.config.auth)),
         Json(serde_json::json!({ "success": true })),
-    )
+    ))
+}
+
+/// Revoke the current session; works with an expired access token as long
+/// as the refresh cookie is present
+async fn logout(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AppError> {
+    let from_refresh = match jar.get(REFRESH_COOKIE).and_then(|c| c.value().split_once('.')) {
+        Some((session_id, _)) => find_session(&state.pool, session_id)
+            .await?
+            .map(|session| (session.user_id, session.id)),
+        None => None,
+    };
+    let session = from_refresh.or_else(|| {
+        let token = jar.get(ACCESS_COOKIE)?;
+        let claims = verify_token(&state.config.auth, token.value(), &state.revoked_tokens).ok()?;
+        Some((claims.sub, claims.sid))
+    });
+    
+    if let Some((user_id, session_id)) = session {
+        end_session(&state.pool, &state.revoked_tokens, user_id, &session_id).await?;
+    }
+    
+    Ok((
+        StatusCode::OK,
+        AppendHeaders(cleared_cookies()),
+        Json(serde_json::json!({ "success": true })),
+    ))
 }
\ No newline at end of file
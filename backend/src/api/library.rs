@@ -1,16 +1,20 @@
 use axum::{
     extract::{Path, State},
     http::StatusCode,
+    middleware,
     response::IntoResponse,
-    routing::{get, delete, patch},
+    routing::{get, delete},
     Json, Router,
 };
 use serde::Serialize;
 use sqlx::SqlitePool;
 use crate::{
+    auth::api_keys::Scope,
+    auth::middleware::{auth_with_scope, AuthUser},
     db::queries::routes::{get_user_routes, get_route_by_id, delete_route_by_id, update_route_name},
     error::AppError,
     models::request::UpdateRouteRequest,
+    state::AppState,
 };
 
 #[derive(Debug, Serialize)]
@@ -31,30 +35,33 @@ pub struct SavedRoute {
     pub match_percentage: f64,
 }
 
-pub fn routes() -> Router<SqlitePool> {
-    Router::new()
+pub fn routes(state: AppState) -> Router<AppState> {
+    let read = Router::new()
         .route("/library", get(get_library))
         .route("/library/:id", get(get_route))
-        .route("/library/:id", delete(delete_route))
-        .route("/library/:id", patch(update_route))
-        // TODO: Add authentication middleware when auth context is implemented
+        .route_layer(middleware::from_fn_with_state((state.clone(), Scope::LibraryRead), auth_with_scope));
+    
+    let write = Router::new()
+        .route("/library/:id", delete(delete_route).patch(update_route))
+        .route_layer(middleware::from_fn_with_state((state, Scope::LibraryWrite), auth_with_scope));
+    
+    read.merge(write)
 }
 
 async fn get_library(
     State(pool): State<SqlitePool>,
+    user: AuthUser,
 ) -> Result<impl IntoResponse, AppError> {
-    // TODO: Get user ID from auth context
-    let user_id = 1; // Placeholder
-    
-    let routes = get_user_routes(&pool, user_id).await?;
+    let routes = get_user_routes(&pool, user.id).await?;
     Ok(Json(routes))
 }
 
 async fn get_route(
     State(pool): State<SqlitePool>,
+    user: AuthUser,
     Path(id): Path<i64>,
 ) -> Result<impl IntoResponse, AppError> {
-    let route = get_route_by_id(&pool, id)
+    let route = get_route_by_id(&pool, id, user.id)
         .await?
         .ok_or_else(|| AppError::NotFound("Route not found".to_string()))?;
     
@@ -62,21 +69,29 @@ async fn get_route(
 }
 
 async fn delete_route(
-    State(pool): State<SqlitePool>,
+    State(state): State<AppState>,
+    user: AuthUser,
     Path(id): Path<i64>,
 ) -> Result<impl IntoResponse, AppError> {
-    delete_route_by_id(&pool, id).await?;
+    if !delete_route_by_id(&state.pool, id, user.id).await? {
+        return Err(AppError::NotFound("Route not found".to_string()));
+    }
+    state.spatial_index.remove(id);
     Ok(StatusCode::NO_CONTENT)
 }
 
 async fn update_route(
-    State(pool): State<SqlitePool>,
+    State(state): State<AppState>,
+    user: AuthUser,
     Path(id): Path<i64>,
     Json(payload): Json<UpdateRouteRequest>,
 ) -> Result<impl IntoResponse, AppError> {
     // Use the update_route_name function if name is provided
     if let Some(ref new_name) = payload.name {
-        update_route_name(&pool, id, new_name).await?;
+        if !update_route_name(&state.pool, id, user.id, new_name).await? {
+            return Err(AppError::NotFound("Route not found".to_string()));
+        }
+        state.spatial_index.rename(id, new_name);
     }
     
     Ok(Json(serde_json::json!({
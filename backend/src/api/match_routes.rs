@@ -1,19 +1,30 @@
 use axum::{
     extract::{DefaultBodyLimit, Multipart, State},
+    middleware,
     response::IntoResponse,
     routing::post,
     Json, Router,
 };
+use geo::LineString;
 use serde::Serialize;
-use sqlx::SqlitePool;
 use tower::ServiceBuilder;
 use tower_http::limit::RequestBodyLimitLayer;
 use crate::{
+    auth::api_keys::Scope,
+    auth::middleware::{auth_with_scope, AuthUser},
     error::AppError,
-    utils::gpx_parser::parse_gpx,
+    utils::gpx_parser::{parse_gpx_selection, ElevationGapFill, GpxSelection, GpxSource, SegmentGap},
     utils::gpx_minifier::minify_gpx,
-    matching::engine::{MatchingEngine, calculate_distance},
-    utils::elevation::calculate_elevation_stats,
+    utils::input_format::{parse_route_file, InputFormat},
+    matching::alignment::ShapeAlignment,
+    matching::engine::{MatchingConfig, MatchingEngine, ShapeMetric, calculate_distance},
+    matching::road_class::{RoadClassProfile, SafetyMode},
+    matching::twistiness::Twistiness,
+    state::AppState,
+    utils::elevation::{
+        calculate_elevation_stats, correct_elevation_profile, interpolate_elevation_profile,
+        ElevationCorrection,
+    },
 };
 
 #[derive(Debug, Serialize)]
@@ -29,9 +40,18 @@ pub struct RouteMatch {
     pub match_percentage: f64,
     #[serde(rename = "curveScore")]
     pub curve_score: f64,
+    pub twistiness: Twistiness,
     pub geometry: serde_json::Value,
     #[serde(rename = "elevationProfile")]
     pub elevation_profile: Vec<f64>,
+    #[serde(rename = "roadClasses", skip_serializing_if = "Option::is_none")]
+    pub road_classes: Option<RoadClassProfile>,
+    /// Only with the Procrustes shape metric
+    #[serde(rename = "shapeAlignment", skip_serializing_if = "Option::is_none")]
+    pub shape_alignment: Option<ShapeAlignment>,
+    /// The uploaded route moved, rotated and scaled onto this match
+    #[serde(rename = "alignedInput", skip_serializing_if = "Option::is_none")]
+    pub aligned_input: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Serialize)]
@@ -39,6 +59,8 @@ pub struct MatchResponse {
     pub matches: Vec<RouteMatch>,
     #[serde(rename = "inputRoute")]
     pub input_route: InputRouteInfo,
+    #[serde(rename = "effectiveConfig")]
+    pub effective_config: MatchingConfig,
 }
 
 #[derive(Debug, Serialize)]
@@ -47,31 +69,52 @@ pub struct InputRouteInfo {
     pub distance: f64,
     #[serde(rename = "elevationGain")]
     pub elevation_gain: f64,
+    pub twistiness: Twistiness,
     pub geometry: serde_json::Value,
     #[serde(rename = "elevationProfile")]
     pub elevation_profile: Vec<f64>,
+    #[serde(rename = "elevationSource")]
+    pub elevation_source: ElevationCorrection,
+    #[serde(rename = "rawElevationGain")]
+    pub raw_elevation_gain: f64,
+    #[serde(rename = "correctedElevationGain", skip_serializing_if = "Option::is_none")]
+    pub corrected_elevation_gain: Option<f64>,
+    #[serde(rename = "filledElevationPoints")]
+    pub filled_elevation_points: usize,
+    /// Detected upload format
+    pub format: InputFormat,
+    /// Tracks, routes and waypoint lists found in the file
+    #[serde(rename = "gpxSources")]
+    pub gpx_sources: Vec<GpxSource>,
+    #[serde(rename = "segmentGaps")]
+    pub segment_gaps: Vec<SegmentGap>,
 }
 
-pub fn routes() -> Router<SqlitePool> {
+pub fn routes(state: AppState) -> Router<AppState> {
+    let max_upload_bytes = state.config.server.max_upload_bytes;
+    
     Router::new()
         .route("/match", post(match_routes))
+        .route_layer(middleware::from_fn_with_state((state, Scope::MatchRun), auth_with_scope))
         .layer(
             ServiceBuilder::new()
-                .layer(DefaultBodyLimit::max(50 * 1024 * 1024))
-                .layer(RequestBodyLimitLayer::new(50 * 1024 * 1024))
+                .layer(DefaultBodyLimit::max(max_upload_bytes))
+                .layer(RequestBodyLimitLayer::new(max_upload_bytes))
         )
 }
 
 async fn match_routes(
-    State(pool): State<SqlitePool>,
+    State(state): State<AppState>,
+    user: AuthUser,
     mut multipart: Multipart,
 ) -> Result<impl IntoResponse, AppError> {
     tracing::info!("Match endpoint called");
     
     let mut gpx_data = Vec::new();
-    let mut distance_flexibility = 10.0;
-    let mut elevation_flexibility = 10.0;
-    let mut _safety_mode = "Moderate".to_string();
+    let mut config = state.config.matching.matching_config();
+    let mut elevation_correction = ElevationCorrection::default();
+    let mut gap_fill = ElevationGapFill::default();
+    let mut gpx_selection = GpxSelection::default();
     let mut search_area: Option<serde_json::Value> = None;
     let mut original_filename = String::new();
     
@@ -95,18 +138,88 @@ async fn match_routes(
                     file_data.extend_from_slice(&chunk);
                 }
                 gpx_data = file_data;
-                tracing::info!("GPX file received: {} bytes, filename: {}", gpx_data.len(), original_filename);
+                tracing::info!("Route file received: {} bytes, filename: {}", gpx_data.len(), original_filename);
             }
             "distanceFlexibility" => {
                 let text = field.text().await.unwrap_or_default();
-                distance_flexibility = text.parse().unwrap_or(10.0);
+                config.distance_flexibility = parse_field(&name, &text)?;
             }
             "elevationFlexibility" => {
                 let text = field.text().await.unwrap_or_default();
-                elevation_flexibility = text.parse().unwrap_or(10.0);
+                config.elevation_flexibility = parse_field(&name, &text)?;
+            }
+            "shapeImportance" => {
+                let text = field.text().await.unwrap_or_default();
+                config.shape_importance = parse_field(&name, &text)?;
+            }
+            "shapeMetric" => {
+                let text = field.text().await.unwrap_or_default();
+                config.shape_metric = ShapeMetric::parse(&text)
+                    .ok_or_else(|| AppError::BadRequest(format!("Invalid shape metric: {}", text)))?;
+            }
+            "shapeScaling" => {
+                let text = field.text().await.unwrap_or_default();
+                config.shape_scaling = parse_field(&name, &text)?;
+            }
+            "turnsImportance" => {
+                let text = field.text().await.unwrap_or_default();
+                config.turns_importance = parse_field(&name, &text)?;
+            }
+            "curvatureImportance" => {
+                let text = field.text().await.unwrap_or_default();
+                config.curvature_importance = parse_field(&name, &text)?;
+            }
+            "minCurvaturePerKm" => {
+                let text = field.text().await.unwrap_or_default();
+                config.min_curvature_per_km = Some(parse_field(&name, &text)?);
+            }
+            "maxCurvaturePerKm" => {
+                let text = field.text().await.unwrap_or_default();
+                config.max_curvature_per_km = Some(parse_field(&name, &text)?);
+            }
+            "elevationImportance" => {
+                let text = field.text().await.unwrap_or_default();
+                config.elevation_importance = parse_field(&name, &text)?;
+            }
+            "granularityMeters" => {
+                let text = field.text().await.unwrap_or_default();
+                config.granularity_meters = parse_field(&name, &text)?;
+            }
+            "minMatchPercentage" => {
+                let text = field.text().await.unwrap_or_default();
+                config.min_match_percentage = parse_field(&name, &text)?;
+            }
+            "maxResults" => {
+                let text = field.text().await.unwrap_or_default();
+                config.max_results = parse_field(&name, &text)?;
+            }
+            "matchElevationLoss" => {
+                let text = field.text().await.unwrap_or_default();
+                config.match_elevation_loss = parse_field(&name, &text)?;
+            }
+            "matchAltitudeRange" => {
+                let text = field.text().await.unwrap_or_default();
+                config.match_altitude_range = parse_field(&name, &text)?;
             }
             "safetyMode" => {
-                _safety_mode = field.text().await.unwrap_or_else(|_| "Moderate".to_string());
+                let text = field.text().await.unwrap_or_default();
+                config.safety_mode = SafetyMode::parse(&text)
+                    .ok_or_else(|| AppError::BadRequest(format!("Invalid safety mode: {}", text)))?;
+            }
+            "elevationSource" => {
+                let text = field.text().await.unwrap_or_default();
+                elevation_correction = ElevationCorrection::parse(&text)
+                    .ok_or_else(|| AppError::BadRequest(format!("Invalid elevation source: {}", text)))?;
+            }
+            "gpxSource" => {
+                let text = field.text().await.unwrap_or_default();
+                gpx_selection = GpxSelection::parse(&text)
+                    .ok_or_else(|| AppError::BadRequest(format!("Invalid GPX source: {}", text)))?;
+            }
+            "elevationGapFill" => {
+                let text = field.text().await.unwrap_or_default();
+                gap_fill = ElevationGapFill::parse(&text)
+                    .ok_or_else(|| AppError::BadRequest(format!("Invalid elevation gap fill: {}", text)))?;
             }
             "searchArea" => {
                 let json_str = field.text().await.unwrap_or_default();
@@ -119,7 +232,30 @@ async fn match_routes(
     }
     
     if gpx_data.is_empty() {
-        return Err(AppError::BadRequest("No GPX file provided".to_string()));
+        return Err(AppError::BadRequest("No route file provided".to_string()));
+    }
+    
+    config.validate()?;
+    
+    if elevation_correction != ElevationCorrection::Raw && state.dem.is_none() {
+        return Err(AppError::BadRequest(
+            "DEM elevation correction requested but no DEM is configured".to_string(),
+        ));
+    }
+    
+    if gap_fill == ElevationGapFill::Dem && state.dem.is_none() {
+        return Err(AppError::BadRequest(
+            "DEM gap filling requested but no DEM is configured".to_string(),
+        ));
+    }
+    
+    // Strict leaves out routes it can't classify, which without an OSM
+    // extract would be all of them
+    if !config.safety_mode.allows_unclassified() && state.road_network.is_none() {
+        return Err(AppError::BadRequest(format!(
+            "{:?} safety mode requested but no OSM extract is configured",
+            config.safety_mode
+        )));
     }
     
     let search_bounds = search_area
@@ -133,28 +269,94 @@ async fn match_routes(
         })
         .ok_or_else(|| AppError::BadRequest("Invalid search area".to_string()))?;
     
-    // Convert to string
-    let gpx_string = String::from_utf8(gpx_data)
-        .map_err(|_| AppError::BadRequest("Invalid GPX file encoding".to_string()))?;
+    // GPX, TCX, KML, GeoJSON or FIT
+    let input_format = InputFormat::detect(&gpx_data)?;
+    tracing::info!("Detected {:?} upload", input_format);
+    
+    let mut parsed_gpx = if input_format == InputFormat::Gpx {
+        // Convert to string
+        let gpx_string = String::from_utf8(gpx_data)
+            .map_err(|_| AppError::BadRequest("Invalid GPX file encoding".to_string()))?;
+        
+        // Try to minify the GPX
+        let gpx_to_parse = match minify_gpx(&gpx_string) {
+            Ok(minified) => {
+                tracing::info!("Successfully minified GPX");
+                minified
+            }
+            Err(e) => {
+                tracing::warn!("Failed to minify GPX: {}, using original", e);
+                gpx_string
+            }
+        };
+        
+        parse_gpx_selection(&gpx_to_parse, gpx_selection)?
+    } else {
+        parse_route_file(&gpx_data, input_format, gpx_selection)?
+    };
     
-    // Try to minify the GPX
-    let gpx_to_parse = match minify_gpx(&gpx_string) {
-        Ok(minified) => {
-            tracing::info!("Successfully minified GPX");
-            minified
+    // The DEM lookups below run inline, so read the tiles they need first
+    if let Some(dem) = &state.dem {
+        if gap_fill == ElevationGapFill::Dem
+            || parsed_gpx.elevation_profile.is_empty()
+            || elevation_correction != ElevationCorrection::Raw
+        {
+            dem.preload(&parsed_gpx.geometry).await;
         }
-        Err(e) => {
-            tracing::warn!("Failed to minify GPX: {}, using original", e);
-            gpx_string
+    }
+    
+    // Fill points recorded without <ele> so the profile stays aligned
+    let filled_elevation_points = parsed_gpx.fill_elevation_gaps(gap_fill, state.dem.as_deref())?;
+    
+    // Tracks recorded without elevation get it from the local DEM
+    if parsed_gpx.elevation_profile.is_empty() {
+        match state.dem.as_deref() {
+            Some(dem) => match interpolate_elevation_profile(&parsed_gpx.geometry, dem) {
+                Some(profile) => {
+                    tracing::info!("Filled {} elevations from DEM", profile.len());
+                    parsed_gpx.elevation_profile = profile;
+                }
+                None => tracing::warn!("Track has no elevation and lies outside DEM coverage"),
+            },
+            None => tracing::warn!("Track has no elevation and no DEM is configured"),
         }
-    };
+    }
     
-    // Parse the GPX
-    let parsed_gpx = parse_gpx(&gpx_to_parse)?;
+    // Optionally replace or blend recorded elevation with DEM values
+    let raw_elevation_gain = calculate_elevation_stats(&parsed_gpx.elevation_profile).total_gain;
+    let mut elevation_source = ElevationCorrection::Raw;
+    let mut corrected_elevation_gain = None;
+    
+    let dem_for_correction = state.dem.as_deref()
+        .filter(|_| elevation_correction != ElevationCorrection::Raw);
+    if let Some(dem) = dem_for_correction {
+        if parsed_gpx.elevation_profile.len() == parsed_gpx.geometry.0.len() {
+            let dem_samples = dem.sample_line(&parsed_gpx.geometry);
+            parsed_gpx.elevation_profile = correct_elevation_profile(
+                &parsed_gpx.elevation_profile,
+                &dem_samples,
+                elevation_correction,
+            );
+            
+            let corrected_gain = calculate_elevation_stats(&parsed_gpx.elevation_profile).total_gain;
+            tracing::info!(
+                "Elevation corrected ({:?}): gain {:.0}m -> {:.0}m",
+                elevation_correction, raw_elevation_gain, corrected_gain
+            );
+            elevation_source = elevation_correction;
+            corrected_elevation_gain = Some(corrected_gain);
+        } else {
+            tracing::warn!("Elevation profile is not aligned with geometry, skipping DEM correction");
+        }
+    }
     
     // Use the parsed GPX name or fallback to filename
     let route_name = parsed_gpx.name.clone()
-        .unwrap_or_else(|| original_filename.replace(".gpx", ""));
+        .unwrap_or_else(|| {
+            std::path::Path::new(&original_filename)
+                .file_stem()
+                .map_or_else(|| original_filename.clone(), |stem| stem.to_string_lossy().into_owned())
+        });
     
     // Calculate route statistics
     let route_distance = calculate_distance(&parsed_gpx.geometry);
@@ -165,22 +367,23 @@ async fn match_routes(
         route_name, route_distance, elevation_stats.total_gain, parsed_gpx.geometry.0.len()
     );
     
-    // Create matching engine with database connection
-    let engine = MatchingEngine::from_database(&pool).await
-        .map_err(|e| AppError::MatchingError(format!("Failed to initialize matching engine: {}", e)))?;
+    // Match against a consistent snapshot of the shared route index
+    let engine = MatchingEngine::from_index(state.spatial_index.snapshot()).for_user(user.id);
+    
+    if config.safety_mode != SafetyMode::None && state.road_network.is_none() {
+        tracing::warn!("Safety mode {:?} requested but no OSM extract is configured", config.safety_mode);
+    }
     
-    let match_results = engine.find_matches(
+    let match_results = engine.find_matches_with_config(
         &parsed_gpx.geometry,
         &parsed_gpx.elevation_profile,
         search_bounds,
-        distance_flexibility,
-        elevation_flexibility,
+        config.clone(),
     )?;
     
     // Convert matching results to API response format
     let matches: Vec<RouteMatch> = match_results
         .into_iter()
-        .take(20)
         .map(|result| RouteMatch {
             id: result.id,
             name: result.name,
@@ -189,13 +392,12 @@ async fn match_routes(
             gain_per_km: result.gain_per_km,
             match_percentage: result.match_percentage,
             curve_score: result.curve_score,
-            geometry: serde_json::json!({
-                "type": "LineString",
-                "coordinates": result.geometry.0.iter()
-                    .map(|coord| [coord.x, coord.y])
-                    .collect::<Vec<_>>()
-            }),
+            twistiness: result.twistiness,
+            geometry: line_geojson(&result.geometry),
             elevation_profile: result.elevation_profile,
+            road_classes: result.road_classes,
+            shape_alignment: result.shape_alignment,
+            aligned_input: result.aligned_input.as_ref().map(line_geojson),
         })
         .collect();
     
@@ -204,6 +406,7 @@ async fn match_routes(
         name: route_name,
         distance: route_distance,
         elevation_gain: elevation_stats.total_gain,
+        twistiness: Twistiness::of_route(&parsed_gpx.geometry),
         geometry: serde_json::json!({
             "type": "LineString",
             "coordinates": parsed_gpx.geometry.0.iter()
@@ -212,8 +415,34 @@ async fn match_routes(
                 .collect::<Vec<_>>()
         }),
         elevation_profile: parsed_gpx.elevation_profile.clone(),
+        elevation_source,
+        raw_elevation_gain,
+        corrected_elevation_gain,
+        filled_elevation_points,
+        format: input_format,
+        gpx_sources: parsed_gpx.sources.clone(),
+        segment_gaps: parsed_gpx.segment_gaps.clone(),
     };
     
     tracing::info!("Returning {} matches", matches.len());
-    Ok(Json(MatchResponse { matches, input_route }))
+    Ok(Json(MatchResponse {
+        matches,
+        input_route,
+        effective_config: config,
+    }))
+}
+
+fn line_geojson(line: &LineString<f64>) -> serde_json::Value {
+    serde_json::json!({
+        "type": "LineString",
+        "coordinates": line.0.iter()
+            .map(|coord| [coord.x, coord.y])
+            .collect::<Vec<_>>()
+    })
+}
+
+fn parse_field<T: std::str::FromStr>(name: &str, text: &str) -> Result<T, AppError> {
+    text.trim()
+        .parse()
+        .map_err(|_| AppError::BadRequest(format!("Invalid value for {}: {}", name, text)))
 }
\ No newline at end of file
@@ -0,0 +1,182 @@
+use axum::{
+    extract::State,
+    middleware,
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use crate::{
+    auth::{
+        audit::{self, AuditEntry, AuditEvent},
+        mfa::{issue_recovery_codes, verify_second_factor, verify_totp},
+        middleware::{auth, AuthUser},
+        password::verify_password,
+        totp::{generate_secret, otpauth_uri},
+    },
+    db::models::DbUser,
+    db::queries::{
+        recovery_codes::{count_unused_recovery_codes, delete_recovery_codes},
+        users::{disable_totp, enable_totp, find_user_by_id, set_pending_totp_secret},
+    },
+    error::AppError,
+    state::AppState,
+};
+
+#[derive(Debug, Serialize)]
+pub struct MfaStatus {
+    pub enabled: bool,
+    #[serde(rename = "recoveryCodesRemaining")]
+    pub recovery_codes_remaining: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TotpSetup {
+    pub secret: String,
+    #[serde(rename = "otpauthUri")]
+    pub otpauth_uri: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecoveryCodes {
+    #[serde(rename = "recoveryCodes")]
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CodeRequest {
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DisableMfaRequest {
+    pub password: String,
+    /// A TOTP code or a recovery code
+    pub code: String,
+}
+
+pub fn routes(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/mfa", get(get_status))
+        .route("/mfa/totp/setup", post(setup_totp))
+        .route("/mfa/totp/activate", post(activate_totp))
+        .route("/mfa/totp/disable", post(disable))
+        .route("/mfa/recovery-codes", post(regenerate_recovery_codes))
+        .route_layer(middleware::from_fn_with_state(state, auth))
+}
+
+async fn current_user(state: &AppState, user: &AuthUser) -> Result<DbUser, AppError> {
+    find_user_by_id(&state.pool, user.id)
+        .await?
+        .ok_or(AppError::Unauthorized)
+}
+
+async fn get_status(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let user = current_user(&state, &user).await?;
+
+    Ok(Json(MfaStatus {
+        enabled: user.totp_enabled_at.is_some(),
+        recovery_codes_remaining: count_unused_recovery_codes(&state.pool, user.id).await?,
+    }))
+}
+
+/// Start enrolment with a new secret; two-factor is enforced only once a
+/// code from it has been verified
+async fn setup_totp(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let user = current_user(&state, &user).await?;
+    if user.totp_enabled_at.is_some() {
+        return Err(AppError::BadRequest("Two-factor authentication is already enabled".to_string()));
+    }
+
+    let secret = generate_secret();
+    set_pending_totp_secret(&state.pool, user.id, &secret).await?;
+
+    Ok(Json(TotpSetup {
+        otpauth_uri: otpauth_uri(&state.config.auth.totp_issuer, &user.email, &secret),
+        secret,
+    }))
+}
+
+async fn activate_totp(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Json(payload): Json<CodeRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let user = current_user(&state, &user).await?;
+    if user.totp_enabled_at.is_some() {
+        return Err(AppError::BadRequest("Two-factor authentication is already enabled".to_string()));
+    }
+    let secret = user.totp_secret.as_deref()
+        .ok_or_else(|| AppError::BadRequest("Start two-factor setup first".to_string()))?;
+
+    if !verify_totp(&state.pool, &user, secret, &payload.code).await? {
+        return Err(AppError::BadRequest("Invalid authentication code".to_string()));
+    }
+
+    enable_totp(&state.pool, user.id).await?;
+    let recovery_codes = issue_recovery_codes(&state.pool, user.id).await?;
+
+    audit::record(
+        &state.pool,
+        AuditEntry { user_id: Some(user.id), email: Some(&user.email), ..AuditEntry::new(AuditEvent::MfaEnabled) },
+    )
+    .await;
+
+    Ok(Json(RecoveryCodes { recovery_codes }))
+}
+
+/// Turning two-factor off needs both the password and a second factor
+async fn disable(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Json(payload): Json<DisableMfaRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let user = current_user(&state, &user).await?;
+    if user.totp_enabled_at.is_none() {
+        return Err(AppError::BadRequest("Two-factor authentication is not enabled".to_string()));
+    }
+
+    if !verify_password(&payload.password, &user.password_hash)? {
+        return Err(AppError::BadRequest("Incorrect password".to_string()));
+    }
+    if verify_second_factor(&state.pool, &user, &payload.code).await?.is_none() {
+        return Err(AppError::BadRequest("Invalid authentication code".to_string()));
+    }
+
+    disable_totp(&state.pool, user.id).await?;
+    delete_recovery_codes(&state.pool, user.id).await?;
+
+    audit::record(
+        &state.pool,
+        AuditEntry { user_id: Some(user.id), email: Some(&user.email), ..AuditEntry::new(AuditEvent::MfaDisabled) },
+    )
+    .await;
+
+    Ok(Json(serde_json::json!({ "success": true })))
+}
+
+/// Replace all recovery codes; needs a current TOTP code
+async fn regenerate_recovery_codes(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Json(payload): Json<CodeRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let user = current_user(&state, &user).await?;
+    let secret = match (&user.totp_enabled_at, &user.totp_secret) {
+        (Some(_), Some(secret)) => secret.clone(),
+        _ => return Err(AppError::BadRequest("Two-factor authentication is not enabled".to_string())),
+    };
+
+    if !verify_totp(&state.pool, &user, &secret, &payload.code).await? {
+        return Err(AppError::BadRequest("Invalid authentication code".to_string()));
+    }
+
+    let recovery_codes = issue_recovery_codes(&state.pool, user.id).await?;
+    Ok(Json(RecoveryCodes { recovery_codes }))
+}
@@ -1,15 +1,31 @@
 use axum::Router;
-use sqlx::SqlitePool;
+use crate::state::AppState;
 
+mod account;
+mod admin;
+mod api_keys;
 mod auth;
 mod routes;
 mod library;
 mod match_routes;
+mod mfa;
+mod oidc;
+mod public_routes;
+mod sessions;
 
-pub fn routes() -> Router<SqlitePool> {
+/// All API routes; `state` is needed up front to build the auth middleware
+/// and the upload limits
+pub fn routes(state: AppState) -> Router<AppState> {
     Router::new()
         .merge(auth::routes())
-        .merge(routes::routes())
-        .merge(library::routes())
-        .merge(match_routes::routes())
+        .merge(account::routes(state.clone()))
+        .merge(admin::routes(state.clone()))
+        .merge(api_keys::routes(state.clone()))
+        .merge(mfa::routes(state.clone()))
+        .merge(oidc::routes())
+        .merge(routes::routes(state.clone()))
+        .merge(library::routes(state.clone()))
+        .merge(public_routes::routes(state.clone()))
+        .merge(match_routes::routes(state.clone()))
+        .merge(sessions::routes(state))
 }
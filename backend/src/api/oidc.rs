@@ -0,0 +1,167 @@
+use axum::{
+    extract::{ConnectInfo, Path, Query, State},
+    http::HeaderMap,
+    response::{AppendHeaders, IntoResponse, Redirect, Response},
+    routing::get,
+    Json, Router,
+};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use crate::{
+    auth::{
+        audit::{self, AuditEntry, AuditEvent},
+        jwt::{create_mfa_token, mfa_token_cookie},
+        oidc::{find_or_create_user, PendingLogin, OIDC_LOGIN_COOKIE},
+        session::start_session,
+    },
+    error::AppError,
+    state::AppState,
+};
+use super::auth::{client_ip, user_agent};
+
+#[derive(Debug, Serialize)]
+pub struct ProviderInfo {
+    pub id: String,
+    #[serde(rename = "displayName")]
+    pub display_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/oidc/providers", get(list_providers))
+        .route("/oidc/:provider/login", get(start_login))
+        .route("/oidc/:provider/callback", get(callback))
+}
+
+async fn list_providers(State(state): State<AppState>) -> impl IntoResponse {
+    let providers: Vec<ProviderInfo> = state.oidc.iter()
+        .map(|provider| ProviderInfo {
+            id: provider.id.clone(),
+            display_name: provider.config.display_name.clone(),
+        })
+        .collect();
+
+    Json(providers)
+}
+
+/// Send the browser to the provider, remembering state, nonce and PKCE
+/// verifier in a cookie for the callback
+async fn start_login(
+    State(state): State<AppState>,
+    Path(provider): Path<String>,
+) -> Result<Response, AppError> {
+    let provider = state.oidc
+        .get(&provider)
+        .ok_or_else(|| AppError::NotFound("Unknown identity provider".to_string()))?;
+
+    let pending = PendingLogin::new(&provider.id);
+    let url = provider.authorization_url(&pending).await?;
+    let sealed = pending.seal(&state.config.auth)?;
+
+    Ok((AppendHeaders([PendingLogin::cookie(&sealed)]), Redirect::to(&url)).into_response())
+}
+
+/// The provider redirects here after sign-in. Failures send the browser
+/// back to the login page rather than showing a JSON error.
+async fn callback(
+    State(state): State<AppState>,
+    Path(provider): Path<String>,
+    Query(query): Query<CallbackQuery>,
+    jar: CookieJar,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+) -> Response {
+    match complete_login(&state, &provider, query, &jar, connect_info, &headers).await {
+        Ok(response) => response,
+        Err(e) => {
+            tracing::warn!("Sign-in with {} failed: {}", provider, e);
+            let detail = format!("oidc:{}", provider);
+            audit::record(
+                &state.pool,
+                AuditEntry {
+                    ip: client_ip(&state, connect_info, &headers),
+                    user_agent: user_agent(&headers),
+                    detail: Some(&detail),
+                    ..AuditEntry::new(AuditEvent::LoginFailed)
+                },
+            )
+            .await;
+
+            let url = format!("{}/login?error=oidc", state.config.oidc.frontend_url);
+            (AppendHeaders([PendingLogin::cleared_cookie()]), Redirect::to(&url)).into_response()
+        }
+    }
+}
+
+async fn complete_login(
+    state: &AppState,
+    provider: &str,
+    query: CallbackQuery,
+    jar: &CookieJar,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: &HeaderMap,
+) -> Result<Response, AppError> {
+    if let Some(error) = query.error {
+        return Err(AppError::BadRequest(format!("Provider returned {}", error)));
+    }
+
+    let provider = state.oidc
+        .get(provider)
+        .ok_or_else(|| AppError::NotFound("Unknown identity provider".to_string()))?;
+
+    // The callback must come back to the browser that started the sign-in
+    let sealed = jar.get(OIDC_LOGIN_COOKIE).ok_or(AppError::Unauthorized)?;
+    let pending = PendingLogin::open(&state.config.auth, sealed.value())?;
+    if pending.provider != provider.id || query.state.as_deref() != Some(pending.state.as_str()) {
+        return Err(AppError::Unauthorized);
+    }
+
+    let code = query.code.ok_or_else(|| AppError::BadRequest("Missing authorization code".to_string()))?;
+    let claims = provider.authenticate(&code, &pending).await?;
+    let user = find_or_create_user(&state.pool, &provider.id, &claims).await?;
+    if user.disabled_at.is_some() {
+        return Err(AppError::Forbidden);
+    }
+    let frontend = &state.config.oidc.frontend_url;
+
+    // Accounts with two-factor still need a code; the login page asks for
+    // it and the pending token follows in a cookie
+    if user.totp_enabled_at.is_some() {
+        let mfa_token = create_mfa_token(&state.config.auth, user.id)?;
+        let cookies = [mfa_token_cookie(&state.config.auth, &mfa_token), PendingLogin::cleared_cookie()];
+        let url = format!("{}/login?mfa=required", frontend);
+        return Ok((AppendHeaders(cookies), Redirect::to(&url)).into_response());
+    }
+
+    let detail = format!("oidc:{}", provider.id);
+    audit::record(
+        &state.pool,
+        AuditEntry {
+            user_id: Some(user.id),
+            email: Some(&user.email),
+            ip: client_ip(state, connect_info, headers),
+            user_agent: user_agent(headers),
+            detail: Some(&detail),
+            ..AuditEntry::new(AuditEvent::LoginSucceeded)
+        },
+    )
+    .await;
+
+    let session = start_session(&state.pool, &state.config.auth, user.id, user_agent(headers)).await?;
+    let [access, refresh] = session.cookies(&state.config.auth);
+
+    // The login page fetches the user with the new session and moves on
+    Ok((
+        AppendHeaders([access, refresh, PendingLogin::cleared_cookie()]),
+        Redirect::to(&format!("{}/login?oidc=success", frontend)),
+    )
+        .into_response())
+}
@@ -0,0 +1,137 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    middleware,
+    response::IntoResponse,
+    routing::{get, patch, post},
+    Json, Router,
+};
+use crate::{
+    auth::{
+        api_keys::Scope,
+        audit::{self, AuditEntry, AuditEvent},
+        middleware::{auth, auth_with_scope, require_permission, AuthUser},
+        roles::Permission,
+    },
+    db::queries::{
+        public_routes::{
+            delete_public_route, get_public_route, get_public_routes, publish_route,
+            rename_public_route, unpublish_route,
+        },
+        routes::get_route_by_id,
+    },
+    error::AppError,
+    models::{request::UpdateRouteRequest, route::SavedRoute},
+    state::AppState,
+};
+
+/// Anyone signed in can browse the public corpus; curators maintain it
+pub fn routes(state: AppState) -> Router<AppState> {
+    let browse = Router::new()
+        .route("/public-routes", get(list_public_routes))
+        .route("/public-routes/:id", get(get_public))
+        .route_layer(middleware::from_fn_with_state((state.clone(), Scope::LibraryRead), auth_with_scope));
+    
+    let curate = Router::new()
+        .route("/curation/routes/:id/publish", post(publish).delete(unpublish))
+        .route("/curation/routes/:id", patch(rename).delete(remove))
+        .route_layer(middleware::from_fn_with_state(
+            (state.clone(), Permission::ManagePublicRoutes),
+            require_permission,
+        ))
+        .route_layer(middleware::from_fn_with_state(state, auth));
+    
+    browse.merge(curate)
+}
+
+async fn list_public_routes(State(state): State<AppState>) -> Result<impl IntoResponse, AppError> {
+    let routes: Vec<SavedRoute> = get_public_routes(&state.pool)
+        .await?
+        .into_iter()
+        .map(SavedRoute::from)
+        .collect();
+    
+    Ok(Json(routes))
+}
+
+async fn get_public(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, AppError> {
+    let route = get_public_route(&state.pool, id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Route not found".to_string()))?;
+    
+    Ok(Json(SavedRoute::from(route)))
+}
+
+/// Curators publish routes from their own library
+async fn publish(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, AppError> {
+    if get_route_by_id(&state.pool, id, user.id).await?.is_none() {
+        return Err(AppError::NotFound("Route not found".to_string()));
+    }
+    publish_route(&state.pool, id, user.id).await?;
+    state.spatial_index.set_public(id, true);
+    
+    record_curation(&state, &user, AuditEvent::RoutePublished, format!("route {}", id)).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn unpublish(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, AppError> {
+    if !unpublish_route(&state.pool, id).await? {
+        return Err(AppError::NotFound("Route not found".to_string()));
+    }
+    state.spatial_index.set_public(id, false);
+    
+    record_curation(&state, &user, AuditEvent::RouteUnpublished, format!("route {}", id)).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn rename(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Json(payload): Json<UpdateRouteRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    if let Some(ref new_name) = payload.name {
+        if !rename_public_route(&state.pool, id, new_name).await? {
+            return Err(AppError::NotFound("Route not found".to_string()));
+        }
+        state.spatial_index.rename(id, new_name);
+    }
+    
+    Ok(Json(serde_json::json!({
+        "id": id,
+        "message": "Route updated successfully"
+    })))
+}
+
+/// Delete a public route outright, whoever saved it
+async fn remove(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, AppError> {
+    if !delete_public_route(&state.pool, id).await? {
+        return Err(AppError::NotFound("Route not found".to_string()));
+    }
+    state.spatial_index.remove(id);
+    
+    record_curation(&state, &user, AuditEvent::RouteUnpublished, format!("route {} deleted", id)).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn record_curation(state: &AppState, user: &AuthUser, event: AuditEvent, detail: String) {
+    audit::record(
+        &state.pool,
+        AuditEntry { user_id: Some(user.id), detail: Some(&detail), ..AuditEntry::new(event) },
+    )
+    .await;
+}
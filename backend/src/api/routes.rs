@@ -1,32 +1,49 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::{header, StatusCode},
+    middleware,
     response::IntoResponse,
     routing::{get, post},
     Json, Router,
 };
+use serde::Deserialize;
 use sqlx::SqlitePool;
-// use serde::{Deserialize, Serialize}; // Not needed
 use crate::{
+    auth::api_keys::Scope,
+    auth::middleware::{auth_with_scope, AuthUser},
     error::AppError,
-    db::queries::routes::{save_route as db_save_route, get_route_by_id},
+    db::queries::routes::{save_route as db_save_route, get_route_by_id, NewRoute},
+    matching::spatial_index::parse_route_geometry,
+    matching::twistiness::Twistiness,
     models::request::SaveRouteRequest,
+    state::AppState,
+    utils::route_export::{content_disposition, export_route as render_route, ExportFormat},
 };
 
-pub fn routes() -> Router<SqlitePool> {
-    Router::new()
-        .route("/route/:id/save", post(save_route))
+pub fn routes(state: AppState) -> Router<AppState> {
+    let read = Router::new()
         .route("/route/:id/gpx", get(download_gpx))
+        .route("/route/:id/export", get(export_route))
+        .route_layer(middleware::from_fn_with_state((state.clone(), Scope::LibraryRead), auth_with_scope));
+    
+    let write = Router::new()
+        .route("/route/:id/save", post(save_route))
+        .route_layer(middleware::from_fn_with_state((state, Scope::LibraryWrite), auth_with_scope));
+    
+    read.merge(write)
+}
+
+#[derive(Debug, Deserialize)]
+struct ExportQuery {
+    format: Option<String>,
 }
 
 async fn save_route(
-    State(pool): State<SqlitePool>,
+    State(state): State<AppState>,
+    user: AuthUser,
     Path(_route_id): Path<String>,
     Json(payload): Json<SaveRouteRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    // TODO: Get user_id from auth context when middleware is implemented
-    let user_id = 1; // Placeholder for now
-    
     // Use all fields from SaveRouteRequest
     let geom_wkt = serde_json::to_string(&payload.geometry)
         .map_err(|_| AppError::BadRequest("Invalid geometry".to_string()))?;
@@ -40,27 +57,45 @@ async fn save_route(
     // Create GPX data
     let gpx_data = generate_gpx(&payload)?;
     
+    let line = parse_route_geometry(&geom_wkt);
+    let twistiness = line.as_ref()
+        .map(Twistiness::of_route)
+        .unwrap_or_default();
+    
+    // Indexing looks up missing elevation in the DEM
+    if let (Some(dem), Some(line)) = (&state.dem, &line) {
+        if payload.elevation_profile.is_empty() {
+            dem.preload(line).await;
+        }
+    }
+    
     // Save to database using all fields
     let saved_route = db_save_route(
-        &pool,
-        user_id,
-        &payload.name,
-        &payload.tag,
-        payload.distance,
-        payload.elevation_gain,
-        payload.gain_per_km,
-        payload.curve_score,
-        payload.match_percentage,
-        &geom_wkt,
-        &elevation_profile_json,
-        &search_area_json,
-        &gpx_data,
+        &state.pool,
+        NewRoute {
+            user_id: user.id,
+            name: &payload.name,
+            tag: &payload.tag,
+            distance_m: payload.distance,
+            elevation_gain_m: payload.elevation_gain,
+            gain_per_km: payload.gain_per_km,
+            curve_score: twistiness.curve_score(),
+            match_pct: payload.match_percentage,
+            geom_wkt: &geom_wkt,
+            elevation_profile_json: &elevation_profile_json,
+            search_area_json: &search_area_json,
+            gpx_data: &gpx_data,
+            twistiness: Some(&twistiness),
+        },
     ).await?;
     
+    let route_id = saved_route.id;
+    state.spatial_index.upsert(saved_route);
+    
     Ok((
         StatusCode::CREATED,
         Json(serde_json::json!({
-            "id": saved_route.id,
+            "id": route_id,
             "message": "Route saved successfully"
         }))
     ))
@@ -68,9 +103,10 @@ async fn save_route(
 
 async fn download_gpx(
     State(pool): State<SqlitePool>,
+    user: AuthUser,
     Path(id): Path<i64>,
 ) -> Result<impl IntoResponse, AppError> {
-    let route = get_route_by_id(&pool, id)
+    let route = get_route_by_id(&pool, id, user.id)
         .await?
         .ok_or_else(|| AppError::NotFound("Route not found".to_string()))?;
     
@@ -81,6 +117,36 @@ async fn download_gpx(
     ))
 }
 
+async fn export_route(
+    State(pool): State<SqlitePool>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+    Query(query): Query<ExportQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let requested = query.format.as_deref().unwrap_or("gpx");
+    let format = ExportFormat::parse(requested)
+        .ok_or_else(|| AppError::BadRequest(format!(
+            "Unsupported export format: {} (expected gpx, geojson, kml, tcx, fit or csv)",
+            requested
+        )))?;
+    
+    let route = get_route_by_id(&pool, id, user.id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Route not found".to_string()))?;
+    
+    let body = render_route(&route, format)?;
+    let disposition = content_disposition(&route.name, route.id, format);
+    
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    ))
+}
+
 fn generate_gpx(route: &SaveRouteRequest) -> Result<Vec<u8>, AppError> {
     let mut gpx = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>
 <gpx version="1.1" creator="CurveMatch"
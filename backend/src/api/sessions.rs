@@ -0,0 +1,91 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    middleware,
+    response::{AppendHeaders, IntoResponse},
+    routing::{delete, get},
+    Json, Router,
+};
+use chrono::{TimeZone, Utc};
+use serde::Serialize;
+use crate::{
+    auth::{
+        middleware::{auth, AuthUser},
+        session::{cleared_cookies, end_all_sessions, end_session},
+    },
+    db::queries::sessions::get_active_sessions,
+    error::AppError,
+    state::AppState,
+};
+
+#[derive(Debug, Serialize)]
+pub struct SessionInfo {
+    pub id: String,
+    #[serde(rename = "userAgent")]
+    pub user_agent: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    #[serde(rename = "lastSeenAt")]
+    pub last_seen_at: String,
+    #[serde(rename = "expiresAt")]
+    pub expires_at: String,
+    pub current: bool,
+}
+
+pub fn routes(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/sessions", get(list_sessions))
+        .route("/sessions", delete(revoke_all_sessions))
+        .route("/sessions/:id", delete(revoke_session))
+        .route_layer(middleware::from_fn_with_state(state, auth))
+}
+
+async fn list_sessions(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let sessions = get_active_sessions(&state.pool, user.id, Utc::now().timestamp()).await?;
+
+    let sessions: Vec<SessionInfo> = sessions
+        .into_iter()
+        .map(|session| SessionInfo {
+            current: user.session_id() == Some(session.id.as_str()),
+            expires_at: Utc
+                .timestamp_opt(session.expires_at, 0)
+                .single()
+                .map(|t| t.to_rfc3339())
+                .unwrap_or_default(),
+            id: session.id,
+            user_agent: session.user_agent,
+            created_at: session.created_at,
+            last_seen_at: session.last_seen_at,
+        })
+        .collect();
+
+    Ok(Json(sessions))
+}
+
+async fn revoke_session(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    if !end_session(&state.pool, &state.revoked_tokens, user.id, &id).await? {
+        return Err(AppError::NotFound("Session not found".to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Log out everywhere, including this browser
+async fn revoke_all_sessions(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+    let revoked = end_all_sessions(&state.pool, &state.revoked_tokens, user.id).await?;
+
+    Ok((
+        StatusCode::OK,
+        AppendHeaders(cleared_cookies()),
+        Json(serde_json::json!({ "revoked": revoked })),
+    ))
+}
@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::fmt;
+use std::str::FromStr;
+use crate::db::models::DbApiKey;
+use crate::db::queries::api_keys::{create_api_key, find_active_api_key, touch_api_key};
+use crate::error::AppError;
+use super::session::{hash_secret, new_secret};
+
+/// Every key starts with this, so keys are recognisable (e.g. by secret scanners)
+pub const API_KEY_PREFIX: &str = "cmk_";
+
+/// Characters of the key kept in plain text to tell keys apart
+const DISPLAY_PREFIX_LEN: usize = 12;
+
+/// What an API key may do. Browser sessions may do everything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Scope {
+    #[serde(rename = "match:run")]
+    MatchRun,
+    #[serde(rename = "library:read")]
+    LibraryRead,
+    #[serde(rename = "library:write")]
+    LibraryWrite,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 3] = [Scope::MatchRun, Scope::LibraryRead, Scope::LibraryWrite];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::MatchRun => "match:run",
+            Scope::LibraryRead => "library:read",
+            Scope::LibraryWrite => "library:write",
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Scope {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Scope::ALL
+            .into_iter()
+            .find(|scope| scope.as_str() == s)
+            .ok_or_else(|| AppError::BadRequest(format!("Unknown scope: {}", s)))
+    }
+}
+
+/// Scopes as stored in `api_keys.scopes`; unknown entries are dropped
+pub fn parse_scopes(stored: &str) -> Vec<Scope> {
+    stored.split_whitespace()
+        .filter_map(|scope| scope.parse().ok())
+        .collect()
+}
+
+/// A newly created key; `key` is shown to the user once and never stored
+pub struct IssuedApiKey {
+    pub key: String,
+    pub record: DbApiKey,
+}
+
+pub async fn issue_api_key(
+    pool: &SqlitePool,
+    user_id: i64,
+    name: &str,
+    scopes: &[Scope],
+) -> Result<IssuedApiKey, AppError> {
+    let key = format!("{}{}", API_KEY_PREFIX, new_secret());
+    let scopes: Vec<&str> = scopes.iter().map(Scope::as_str).collect();
+
+    let record = create_api_key(
+        pool,
+        user_id,
+        name,
+        &key[..DISPLAY_PREFIX_LEN],
+        &hash_secret(&key),
+        &scopes.join(" "),
+    )
+    .await?;
+
+    Ok(IssuedApiKey { key, record })
+}
+
+/// Look up an unrevoked key and note that it was used
+pub async fn verify_api_key(pool: &SqlitePool, key: &str) -> Result<DbApiKey, AppError> {
+    if !key.starts_with(API_KEY_PREFIX) {
+        return Err(AppError::Unauthorized);
+    }
+
+    let record = find_active_api_key(pool, &hash_secret(key))
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+    touch_api_key(pool, record.id).await?;
+    Ok(record)
+}
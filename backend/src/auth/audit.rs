@@ -0,0 +1,102 @@
+use sqlx::SqlitePool;
+use std::net::IpAddr;
+use crate::db::queries::audit::insert_audit_entry;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditEvent {
+    LoginSucceeded,
+    LoginFailed,
+    /// A failure that locked the account or client address
+    LoginLocked,
+    /// An attempt refused because of an earlier lockout
+    LoginThrottled,
+    /// A wrong two-factor code at login
+    MfaFailed,
+    MfaEnabled,
+    MfaDisabled,
+    PasswordReset,
+    ApiKeyCreated,
+    ApiKeyRevoked,
+    RoleChanged,
+    AccountDisabled,
+    AccountEnabled,
+    RoutePublished,
+    RouteUnpublished,
+}
+
+impl AuditEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditEvent::LoginSucceeded => "login.succeeded",
+            AuditEvent::LoginFailed => "login.failed",
+            AuditEvent::LoginLocked => "login.locked",
+            AuditEvent::LoginThrottled => "login.throttled",
+            AuditEvent::MfaFailed => "mfa.failed",
+            AuditEvent::MfaEnabled => "mfa.enabled",
+            AuditEvent::MfaDisabled => "mfa.disabled",
+            AuditEvent::PasswordReset => "password.reset",
+            AuditEvent::ApiKeyCreated => "api_key.created",
+            AuditEvent::ApiKeyRevoked => "api_key.revoked",
+            AuditEvent::RoleChanged => "user.role_changed",
+            AuditEvent::AccountDisabled => "user.disabled",
+            AuditEvent::AccountEnabled => "user.enabled",
+            AuditEvent::RoutePublished => "route.published",
+            AuditEvent::RouteUnpublished => "route.unpublished",
+        }
+    }
+}
+
+/// One audit log entry
+#[derive(Debug, Clone, Copy)]
+pub struct AuditEntry<'a> {
+    pub event: AuditEvent,
+    pub user_id: Option<i64>,
+    pub email: Option<&'a str>,
+    pub ip: Option<IpAddr>,
+    pub user_agent: Option<&'a str>,
+    pub detail: Option<&'a str>,
+}
+
+impl<'a> AuditEntry<'a> {
+    pub fn new(event: AuditEvent) -> Self {
+        Self {
+            event,
+            user_id: None,
+            email: None,
+            ip: None,
+            user_agent: None,
+            detail: None,
+        }
+    }
+}
+
+/// Write an entry to the `audit` tracing target and the `audit_log` table.
+///
+/// Failing to store the entry is logged but never fails the request.
+pub async fn record(pool: &SqlitePool, entry: AuditEntry<'_>) {
+    let ip = entry.ip.map(|ip| ip.to_string());
+
+    tracing::info!(
+        target: "audit",
+        event = entry.event.as_str(),
+        user_id = entry.user_id,
+        email = entry.email,
+        ip = ip.as_deref(),
+        user_agent = entry.user_agent,
+        detail = entry.detail,
+    );
+
+    if let Err(e) = insert_audit_entry(
+        pool,
+        entry.event.as_str(),
+        entry.user_id,
+        entry.email,
+        ip.as_deref(),
+        entry.user_agent,
+        entry.detail,
+    )
+    .await
+    {
+        tracing::error!("Failed to write audit log entry: {}", e);
+    }
+}
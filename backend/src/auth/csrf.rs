@@ -0,0 +1,104 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderName},
+    middleware::Next,
+    response::Response,
+};
+use axum_extra::extract::CookieJar;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use crate::config::AuthConfig;
+use crate::error::AppError;
+use crate::state::AppState;
+use super::jwt::session_id_of;
+use super::session::{new_secret, ACCESS_COOKIE, REFRESH_COOKIE};
+
+pub const CSRF_COOKIE: &str = "csrf_token";
+
+/// Header that must repeat the cookie on state-changing requests
+pub const CSRF_HEADER: HeaderName = HeaderName::from_static("x-csrf-token");
+
+/// A new token for a session (`None` when signed out): a random nonce and
+/// its HMAC with the session id under the JWT secret. A cookie planted by
+/// another site (e.g. a sibling subdomain) was issued for some other
+/// session, so it is rejected.
+pub fn issue_token(config: &AuthConfig, session_id: Option<&str>) -> String {
+    let nonce = new_secret();
+    let signature = sign(config, session_id, &nonce);
+    format!("{}.{}", nonce, signature)
+}
+
+/// Whether `token` was issued by [`issue_token`] with this secret for this session
+pub fn is_valid_token(config: &AuthConfig, token: &str, session_id: Option<&str>) -> bool {
+    match token.split_once('.') {
+        Some((nonce, signature)) => {
+            constant_time_eq(sign(config, session_id, nonce).as_bytes(), signature.as_bytes())
+        }
+        None => false,
+    }
+}
+
+/// The session of the request's access token cookie. Expiry is ignored so
+/// the token still matches when an expired session is being refreshed.
+pub fn request_session(config: &AuthConfig, jar: &CookieJar) -> Option<String> {
+    jar.get(ACCESS_COOKIE).and_then(|cookie| session_id_of(config, cookie.value()))
+}
+
+/// Readable by scripts on purpose: the client sends it back in [`CSRF_HEADER`]
+pub fn cookie(token: &str) -> (HeaderName, String) {
+    (header::SET_COOKIE, format!("{}={}; Secure; SameSite=Strict; Path=/", CSRF_COOKIE, token))
+}
+
+/// Double-submit check for every request that can change state.
+///
+/// Unsafe methods need [`CSRF_HEADER`] to match the `csrf_token` cookie,
+/// which must have been issued for the request's session. Requests authenticated with an API key and carrying no session cookies
+/// are exempt: browsers never attach those on their own.
+pub async fn protect(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    if request.method().is_safe() || uses_api_key_only(&request, &jar) {
+        return Ok(next.run(request).await);
+    }
+    
+    let header = request.headers()
+        .get(&CSRF_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .ok_or(AppError::InvalidCsrfToken)?;
+    let cookie = jar.get(CSRF_COOKIE).map(|c| c.value()).ok_or(AppError::InvalidCsrfToken)?;
+    
+    let session_id = request_session(&state.config.auth, &jar);
+    if !constant_time_eq(header.as_bytes(), cookie.as_bytes())
+        || !is_valid_token(&state.config.auth, cookie, session_id.as_deref())
+    {
+        return Err(AppError::InvalidCsrfToken);
+    }
+    
+    Ok(next.run(request).await)
+}
+
+fn uses_api_key_only(request: &Request, jar: &CookieJar) -> bool {
+    let bearer = request.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("Bearer "));
+    bearer && jar.get(ACCESS_COOKIE).is_none() && jar.get(REFRESH_COOKIE).is_none()
+}
+
+fn sign(config: &AuthConfig, session_id: Option<&str>, nonce: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(config.jwt_secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(b"csrf:");
+    // Session ids are UUIDs, so the separator can't be forged into one
+    mac.update(session_id.unwrap_or_default().as_bytes());
+    mac.update(b":");
+    mac.update(nonce.as_bytes());
+    format!("{:x}", mac.finalize().into_bytes())
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -1,46 +1,130 @@
+use axum::http::{header, HeaderName};
 use chrono::{Duration, Utc};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
+use crate::config::AuthConfig;
 use crate::error::AppError;
+use super::session::RevocationList;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
-    pub sub: i64,  // user id
-    pub exp: i64,  // expiration time
-    pub iat: i64,  // issued at
+    pub sub: i64,     // user id
+    pub exp: i64,     // expiration time
+    pub iat: i64,     // issued at
+    pub jti: String,  // token id, checked against the revocation list
+    pub sid: String,  // session id
 }
 
-pub fn create_token(user_id: i64) -> Result<String, AppError> {
-    let jwt_secret = std::env::var("JWT_SECRET")
-        .unwrap_or_else(|_| "default-secret-change-in-production".to_string());
-    
+/// Issue a short-lived access token; sessions are extended with refresh tokens
+pub fn create_token(
+    config: &AuthConfig,
+    user_id: i64,
+    session_id: &str,
+) -> Result<(String, Claims), AppError> {
     let now = Utc::now();
-    let expires_at = now + Duration::days(7);
+    let expires_at = now + Duration::minutes(config.access_token_ttl_minutes);
     
     let claims = Claims {
         sub: user_id,
         exp: expires_at.timestamp(),
         iat: now.timestamp(),
+        jti: uuid::Uuid::new_v4().to_string(),
+        sid: session_id.to_string(),
     };
     
     let token = encode(
         &Header::default(),
         &claims,
-        &EncodingKey::from_secret(jwt_secret.as_bytes()),
+        &EncodingKey::from_secret(config.jwt_secret.as_bytes()),
     )?;
     
-    Ok(token)
+    Ok((token, claims))
 }
 
-pub fn verify_token(token: &str) -> Result<Claims, AppError> {
-    let jwt_secret = std::env::var("JWT_SECRET")
-        .unwrap_or_else(|_| "default-secret-change-in-production".to_string());
-    
+/// Session id of a token this server signed, even if it has expired or
+/// been revoked; for binding values to a session, not for authenticating
+pub fn session_id_of(config: &AuthConfig, token: &str) -> Option<String> {
+    let mut validation = Validation::default();
+    validation.validate_exp = false;
+    decode::<Claims>(token, &DecodingKey::from_secret(config.jwt_secret.as_bytes()), &validation)
+        .ok()
+        .map(|token_data| token_data.claims.sid)
+}
+
+pub fn verify_token(
+    config: &AuthConfig,
+    token: &str,
+    revoked: &RevocationList,
+) -> Result<Claims, AppError> {
     let token_data = decode::<Claims>(
         token,
-        &DecodingKey::from_secret(jwt_secret.as_bytes()),
+        &DecodingKey::from_secret(config.jwt_secret.as_bytes()),
+        &Validation::default(),
+    )?;
+    
+    if revoked.is_revoked(&token_data.claims.jti) {
+        return Err(AppError::Unauthorized);
+    }
+    
+    Ok(token_data.claims)
+}
+
+/// Claims of the short-lived token issued between the password and the
+/// second factor. It can't be used as an access token.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MfaClaims {
+    pub sub: i64,
+    pub exp: i64,
+    pub iat: i64,
+    pub typ: String,  // always MFA_TOKEN_TYPE
+}
+
+const MFA_TOKEN_TYPE: &str = "mfa_pending";
+
+/// Carries the pending token to the second login step after an external
+/// sign-in, so it never appears in a URL
+pub const MFA_TOKEN_COOKIE: &str = "mfa_token";
+
+pub fn create_mfa_token(config: &AuthConfig, user_id: i64) -> Result<String, AppError> {
+    let now = Utc::now();
+    let claims = MfaClaims {
+        sub: user_id,
+        exp: (now + Duration::minutes(config.mfa_token_ttl_minutes)).timestamp(),
+        iat: now.timestamp(),
+        typ: MFA_TOKEN_TYPE.to_string(),
+    };
+    
+    Ok(encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(config.jwt_secret.as_bytes()),
+    )?)
+}
+
+pub fn verify_mfa_token(config: &AuthConfig, token: &str) -> Result<MfaClaims, AppError> {
+    let token_data = decode::<MfaClaims>(
+        token,
+        &DecodingKey::from_secret(config.jwt_secret.as_bytes()),
         &Validation::default(),
     )?;
     
+    if token_data.claims.typ != MFA_TOKEN_TYPE {
+        return Err(AppError::Unauthorized);
+    }
+    
     Ok(token_data.claims)
 }
+
+pub fn mfa_token_cookie(config: &AuthConfig, token: &str) -> (HeaderName, String) {
+    (header::SET_COOKIE, format!(
+        "{}={}; HttpOnly; Secure; SameSite=Strict; Path=/api/login/mfa; Max-Age={}",
+        MFA_TOKEN_COOKIE, token, config.mfa_token_ttl_minutes * 60
+    ))
+}
+
+pub fn cleared_mfa_token_cookie() -> (HeaderName, String) {
+    (header::SET_COOKIE, format!(
+        "{}=; HttpOnly; Secure; SameSite=Strict; Path=/api/login/mfa; Max-Age=0",
+        MFA_TOKEN_COOKIE
+    ))
+}
@@ -0,0 +1,81 @@
+use chrono::Utc;
+use rand::Rng;
+use sqlx::SqlitePool;
+use crate::db::models::DbUser;
+use crate::db::queries::recovery_codes::{consume_recovery_code, replace_recovery_codes};
+use crate::db::queries::users::record_totp_step;
+use crate::error::AppError;
+use super::session::hash_secret;
+use super::totp::verify_code;
+
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+/// How a second factor was proven
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecondFactor {
+    Totp,
+    RecoveryCode,
+}
+
+/// Generate a fresh set of recovery codes, replacing any existing ones.
+/// Only hashes are stored; the codes are returned to show the user once.
+pub async fn issue_recovery_codes(pool: &SqlitePool, user_id: i64) -> Result<Vec<String>, AppError> {
+    let codes: Vec<String> = (0..RECOVERY_CODE_COUNT).map(|_| new_recovery_code()).collect();
+    let hashes: Vec<String> = codes.iter().map(|code| hash_secret(&normalize_recovery_code(code))).collect();
+    replace_recovery_codes(pool, user_id, &hashes).await?;
+    Ok(codes)
+}
+
+/// Check a TOTP code against a secret and record its time step so it can't
+/// be used twice
+pub async fn verify_totp(
+    pool: &SqlitePool,
+    user: &DbUser,
+    secret: &str,
+    code: &str,
+) -> Result<bool, AppError> {
+    let Some(step) = verify_code(secret, code, Utc::now().timestamp(), user.totp_last_step) else {
+        return Ok(false);
+    };
+    record_totp_step(pool, user.id, step).await
+}
+
+/// Accept either a current TOTP code or an unused recovery code
+pub async fn verify_second_factor(
+    pool: &SqlitePool,
+    user: &DbUser,
+    code: &str,
+) -> Result<Option<SecondFactor>, AppError> {
+    let Some(ref secret) = user.totp_secret else {
+        return Ok(None);
+    };
+
+    if verify_totp(pool, user, secret, code).await? {
+        return Ok(Some(SecondFactor::Totp));
+    }
+
+    let normalized = normalize_recovery_code(code);
+    if !normalized.is_empty() && consume_recovery_code(pool, user.id, &hash_secret(&normalized)).await? {
+        return Ok(Some(SecondFactor::RecoveryCode));
+    }
+
+    Ok(None)
+}
+
+/// Ten characters from an unambiguous alphabet, shown as `xxxxx-xxxxx`
+fn new_recovery_code() -> String {
+    const ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+    let mut rng = rand::thread_rng();
+    let chars: String = (0..10)
+        .map(|_| ALPHABET[rng.gen_range(0..ALPHABET.len())] as char)
+        .collect();
+    format!("{}-{}", &chars[..5], &chars[5..])
+}
+
+/// Recovery codes are compared without case, spaces or dashes
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
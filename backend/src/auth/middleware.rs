@@ -1,30 +1,155 @@
 use axum::{
-    extract::{Request, State},
+    async_trait,
+    extract::{FromRequestParts, Request, State},
+    http::{header, request::Parts, HeaderMap},
     middleware::Next,
     response::Response,
 };
 use axum_extra::extract::CookieJar;
-use sqlx::SqlitePool;
+use crate::db::queries::sessions::touch_session;
+use crate::db::queries::users::find_user_by_id;
 use crate::error::AppError;
+use crate::state::AppState;
+use super::api_keys::{parse_scopes, verify_api_key, Scope};
+use super::roles::{Permission, Role};
+use super::session::ACCESS_COOKIE;
 
+/// How the request was authenticated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Credential {
+    /// Browser session from the access token cookie
+    Session { session_id: String },
+    /// Personal API key sent as `Authorization: Bearer`
+    ApiKey { key_id: i64, scopes: Vec<Scope> },
+}
+
+/// The signed-in user, placed in request extensions by [`auth`] or
+/// [`auth_with_scope`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthUser {
+    pub id: i64,
+    pub credential: Credential,
+}
+
+impl AuthUser {
+    /// The browser session, if not signed in with an API key
+    pub fn session_id(&self) -> Option<&str> {
+        match self.credential {
+            Credential::Session { ref session_id } => Some(session_id),
+            Credential::ApiKey { .. } => None,
+        }
+    }
+    
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        match self.credential {
+            Credential::Session { .. } => true,
+            Credential::ApiKey { ref scopes, .. } => scopes.contains(&scope),
+        }
+    }
+}
+
+/// Require a browser session. Used for account management, which API keys
+/// can't reach.
 pub async fn auth(
-    State(_pool): State<SqlitePool>,
+    State(state): State<AppState>,
+    jar: CookieJar,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let user = session_user(&state, &jar).await?;
+    
+    // Add the authenticated user to request extensions
+    request.extensions_mut().insert(user);
+    
+    // Continue to the next handler
+    Ok(next.run(request).await)
+}
+
+/// Require a browser session or an API key holding `scope`. Layer with
+/// `middleware::from_fn_with_state((state, scope), auth_with_scope)`.
+pub async fn auth_with_scope(
+    State((state, scope)): State<(AppState, Scope)>,
     jar: CookieJar,
+    headers: HeaderMap,
     mut request: Request,
     next: Next,
 ) -> Result<Response, AppError> {
+    let user = match bearer_token(&headers) {
+        Some(key) => {
+            let key = verify_api_key(&state.pool, key).await?;
+            AuthUser {
+                id: key.user_id,
+                credential: Credential::ApiKey { key_id: key.id, scopes: parse_scopes(&key.scopes) },
+            }
+        }
+        None => session_user(&state, &jar).await?,
+    };
+    
+    if !user.has_scope(scope) {
+        return Err(AppError::Forbidden);
+    }
+    
+    request.extensions_mut().insert(user);
+    Ok(next.run(request).await)
+}
+
+/// Require a role with `permission`. Goes inside [`auth`], which must run
+/// first: `.route_layer(require_permission).route_layer(auth)`.
+///
+/// The role is read from the database on every request so role changes and
+/// disabled accounts take effect immediately.
+pub async fn require_permission(
+    State((state, permission)): State<(AppState, Permission)>,
+    user: AuthUser,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let account = find_user_by_id(&state.pool, user.id)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+    
+    if account.disabled_at.is_some() || !Role::from_db(&account.role).can(permission) {
+        return Err(AppError::Forbidden);
+    }
+    
+    Ok(next.run(request).await)
+}
+
+async fn session_user(state: &AppState, jar: &CookieJar) -> Result<AuthUser, AppError> {
     // Extract token from cookie
     let token = jar
-        .get("token")
+        .get(ACCESS_COOKIE)
         .map(|cookie| cookie.value())
         .ok_or(AppError::Unauthorized)?;
     
-    // Verify token
-    let claims = super::jwt::verify_token(token)?;
+    // Verify signature, expiry and revocation
+    let claims = super::jwt::verify_token(&state.config.auth, token, &state.revoked_tokens)?;
+    touch_session(&state.pool, &claims.sid).await?;
     
-    // Add user ID to request extensions
-    request.extensions_mut().insert(claims.sub);
+    Ok(AuthUser {
+        id: claims.sub,
+        credential: Credential::Session { session_id: claims.sid },
+    })
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
+
+/// Rejects with 401 on routes that aren't behind the [`auth`] middleware
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for AuthUser {
+    type Rejection = AppError;
     
-    // Continue to the next handler
-    Ok(next.run(request).await)
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts.extensions
+            .get::<AuthUser>()
+            .cloned()
+            .ok_or(AppError::Unauthorized)
+    }
 }
@@ -1,3 +1,13 @@
+pub mod api_keys;
+pub mod audit;
+pub mod csrf;
 pub mod jwt;
+pub mod mfa;
 pub mod middleware;
+pub mod oidc;
 pub mod password;
+pub mod roles;
+pub mod session;
+pub mod throttle;
+pub mod tokens;
+pub mod totp;
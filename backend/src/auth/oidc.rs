@@ -0,0 +1,415 @@
+use axum::http::{header, HeaderName};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{Duration, Utc};
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Deserializer, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::SqlitePool;
+use std::collections::BTreeMap;
+use tokio::sync::RwLock;
+use crate::config::{AuthConfig, OidcConfig, OidcProviderConfig};
+use crate::db::models::DbUser;
+use crate::db::queries::identities::{create_identity, find_identity, touch_identity};
+use crate::db::queries::users::{
+    create_user, find_user_by_email, find_user_by_id, find_user_by_username, mark_email_verified,
+};
+use crate::error::AppError;
+use super::password::hash_password;
+use super::session::new_secret;
+
+/// Carries the pending sign-in between the redirect and the callback
+pub const OIDC_LOGIN_COOKIE: &str = "oidc_login";
+
+/// How long the user has to finish signing in at the provider
+const LOGIN_TTL_MINUTES: i64 = 10;
+const LOGIN_TOKEN_TYPE: &str = "oidc_login";
+
+/// Signature algorithms accepted on ID tokens. Symmetric ones are refused
+/// because the keys come from the provider's JWKS.
+const ID_TOKEN_ALGORITHMS: &[Algorithm] = &[
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+    Algorithm::ES256,
+    Algorithm::ES384,
+    Algorithm::EdDSA,
+];
+
+/// The parts of a provider's discovery document used here
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+}
+
+/// Claims read from a validated ID token
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdTokenClaims {
+    pub sub: String,
+    pub nonce: Option<String>,
+    pub email: Option<String>,
+    /// Some providers send this as the string "true"
+    #[serde(default, deserialize_with = "lenient_bool")]
+    pub email_verified: bool,
+    pub name: Option<String>,
+    pub preferred_username: Option<String>,
+}
+
+fn lenient_bool<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    Ok(match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::Bool(value) => value,
+        serde_json::Value::String(value) => value == "true",
+        _ => false,
+    })
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: Option<String>,
+    error: Option<String>,
+}
+
+/// A sign-in in progress. Sealed into a short-lived cookie so the callback
+/// can only be completed by the browser that started it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PendingLogin {
+    pub provider: String,
+    pub state: String,
+    pub nonce: String,
+    pub code_verifier: String,
+    exp: i64,
+    typ: String,
+}
+
+impl PendingLogin {
+    pub fn new(provider: &str) -> Self {
+        Self {
+            provider: provider.to_string(),
+            state: new_secret(),
+            nonce: new_secret(),
+            code_verifier: new_secret(),
+            exp: (Utc::now() + Duration::minutes(LOGIN_TTL_MINUTES)).timestamp(),
+            typ: LOGIN_TOKEN_TYPE.to_string(),
+        }
+    }
+
+    /// PKCE `S256` challenge for the verifier
+    pub fn code_challenge(&self) -> String {
+        URL_SAFE_NO_PAD.encode(Sha256::digest(self.code_verifier.as_bytes()))
+    }
+
+    pub fn seal(&self, config: &AuthConfig) -> Result<String, AppError> {
+        Ok(encode(
+            &Header::default(),
+            self,
+            &EncodingKey::from_secret(config.jwt_secret.as_bytes()),
+        )?)
+    }
+
+    pub fn open(config: &AuthConfig, sealed: &str) -> Result<Self, AppError> {
+        let mut validation = Validation::default();
+        validation.set_required_spec_claims(&["exp"]);
+        let pending = decode::<PendingLogin>(
+            sealed,
+            &DecodingKey::from_secret(config.jwt_secret.as_bytes()),
+            &validation,
+        )?
+        .claims;
+
+        if pending.typ != LOGIN_TOKEN_TYPE {
+            return Err(AppError::Unauthorized);
+        }
+        Ok(pending)
+    }
+
+    /// `Set-Cookie` header for the sealed sign-in. `SameSite=Lax` so it is
+    /// sent on the provider's redirect back.
+    pub fn cookie(sealed: &str) -> (HeaderName, String) {
+        (header::SET_COOKIE, format!(
+            "{}={}; HttpOnly; Secure; SameSite=Lax; Path=/api/oidc; Max-Age={}",
+            OIDC_LOGIN_COOKIE, sealed, LOGIN_TTL_MINUTES * 60
+        ))
+    }
+
+    pub fn cleared_cookie() -> (HeaderName, String) {
+        (header::SET_COOKIE, format!(
+            "{}=; HttpOnly; Secure; SameSite=Lax; Path=/api/oidc; Max-Age=0",
+            OIDC_LOGIN_COOKIE
+        ))
+    }
+}
+
+/// A configured provider, with its discovery document and signing keys
+/// fetched on first use
+pub struct OidcProvider {
+    pub id: String,
+    pub config: OidcProviderConfig,
+    http: reqwest::Client,
+    metadata: RwLock<Option<ProviderMetadata>>,
+    jwks: RwLock<Option<JwkSet>>,
+}
+
+impl OidcProvider {
+    /// Where to send the browser to sign in
+    pub async fn authorization_url(&self, pending: &PendingLogin) -> Result<String, AppError> {
+        let metadata = self.metadata().await?;
+        let url = reqwest::Url::parse_with_params(
+            &metadata.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", self.config.client_id.as_str()),
+                ("redirect_uri", self.config.redirect_uri.as_str()),
+                ("scope", self.config.scopes.join(" ").as_str()),
+                ("state", pending.state.as_str()),
+                ("nonce", pending.nonce.as_str()),
+                ("code_challenge", pending.code_challenge().as_str()),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .map_err(|e| anyhow::anyhow!("Invalid authorization endpoint for {}: {}", self.id, e))?;
+
+        Ok(url.into())
+    }
+
+    /// Redeem the authorization code and validate the returned ID token
+    pub async fn authenticate(&self, code: &str, pending: &PendingLogin) -> Result<IdTokenClaims, AppError> {
+        let metadata = self.metadata().await?;
+
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", self.config.redirect_uri.as_str()),
+            ("client_id", self.config.client_id.as_str()),
+            ("code_verifier", pending.code_verifier.as_str()),
+        ];
+        if let Some(ref secret) = self.config.client_secret {
+            form.push(("client_secret", secret.as_str()));
+        }
+
+        let response: TokenResponse = self.http
+            .post(&metadata.token_endpoint)
+            .form(&form)
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!("Token request to {} failed: {}", self.id, e))?
+            .json()
+            .await
+            .map_err(|e| anyhow::anyhow!("Invalid token response from {}: {}", self.id, e))?;
+
+        let id_token = match (response.id_token, response.error) {
+            (Some(id_token), None) => id_token,
+            (_, error) => {
+                tracing::warn!("{} refused the authorization code: {}", self.id, error.unwrap_or_default());
+                return Err(AppError::Unauthorized);
+            }
+        };
+
+        self.validate_id_token(&metadata, &id_token, &pending.nonce).await
+    }
+
+    async fn validate_id_token(
+        &self,
+        metadata: &ProviderMetadata,
+        id_token: &str,
+        nonce: &str,
+    ) -> Result<IdTokenClaims, AppError> {
+        let header = decode_header(id_token)?;
+        if !ID_TOKEN_ALGORITHMS.contains(&header.alg) {
+            return Err(AppError::Unauthorized);
+        }
+        let key = self.signing_key(header.kid.as_deref()).await?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&metadata.issuer]);
+        validation.set_audience(&[&self.config.client_id]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+        let claims = decode::<IdTokenClaims>(id_token, &key, &validation)?.claims;
+
+        // Ties the token to this browser's sign-in, so it can't be replayed
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(AppError::Unauthorized);
+        }
+        Ok(claims)
+    }
+
+    async fn metadata(&self) -> Result<ProviderMetadata, AppError> {
+        if let Some(ref metadata) = *self.metadata.read().await {
+            return Ok(metadata.clone());
+        }
+
+        let url = format!("{}/.well-known/openid-configuration", self.config.issuer.trim_end_matches('/'));
+        let metadata: ProviderMetadata = self.fetch_json(&url).await?;
+        if metadata.issuer != self.config.issuer {
+            return Err(anyhow::anyhow!(
+                "{} reports issuer {} but {} is configured", self.id, metadata.issuer, self.config.issuer
+            )
+            .into());
+        }
+
+        *self.metadata.write().await = Some(metadata.clone());
+        Ok(metadata)
+    }
+
+    /// Look the key up in the cached JWKS, refetching it once in case the
+    /// provider has rotated its keys
+    async fn signing_key(&self, kid: Option<&str>) -> Result<DecodingKey, AppError> {
+        if let Some(key) = self.jwks.read().await.as_ref().and_then(|jwks| find_key(jwks, kid)) {
+            return key;
+        }
+
+        let metadata = self.metadata().await?;
+        let jwks: JwkSet = self.fetch_json(&metadata.jwks_uri).await?;
+        let key = find_key(&jwks, kid);
+        *self.jwks.write().await = Some(jwks);
+        key.unwrap_or(Err(AppError::Unauthorized))
+    }
+
+    async fn fetch_json<T: serde::de::DeserializeOwned>(&self, url: &str) -> Result<T, AppError> {
+        let response = self.http
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| anyhow::anyhow!("Request to {} failed: {}", url, e))?;
+
+        Ok(response
+            .json()
+            .await
+            .map_err(|e| anyhow::anyhow!("Invalid JSON from {}: {}", url, e))?)
+    }
+}
+
+/// The key with the given id, or the only key when the token names none
+fn find_key(jwks: &JwkSet, kid: Option<&str>) -> Option<Result<DecodingKey, AppError>> {
+    let jwk = match kid {
+        Some(kid) => jwks.find(kid)?,
+        None if jwks.keys.len() == 1 => &jwks.keys[0],
+        None => return None,
+    };
+    Some(DecodingKey::from_jwk(jwk).map_err(AppError::from))
+}
+
+/// All configured providers
+pub struct OidcProviders {
+    providers: BTreeMap<String, OidcProvider>,
+}
+
+impl OidcProviders {
+    pub fn new(config: &OidcConfig) -> anyhow::Result<Self> {
+        let http = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(10))
+            .build()?;
+
+        let providers = config.providers.iter()
+            .map(|(id, config)| {
+                let provider = OidcProvider {
+                    id: id.clone(),
+                    config: config.clone(),
+                    http: http.clone(),
+                    metadata: RwLock::new(None),
+                    jwks: RwLock::new(None),
+                };
+                (id.clone(), provider)
+            })
+            .collect();
+
+        Ok(Self { providers })
+    }
+
+    pub fn get(&self, id: &str) -> Option<&OidcProvider> {
+        self.providers.get(id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &OidcProvider> {
+        self.providers.values()
+    }
+}
+
+/// The user an external identity signs in as. On first sign-in the identity
+/// is linked to the account with the same email, or a new account is created.
+pub async fn find_or_create_user(
+    pool: &SqlitePool,
+    provider: &str,
+    claims: &IdTokenClaims,
+) -> Result<DbUser, AppError> {
+    let email = claims.email.as_deref().map(str::trim).filter(|email| !email.is_empty());
+
+    if let Some(identity) = find_identity(pool, provider, &claims.sub).await? {
+        touch_identity(pool, identity.id, email).await?;
+        return find_user_by_id(pool, identity.user_id)
+            .await?
+            .ok_or(AppError::Unauthorized);
+    }
+
+    let email = email.ok_or_else(|| {
+        AppError::BadRequest("The identity provider did not share an email address".to_string())
+    })?;
+
+    let user = match find_user_by_email(pool, email).await? {
+        // Only link to an existing account when both the provider and the
+        // account have proven the address; otherwise whoever registered it
+        // first could keep a password on the linked account
+        Some(user) if claims.email_verified && user.email_verified_at.is_some() => user,
+        Some(_) => return Err(AppError::BadRequest("An account with this email already exists".to_string())),
+        None => create_external_user(pool, email, claims).await?,
+    };
+
+    if claims.email_verified {
+        mark_email_verified(pool, user.id).await?;
+    }
+    create_identity(pool, user.id, provider, &claims.sub, Some(email)).await?;
+
+    find_user_by_id(pool, user.id)
+        .await?
+        .ok_or(AppError::Unauthorized)
+}
+
+/// New account for an external identity. The password is random; the user
+/// can set one through password reset.
+async fn create_external_user(
+    pool: &SqlitePool,
+    email: &str,
+    claims: &IdTokenClaims,
+) -> Result<DbUser, AppError> {
+    let base = username_base(claims, email);
+    let mut username = base.clone();
+    let mut suffix = 2;
+    while find_user_by_username(pool, &username).await?.is_some() {
+        username = if suffix < 100 {
+            format!("{}-{}", base, suffix)
+        } else {
+            format!("{}-{}", base, &uuid::Uuid::new_v4().simple().to_string()[..8])
+        };
+        suffix += 1;
+    }
+
+    let (password_salt, password_hash) = hash_password(&new_secret())?;
+    create_user(pool, email, &username, &password_salt, &password_hash).await
+}
+
+/// A username from the provider's profile, falling back to the email's local part
+fn username_base(claims: &IdTokenClaims, email: &str) -> String {
+    let candidate = claims.preferred_username.as_deref()
+        .or(claims.name.as_deref())
+        .unwrap_or_else(|| email.split('@').next().unwrap_or_default());
+
+    let username: String = candidate.trim()
+        .chars()
+        .map(|c| if c.is_whitespace() { '-' } else { c })
+        .filter(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        .take(30)
+        .collect();
+
+    if username.is_empty() {
+        "rider".to_string()
+    } else {
+        username
+    }
+}
@@ -1,5 +1,8 @@
 use bcrypt::{hash, verify, DEFAULT_COST};
 use rand::Rng;
+use sha1::{Digest, Sha1};
+use std::collections::HashSet;
+use crate::config::PasswordConfig;
 use crate::error::AppError;
 
 pub fn hash_password(password: &str) -> Result<(String, String), AppError> {
@@ -22,3 +25,87 @@ pub fn hash_password(password: &str) -> Result<(String, String), AppError> {
 pub fn verify_password(password: &str, hash: &str) -> Result<bool, AppError> {
     Ok(verify(password, hash)?)
 }
+
+/// Rules new passwords must satisfy
+pub struct PasswordPolicy {
+    min_length: usize,
+    max_length: usize,
+    /// Uppercase SHA-1 hex digests of known-breached passwords
+    breached: HashSet<String>,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self::new(&PasswordConfig::default(), std::iter::empty::<&str>())
+    }
+}
+
+impl PasswordPolicy {
+    /// Build a policy from config, reading the breached-password list if one is set
+    pub fn load(config: &PasswordConfig) -> anyhow::Result<Self> {
+        let Some(ref path) = config.breached_list_path else {
+            return Ok(Self::new(config, std::iter::empty::<&str>()));
+        };
+
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read breached password list {}: {}", path, e))?;
+        let policy = Self::new(config, contents.lines());
+        tracing::info!("Loaded {} breached passwords from {}", policy.breached.len(), path);
+        Ok(policy)
+    }
+
+    /// `breached` lines are plain passwords or SHA-1 hex, optionally followed by `:count`
+    pub fn new<'a>(config: &PasswordConfig, breached: impl IntoIterator<Item = &'a str>) -> Self {
+        let breached = breached
+            .into_iter()
+            .map(|line| line.trim_end_matches('\r'))
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                let digest = line.split(':').next().unwrap_or_default().trim();
+                if digest.len() == 40 && digest.chars().all(|c| c.is_ascii_hexdigit()) {
+                    digest.to_ascii_uppercase()
+                } else {
+                    sha1_hex(line)
+                }
+            })
+            .collect();
+
+        Self {
+            min_length: config.min_length,
+            max_length: config.max_length,
+            breached,
+        }
+    }
+
+    /// Check a new password; `personal` holds values it must not equal,
+    /// such as the email address and username
+    pub fn check(&self, password: &str, personal: &[&str]) -> Result<(), AppError> {
+        if password.chars().count() < self.min_length {
+            return Err(AppError::BadRequest(format!(
+                "Password must be at least {} characters",
+                self.min_length
+            )));
+        }
+        if password.len() > self.max_length {
+            return Err(AppError::BadRequest(format!(
+                "Password must be at most {} bytes",
+                self.max_length
+            )));
+        }
+        if personal.iter().any(|value| !value.is_empty() && password.eq_ignore_ascii_case(value.trim())) {
+            return Err(AppError::BadRequest(
+                "Password must not be your email address or username".to_string(),
+            ));
+        }
+        if self.breached.contains(&sha1_hex(password)) {
+            return Err(AppError::BadRequest(
+                "This password has appeared in a data breach, please choose another".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn sha1_hex(value: &str) -> String {
+    format!("{:X}", Sha1::digest(value.as_bytes()))
+}
@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use crate::error::AppError;
+
+/// Stored in `users.role`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    /// Maintains the shared public route corpus
+    Curator,
+    Admin,
+}
+
+/// Something only some roles may do, checked by
+/// [`require_permission`](super::middleware::require_permission)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    /// Publish, rename and remove routes in the public corpus
+    ManagePublicRoutes,
+    /// List accounts, change roles, disable accounts
+    ManageUsers,
+    ViewPlatformStats,
+}
+
+impl Role {
+    pub const ALL: [Role; 3] = [Role::User, Role::Curator, Role::Admin];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::User => "user",
+            Role::Curator => "curator",
+            Role::Admin => "admin",
+        }
+    }
+
+    /// Unknown values in the database get no extra permissions
+    pub fn from_db(role: &str) -> Self {
+        role.parse().unwrap_or(Role::User)
+    }
+
+    pub fn can(&self, permission: Permission) -> bool {
+        match self {
+            Role::Admin => true,
+            Role::Curator => permission == Permission::ManagePublicRoutes,
+            Role::User => false,
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Role {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Role::ALL
+            .into_iter()
+            .find(|role| role.as_str() == s)
+            .ok_or_else(|| AppError::BadRequest(format!("Unknown role: {}", s)))
+    }
+}
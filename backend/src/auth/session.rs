@@ -0,0 +1,204 @@
+use axum::http::{header, HeaderName};
+use chrono::{Duration, Utc};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use crate::db::queries::sessions::{
+    create_session, find_session, get_revoked_tokens, insert_revoked_token, revoke_session,
+    revoke_user_sessions, rotate_session, NewSession,
+};
+use crate::config::AuthConfig;
+use crate::error::AppError;
+use super::jwt::create_token;
+
+pub const ACCESS_COOKIE: &str = "token";
+pub const REFRESH_COOKIE: &str = "refresh_token";
+
+/// Access token ids revoked before their expiry.
+///
+/// Kept in memory so `verify_token` stays synchronous; every revocation is
+/// written through to the `revoked_tokens` table and reloaded at startup.
+#[derive(Default)]
+pub struct RevocationList {
+    revoked: RwLock<HashMap<String, i64>>,
+}
+
+impl RevocationList {
+    pub async fn load(pool: &SqlitePool) -> Result<Self, sqlx::Error> {
+        let revoked = get_revoked_tokens(pool, Utc::now().timestamp()).await?;
+        Ok(Self {
+            revoked: RwLock::new(revoked.into_iter().collect()),
+        })
+    }
+    
+    pub fn is_revoked(&self, jti: &str) -> bool {
+        self.revoked.read().unwrap().contains_key(jti)
+    }
+    
+    /// Revoke an access token until `expires_at` (unix seconds)
+    pub async fn revoke(&self, pool: &SqlitePool, jti: &str, expires_at: i64) -> Result<(), AppError> {
+        let now = Utc::now().timestamp();
+        if expires_at <= now {
+            return Ok(());
+        }
+        insert_revoked_token(pool, jti, expires_at).await?;
+        
+        let mut revoked = self.revoked.write().unwrap();
+        revoked.retain(|_, exp| *exp > now);
+        revoked.insert(jti.to_string(), expires_at);
+        Ok(())
+    }
+}
+
+/// Tokens handed to the client when a session starts or is refreshed
+pub struct IssuedSession {
+    pub session_id: String,
+    pub access_token: String,
+    pub refresh_token: String,
+}
+
+impl IssuedSession {
+    /// `Set-Cookie` headers for both tokens
+    pub fn cookies(&self, config: &AuthConfig) -> [(HeaderName, String); 2] {
+        [
+            (header::SET_COOKIE, format!(
+                "{}={}; HttpOnly; Secure; SameSite=Strict; Path=/; Max-Age={}",
+                ACCESS_COOKIE, self.access_token, config.access_token_ttl_minutes * 60
+            )),
+            (header::SET_COOKIE, format!(
+                "{}={}; HttpOnly; Secure; SameSite=Strict; Path=/api; Max-Age={}",
+                REFRESH_COOKIE, self.refresh_token, config.refresh_token_ttl_days * 24 * 3600
+            )),
+        ]
+    }
+}
+
+/// `Set-Cookie` headers that remove both tokens
+pub fn cleared_cookies() -> [(HeaderName, String); 2] {
+    [
+        (header::SET_COOKIE, format!("{}=; HttpOnly; Secure; SameSite=Strict; Path=/; Max-Age=0", ACCESS_COOKIE)),
+        (header::SET_COOKIE, format!("{}=; HttpOnly; Secure; SameSite=Strict; Path=/api; Max-Age=0", REFRESH_COOKIE)),
+    ]
+}
+
+/// Open a new session for a user who just authenticated
+pub async fn start_session(
+    pool: &SqlitePool,
+    config: &AuthConfig,
+    user_id: i64,
+    user_agent: Option<&str>,
+) -> Result<IssuedSession, AppError> {
+    let session_id = uuid::Uuid::new_v4().to_string();
+    let secret = new_secret();
+    let (access_token, claims) = create_token(config, user_id, &session_id)?;
+    let expires_at = (Utc::now() + Duration::days(config.refresh_token_ttl_days)).timestamp();
+    
+    create_session(
+        pool,
+        NewSession {
+            id: &session_id,
+            user_id,
+            refresh_token_hash: &hash_secret(&secret),
+            user_agent,
+            access_jti: &claims.jti,
+            access_expires_at: claims.exp,
+            expires_at,
+        },
+    )
+    .await?;
+    
+    Ok(IssuedSession {
+        refresh_token: format!("{}.{}", session_id, secret),
+        session_id,
+        access_token,
+    })
+}
+
+/// Exchange a refresh token for a new access token and refresh token.
+///
+/// Presenting an already-rotated refresh token means it was copied, so the
+/// whole session is revoked. That includes losing a race against another
+/// refresh with the same token.
+pub async fn refresh_session(
+    pool: &SqlitePool,
+    config: &AuthConfig,
+    revoked: &RevocationList,
+    refresh_token: &str,
+) -> Result<IssuedSession, AppError> {
+    let (session_id, secret) = refresh_token.split_once('.').ok_or(AppError::Unauthorized)?;
+    let session = find_session(pool, session_id)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+    
+    if session.revoked_at.is_some() || session.expires_at <= Utc::now().timestamp() {
+        return Err(AppError::Unauthorized);
+    }
+    
+    let presented_hash = hash_secret(secret);
+    let new_secret = new_secret();
+    let (access_token, claims) = create_token(config, session.user_id, &session.id)?;
+    // The update re-checks the hash, so of two refreshes racing with the
+    // same token only one can rotate it
+    let rotated = session.refresh_token_hash == presented_hash
+        && rotate_session(
+            pool, &session.id, &presented_hash, &hash_secret(&new_secret), &claims.jti, claims.exp,
+        )
+        .await?;
+    if !rotated {
+        tracing::warn!("Refresh token reuse detected for session {}, revoking it", session.id);
+        end_session(pool, revoked, session.user_id, &session.id).await?;
+        return Err(AppError::Unauthorized);
+    }
+    
+    // Only the newest access token of a session stays valid
+    revoked.revoke(pool, &session.access_jti, session.access_expires_at).await?;
+    
+    Ok(IssuedSession {
+        refresh_token: format!("{}.{}", session.id, new_secret),
+        session_id: session.id,
+        access_token,
+    })
+}
+
+/// Revoke one session and its current access token; returns whether the
+/// session was active
+pub async fn end_session(
+    pool: &SqlitePool,
+    revoked: &RevocationList,
+    user_id: i64,
+    session_id: &str,
+) -> Result<bool, AppError> {
+    match revoke_session(pool, session_id, user_id).await? {
+        Some(session) => {
+            revoked.revoke(pool, &session.access_jti, session.access_expires_at).await?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Revoke every session of a user ("log out everywhere")
+pub async fn end_all_sessions(
+    pool: &SqlitePool,
+    revoked: &RevocationList,
+    user_id: i64,
+) -> Result<usize, AppError> {
+    let sessions = revoke_user_sessions(pool, user_id).await?;
+    for session in &sessions {
+        revoked.revoke(pool, &session.access_jti, session.access_expires_at).await?;
+    }
+    Ok(sessions.len())
+}
+
+pub(super) fn new_secret() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Refresh tokens and emailed tokens are stored as SHA-256 digests, never in plain text
+pub(super) fn hash_secret(secret: &str) -> String {
+    format!("{:x}", Sha256::digest(secret.as_bytes()))
+}
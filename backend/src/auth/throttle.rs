@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use crate::config::LoginConfig;
+use crate::error::AppError;
+
+#[derive(Debug, Clone, Copy)]
+struct Failures {
+    count: u32,
+    last_failure: Instant,
+    locked_until: Option<Instant>,
+}
+
+/// Failed login attempts per account and per client address, kept in
+/// memory. Once a key runs out of attempts every further failure locks it
+/// with exponential backoff.
+pub struct LoginThrottle {
+    config: LoginConfig,
+    failures: Mutex<HashMap<String, Failures>>,
+}
+
+impl LoginThrottle {
+    pub fn new(config: LoginConfig) -> Self {
+        Self {
+            config,
+            failures: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Refuse the attempt while the account or address is locked
+    pub fn check(&self, email: &str, ip: Option<IpAddr>) -> Result<(), AppError> {
+        let now = Instant::now();
+        let failures = self.failures.lock().unwrap();
+
+        let retry_after = self.keys(email, ip)
+            .iter()
+            .filter_map(|(key, _)| failures.get(key)?.locked_until)
+            .filter(|until| *until > now)
+            .map(|until| until.duration_since(now))
+            .max();
+
+        match retry_after {
+            // Round up so clients never retry a moment too early
+            Some(wait) => Err(AppError::TooManyRequests(wait.as_secs() + 1)),
+            None => Ok(()),
+        }
+    }
+
+    /// Count a failed attempt; returns how long the account or address is
+    /// now locked for, if this failure triggered a lockout
+    pub fn record_failure(&self, email: &str, ip: Option<IpAddr>) -> Option<Duration> {
+        let now = Instant::now();
+        let window = Duration::from_secs(self.config.attempt_window_secs);
+        let mut failures = self.failures.lock().unwrap();
+
+        // Forget keys that have been quiet for a whole window
+        failures.retain(|_, f| {
+            now.duration_since(f.last_failure) < window || f.locked_until.is_some_and(|until| until > now)
+        });
+
+        let mut lockout = None;
+        for (key, allowed) in self.keys(email, ip) {
+            let entry = failures.entry(key).or_insert(Failures {
+                count: 0,
+                last_failure: now,
+                locked_until: None,
+            });
+            entry.count += 1;
+            entry.last_failure = now;
+
+            if entry.count >= allowed {
+                let duration = self.lockout_duration(entry.count - allowed);
+                entry.locked_until = Some(now + duration);
+                lockout = lockout.max(Some(duration));
+            }
+        }
+
+        lockout
+    }
+
+    /// A successful login clears the account's failures. The address keeps
+    /// its count so one valid account can't be used to reset it.
+    pub fn record_success(&self, email: &str) {
+        self.failures.lock().unwrap().remove(&account_key(email));
+    }
+
+    fn keys(&self, email: &str, ip: Option<IpAddr>) -> Vec<(String, u32)> {
+        let mut keys = vec![(account_key(email), self.config.max_attempts_per_account)];
+        if let Some(ip) = ip {
+            keys.push((format!("ip:{}", ip), self.config.max_attempts_per_ip));
+        }
+        keys
+    }
+
+    /// `lockout_base_secs` doubled for every failure past the limit
+    fn lockout_duration(&self, excess: u32) -> Duration {
+        let secs = self.config.lockout_base_secs
+            .saturating_mul(1u64 << excess.min(32))
+            .min(self.config.lockout_max_secs);
+        Duration::from_secs(secs)
+    }
+}
+
+fn account_key(email: &str) -> String {
+    format!("account:{}", email.trim().to_lowercase())
+}
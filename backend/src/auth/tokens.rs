@@ -0,0 +1,71 @@
+use chrono::{Duration, Utc};
+use sqlx::SqlitePool;
+use crate::db::queries::auth_tokens::{
+    consume_auth_token, create_auth_token, find_auth_token, invalidate_auth_tokens,
+};
+use crate::error::AppError;
+use super::session::{hash_secret, new_secret};
+
+/// What an emailed token may be used for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenPurpose {
+    PasswordReset,
+    EmailVerification,
+}
+
+impl TokenPurpose {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenPurpose::PasswordReset => "password_reset",
+            TokenPurpose::EmailVerification => "email_verification",
+        }
+    }
+}
+
+/// Create a single-use token for `user_id`, replacing any outstanding token
+/// with the same purpose. Only its hash is stored; the plain token is
+/// returned to be sent by email.
+pub async fn issue_token(
+    pool: &SqlitePool,
+    user_id: i64,
+    purpose: TokenPurpose,
+    ttl: Duration,
+) -> Result<String, AppError> {
+    invalidate_auth_tokens(pool, user_id, purpose.as_str()).await?;
+    
+    let token = new_secret();
+    let expires_at = (Utc::now() + ttl).timestamp();
+    create_auth_token(pool, user_id, purpose.as_str(), &hash_secret(&token), expires_at).await?;
+    
+    Ok(token)
+}
+
+/// The user a token was issued to, without using it up
+pub async fn find_token_user(
+    pool: &SqlitePool,
+    purpose: TokenPurpose,
+    token: &str,
+) -> Result<i64, AppError> {
+    let record = find_auth_token(pool, purpose.as_str(), &hash_secret(token.trim()), Utc::now().timestamp())
+        .await?
+        .ok_or_else(invalid_token)?;
+    
+    Ok(record.user_id)
+}
+
+/// Use up a token, returning the user it was issued to
+pub async fn consume_token(
+    pool: &SqlitePool,
+    purpose: TokenPurpose,
+    token: &str,
+) -> Result<i64, AppError> {
+    let record = consume_auth_token(pool, purpose.as_str(), &hash_secret(token.trim()), Utc::now().timestamp())
+        .await?
+        .ok_or_else(invalid_token)?;
+    
+    Ok(record.user_id)
+}
+
+fn invalid_token() -> AppError {
+    AppError::BadRequest("This link is invalid or has expired".to_string())
+}
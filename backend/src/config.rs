@@ -1,30 +1,491 @@
-use serde::Deserialize;
+use config::{ConfigBuilder, Environment, File, FileFormat};
+use config::builder::DefaultState;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use crate::matching::engine::{MatchingConfig, ShapeMetric};
 
-#[derive(Debug, Deserialize, Clone)]
+/// Secret used when none is configured; only accepted in the dev profile
+pub const DEFAULT_JWT_SECRET: &str = "default-secret-change-in-production";
+
+/// Config file read when `--config` / `CURVEMATCH_CONFIG` isn't given
+const DEFAULT_CONFIG_FILE: &str = "curvematch.toml";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Profile {
+    #[serde(rename = "dev", alias = "development")]
+    Dev,
+    #[serde(rename = "production", alias = "prod")]
+    Production,
+}
+
+impl Default for Profile {
+    /// Debug builds default to dev so `cargo run` works out of the box
+    fn default() -> Self {
+        if cfg!(debug_assertions) {
+            Profile::Dev
+        } else {
+            Profile::Production
+        }
+    }
+}
+
+/// Application settings, layered as defaults → TOML file → environment → CLI flags
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Config {
-    pub database_url: String,
+    pub profile: Profile,
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub auth: AuthConfig,
+    pub login: LoginConfig,
+    pub password: PasswordConfig,
+    pub matching: MatchingDefaults,
+    pub data: DataConfig,
+    pub mail: MailConfig,
+    pub oidc: OidcConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    /// Largest accepted request body, in bytes (route uploads)
+    pub max_upload_bytes: usize,
+    /// Origins allowed to make credentialed cross-origin requests
+    pub cors_origins: Vec<String>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            port: 3000,
+            max_upload_bytes: 50 * 1024 * 1024,
+            cors_origins: vec!["http://localhost:5173".to_string()],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatabaseConfig {
+    pub url: String,
+    pub max_connections: u32,
+    pub acquire_timeout_secs: u64,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            url: "sqlite:./curvematch.db".to_string(),
+            max_connections: 5,
+            acquire_timeout_secs: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthConfig {
     pub jwt_secret: String,
-    pub server_host: String,
-    pub server_port: u16,
+    pub access_token_ttl_minutes: i64,
+    /// How long a session survives without being refreshed
+    pub refresh_token_ttl_days: i64,
+    pub password_reset_ttl_minutes: i64,
+    pub email_verification_ttl_hours: i64,
+    /// Time allowed to enter a two-factor code after the password
+    pub mfa_token_ttl_minutes: i64,
+    /// Issuer shown in authenticator apps
+    pub totp_issuer: String,
+    /// Verified accounts made admins at startup, to bootstrap the first one
+    #[serde(default)]
+    pub admin_emails: Vec<String>,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            jwt_secret: DEFAULT_JWT_SECRET.to_string(),
+            access_token_ttl_minutes: 15,
+            refresh_token_ttl_days: 30,
+            password_reset_ttl_minutes: 60,
+            email_verification_ttl_hours: 48,
+            mfa_token_ttl_minutes: 5,
+            totp_issuer: "CurveMatch".to_string(),
+            admin_emails: Vec::new(),
+        }
+    }
+}
+
+/// Failed-login throttling. After the allowed attempts each further failure
+/// locks the account (or address) for twice as long as the previous one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoginConfig {
+    pub max_attempts_per_account: u32,
+    pub max_attempts_per_ip: u32,
+    pub lockout_base_secs: u64,
+    pub lockout_max_secs: u64,
+    /// Failures older than this are forgotten
+    pub attempt_window_secs: u64,
+    /// Use the last `X-Forwarded-For` address as the client address; only
+    /// behind a single trusted proxy that appends to the header
+    pub trust_forwarded_for: bool,
+}
+
+impl Default for LoginConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts_per_account: 5,
+            max_attempts_per_ip: 20,
+            lockout_base_secs: 30,
+            lockout_max_secs: 15 * 60,
+            attempt_window_secs: 15 * 60,
+            trust_forwarded_for: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PasswordConfig {
+    pub min_length: usize,
+    /// In bytes; bcrypt ignores anything past 72
+    pub max_length: usize,
+    /// Known-breached passwords, one per line, either plain text or
+    /// SHA-1 hex as in the Pwned Passwords downloads (`HASH:count`)
+    pub breached_list_path: Option<String>,
+}
+
+impl Default for PasswordConfig {
+    fn default() -> Self {
+        Self {
+            min_length: 8,
+            max_length: 72,
+            breached_list_path: None,
+        }
+    }
+}
+
+/// Matching settings used for any field a `/api/match` request leaves out
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchingDefaults {
+    pub distance_flexibility: f64,
+    pub elevation_flexibility: f64,
+    pub shape_importance: f64,
+    pub shape_metric: ShapeMetric,
+    pub turns_importance: f64,
+    #[serde(default)]
+    pub curvature_importance: f64,
+    pub elevation_importance: f64,
+    pub granularity_meters: f64,
+    pub min_match_percentage: f64,
+    pub max_results: usize,
+}
+
+impl Default for MatchingDefaults {
+    fn default() -> Self {
+        let engine = MatchingConfig::default();
+        Self {
+            distance_flexibility: engine.distance_flexibility,
+            elevation_flexibility: engine.elevation_flexibility,
+            shape_importance: engine.shape_importance,
+            shape_metric: engine.shape_metric,
+            turns_importance: engine.turns_importance,
+            curvature_importance: engine.curvature_importance,
+            elevation_importance: engine.elevation_importance,
+            granularity_meters: engine.granularity_meters,
+            min_match_percentage: engine.min_match_percentage,
+            max_results: engine.max_results,
+        }
+    }
+}
+
+impl MatchingDefaults {
+    pub fn matching_config(&self) -> MatchingConfig {
+        MatchingConfig {
+            distance_flexibility: self.distance_flexibility,
+            elevation_flexibility: self.elevation_flexibility,
+            shape_importance: self.shape_importance,
+            shape_metric: self.shape_metric,
+            turns_importance: self.turns_importance,
+            curvature_importance: self.curvature_importance,
+            elevation_importance: self.elevation_importance,
+            granularity_meters: self.granularity_meters,
+            min_match_percentage: self.min_match_percentage,
+            max_results: self.max_results,
+            ..Default::default()
+        }
+    }
+}
+
+/// Optional datasets used to enrich routes
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DataConfig {
+    pub osm_pbf_path: Option<String>,
+    pub dem_dir: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MailTransport {
+    /// Write messages to `outbox_dir` instead of sending them
+    #[serde(rename = "outbox")]
+    Outbox,
+    #[serde(rename = "smtp")]
+    Smtp,
+}
+
+/// Outgoing mail for password resets and email verification
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MailConfig {
+    pub transport: MailTransport,
+    pub from: String,
+    /// Frontend address that links in emails point to
+    pub link_base_url: String,
+    pub outbox_dir: String,
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub smtp_username: Option<String>,
+    pub smtp_password: Option<String>,
+    /// Upgrade the connection with STARTTLS; disable only for local relays
+    pub smtp_starttls: bool,
+}
+
+impl Default for MailConfig {
+    fn default() -> Self {
+        Self {
+            transport: MailTransport::Outbox,
+            from: "CurveMatch <no-reply@localhost>".to_string(),
+            link_base_url: "http://localhost:5173".to_string(),
+            outbox_dir: "./outbox".to_string(),
+            smtp_host: "localhost".to_string(),
+            smtp_port: 587,
+            smtp_username: None,
+            smtp_password: None,
+            smtp_starttls: true,
+        }
+    }
+}
+
+/// Sign-in through external OpenID Connect providers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OidcConfig {
+    /// Frontend address the browser returns to after signing in
     pub frontend_url: String,
+    /// Keyed by the id used in `/api/oidc/{id}/...`
+    #[serde(default)]
+    pub providers: BTreeMap<String, OidcProviderConfig>,
 }
 
+impl Default for OidcConfig {
+    fn default() -> Self {
+        Self {
+            frontend_url: "http://localhost:5173".to_string(),
+            providers: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OidcProviderConfig {
+    /// Shown on the login button
+    pub display_name: String,
+    /// Discovery is read from `{issuer}/.well-known/openid-configuration`
+    pub issuer: String,
+    pub client_id: String,
+    /// Omit for public clients, which rely on PKCE alone
+    #[serde(default)]
+    pub client_secret: Option<String>,
+    /// Must match the callback registered with the provider,
+    /// e.g. `https://curvematch.example/api/oidc/{id}/callback`
+    pub redirect_uri: String,
+    #[serde(default = "default_oidc_scopes")]
+    pub scopes: Vec<String>,
+}
+
+fn default_oidc_scopes() -> Vec<String> {
+    vec!["openid".to_string(), "email".to_string(), "profile".to_string()]
+}
+
+/// Unprefixed variables from the original `.env` layout, still honoured
+const LEGACY_ENV: &[(&str, &str)] = &[
+    ("DATABASE_URL", "database.url"),
+    ("JWT_SECRET", "auth.jwt_secret"),
+    ("SERVER_HOST", "server.host"),
+    ("SERVER_PORT", "server.port"),
+    ("OSM_PBF_PATH", "data.osm_pbf_path"),
+    ("DEM_DIR", "data.dem_dir"),
+];
+
 impl Config {
-    pub fn from_env() -> anyhow::Result<Self> {
+    /// Load settings for the server process from `.env`, the config file,
+    /// the environment and command-line flags
+    pub fn load() -> anyhow::Result<Self> {
         dotenvy::dotenv().ok();
-        
-        Ok(Config {
-            database_url: std::env::var("DATABASE_URL")
-                .unwrap_or_else(|_| "sqlite:./curvematch.db".to_string()),
-            jwt_secret: std::env::var("JWT_SECRET")
-                .unwrap_or_else(|_| "default-secret-change-in-production".to_string()),
-            server_host: std::env::var("SERVER_HOST")
-                .unwrap_or_else(|_| "127.0.0.1".to_string()),
-            server_port: std::env::var("SERVER_PORT")
-                .unwrap_or_else(|_| "3000".to_string())
-                .parse()?,
-            frontend_url: std::env::var("FRONTEND_URL")
-                .unwrap_or_else(|_| "http://localhost:5173".to_string()),
-        })
+
+        let cli = CliArgs::parse(std::env::args().skip(1))?;
+        let config = Self::from_layers(&cli, std::env::vars().collect())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Merge all layers over the given environment variables
+    pub fn from_layers(cli: &CliArgs, env: HashMap<String, String>) -> anyhow::Result<Self> {
+        let mut builder = config::Config::builder()
+            .add_source(config::Config::try_from(&Config::default())?);
+
+        // TOML file: required when named explicitly, optional otherwise
+        let named = cli.config_file.clone().or_else(|| env.get("CURVEMATCH_CONFIG").map(PathBuf::from));
+        builder = match named {
+            Some(path) => builder.add_source(File::from(path).format(FileFormat::Toml).required(true)),
+            None => builder.add_source(
+                File::from(Path::new(DEFAULT_CONFIG_FILE)).format(FileFormat::Toml).required(false),
+            ),
+        };
+
+        // Environment: legacy names first, then CURVEMATCH_SECTION__KEY
+        let mut legacy = config::Config::builder();
+        for (var, key) in LEGACY_ENV {
+            legacy = legacy.set_override_option(*key, env.get(*var).cloned())?;
+        }
+        if let Some(origins) = env.get("FRONTEND_URL") {
+            legacy = legacy.set_override("server.cors_origins", split_list(origins))?;
+        }
+        builder = builder.add_source(legacy.build()?);
+
+        builder = builder.add_source(
+            Environment::with_prefix("CURVEMATCH")
+                .prefix_separator("_")
+                .separator("__")
+                .list_separator(",")
+                .with_list_parse_key("server.cors_origins")
+                .with_list_parse_key("auth.admin_emails")
+                .try_parsing(true)
+                .source(Some(env)),
+        );
+
+        builder = cli.apply(builder)?;
+
+        let mut config: Config = builder.build()?.try_deserialize()?;
+        // Lists from the environment keep the spaces around separators
+        config.server.cors_origins = split_list(&config.server.cors_origins.join(","));
+        config.auth.admin_emails = split_list(&config.auth.admin_emails.join(","));
+        Ok(config)
+    }
+
+    /// Refuse settings that are unsafe or unusable
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.profile != Profile::Dev
+            && (self.auth.jwt_secret.is_empty() || self.auth.jwt_secret == DEFAULT_JWT_SECRET)
+        {
+            anyhow::bail!(
+                "auth.jwt_secret must be set (JWT_SECRET or CURVEMATCH_AUTH__JWT_SECRET) outside the dev profile"
+            );
+        }
+        if self.server.cors_origins.is_empty() {
+            anyhow::bail!("server.cors_origins must list at least one origin");
+        }
+        if self.database.max_connections == 0 {
+            anyhow::bail!("database.max_connections must be at least 1");
+        }
+        if self.auth.access_token_ttl_minutes <= 0
+            || self.auth.refresh_token_ttl_days <= 0
+            || self.auth.password_reset_ttl_minutes <= 0
+            || self.auth.email_verification_ttl_hours <= 0
+            || self.auth.mfa_token_ttl_minutes <= 0
+        {
+            anyhow::bail!("auth token lifetimes must be positive");
+        }
+        if self.login.max_attempts_per_account == 0 || self.login.max_attempts_per_ip == 0 {
+            anyhow::bail!("login attempt limits must be at least 1");
+        }
+        if self.password.min_length == 0 || self.password.min_length > self.password.max_length {
+            anyhow::bail!("password.min_length must be between 1 and password.max_length");
+        }
+        if self.profile != Profile::Dev && self.mail.transport == MailTransport::Outbox {
+            tracing::warn!("mail.transport is outbox; emails are written to {} and not sent", self.mail.outbox_dir);
+        }
+        for (id, provider) in &self.oidc.providers {
+            if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+                anyhow::bail!("oidc provider id {:?} may only contain letters, digits, '-' and '_'", id);
+            }
+            if provider.client_id.is_empty() || provider.redirect_uri.is_empty() {
+                anyhow::bail!("oidc.providers.{} needs client_id and redirect_uri", id);
+            }
+            // Plain http is only for a local provider during development
+            let allowed = provider.issuer.starts_with("https://")
+                || (self.profile == Profile::Dev && provider.issuer.starts_with("http://"));
+            if !allowed {
+                anyhow::bail!("oidc.providers.{}.issuer must be an https URL", id);
+            }
+            if !provider.scopes.iter().any(|scope| scope == "openid") {
+                anyhow::bail!("oidc.providers.{}.scopes must include openid", id);
+            }
+        }
+        self.matching.matching_config()
+            .validate()
+            .map_err(|e| anyhow::anyhow!("Invalid matching defaults: {}", e))?;
+        Ok(())
+    }
+}
+
+/// Command-line overrides, the highest-priority layer
+#[derive(Debug, Default)]
+pub struct CliArgs {
+    pub config_file: Option<PathBuf>,
+    /// `(key, value)` pairs using dotted config keys
+    pub overrides: Vec<(String, String)>,
+}
+
+impl CliArgs {
+    /// Accepts `--config PATH`, `--profile P`, `--host H`, `--port N`,
+    /// `--database-url URL` and `--set section.key=value`, each also as `--flag=value`
+    pub fn parse(args: impl IntoIterator<Item = String>) -> anyhow::Result<Self> {
+        let mut cli = CliArgs::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if arg.starts_with("--") => (flag.to_string(), Some(value.to_string())),
+                _ => (arg.clone(), None),
+            };
+            let mut value = || {
+                inline.clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| anyhow::anyhow!("Missing value for {}", flag))
+            };
+
+            match flag.as_str() {
+                "--config" => cli.config_file = Some(PathBuf::from(value()?)),
+                "--profile" => cli.overrides.push(("profile".to_string(), value()?)),
+                "--host" => cli.overrides.push(("server.host".to_string(), value()?)),
+                "--port" => cli.overrides.push(("server.port".to_string(), value()?)),
+                "--database-url" => cli.overrides.push(("database.url".to_string(), value()?)),
+                "--set" => {
+                    let pair = value()?;
+                    let (key, value) = pair.split_once('=')
+                        .ok_or_else(|| anyhow::anyhow!("--set expects key=value, got {}", pair))?;
+                    cli.overrides.push((key.trim().to_string(), value.trim().to_string()));
+                }
+                _ => anyhow::bail!("Unknown argument: {}", arg),
+            }
+        }
+
+        Ok(cli)
+    }
+
+    fn apply(&self, builder: ConfigBuilder<DefaultState>) -> anyhow::Result<ConfigBuilder<DefaultState>> {
+        let mut overrides = config::Config::builder();
+        for (key, value) in &self.overrides {
+            overrides = if key == "server.cors_origins" {
+                overrides.set_override(key.as_str(), split_list(value))?
+            } else {
+                overrides.set_override(key.as_str(), value.as_str())?
+            };
+        }
+        Ok(builder.add_source(overrides.build()?))
     }
 }
+
+fn split_list(value: &str) -> Vec<String> {
+    value.split(',')
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
@@ -10,6 +10,11 @@ pub struct DbUser {
     pub password_hash: String,
     pub role: String,
     pub created_at: String,
+    pub email_verified_at: Option<String>,
+    pub totp_secret: Option<String>,
+    pub totp_enabled_at: Option<String>,
+    pub totp_last_step: Option<i64>,
+    pub disabled_at: Option<String>,
 }
 
 #[derive(Debug, FromRow, Serialize, Deserialize)]
@@ -28,4 +33,83 @@ pub struct DbSavedRoute {
     pub elevation_profile_json: String,
     pub search_area_json: String,
     pub gpx_data: Vec<u8>,
+    pub curvature_per_km: Option<f64>,
+    pub tight_bends: Option<i64>,
+    pub medium_bends: Option<i64>,
+    pub sweeping_bends: Option<i64>,
+}
+
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct DbSession {
+    pub id: String,
+    pub user_id: i64,
+    pub refresh_token_hash: String,
+    pub user_agent: Option<String>,
+    pub access_jti: String,
+    pub access_expires_at: i64,
+    pub created_at: String,
+    pub last_seen_at: String,
+    pub expires_at: i64,
+    pub revoked_at: Option<String>,
+}
+
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct DbAuthToken {
+    pub id: i64,
+    pub user_id: i64,
+    pub purpose: String,
+    pub token_hash: String,
+    pub expires_at: i64,
+    pub used_at: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct DbAuditEntry {
+    pub id: i64,
+    pub event: String,
+    pub user_id: Option<i64>,
+    pub email: Option<String>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub detail: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct DbUserIdentity {
+    pub id: i64,
+    pub user_id: i64,
+    pub provider: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub created_at: String,
+    pub last_login_at: Option<String>,
+}
+
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct DbApiKey {
+    pub id: i64,
+    pub user_id: i64,
+    pub name: String,
+    pub prefix: String,
+    pub key_hash: String,
+    /// Space-separated, e.g. `library:read match:run`
+    pub scopes: String,
+    pub created_at: String,
+    pub last_used_at: Option<String>,
+    pub revoked_at: Option<String>,
+}
+
+/// Counts for the admin dashboard
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct DbPlatformStats {
+    pub users: i64,
+    pub curators: i64,
+    pub admins: i64,
+    pub disabled_users: i64,
+    pub saved_routes: i64,
+    pub public_routes: i64,
+    pub active_sessions: i64,
+    pub active_api_keys: i64,
 }
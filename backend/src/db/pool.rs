@@ -1,10 +1,11 @@
 use sqlx::sqlite::{SqlitePool, SqlitePoolOptions};
 use std::time::Duration;
+use crate::config::DatabaseConfig;
 
-pub async fn create_pool(database_url: &str) -> Result<SqlitePool, sqlx::Error> {
+pub async fn create_pool(config: &DatabaseConfig) -> Result<SqlitePool, sqlx::Error> {
     SqlitePoolOptions::new()
-        .max_connections(5)
-        .acquire_timeout(Duration::from_secs(3))
-        .connect(database_url)
+        .max_connections(config.max_connections)
+        .acquire_timeout(Duration::from_secs(config.acquire_timeout_secs))
+        .connect(&config.url)
         .await
 }
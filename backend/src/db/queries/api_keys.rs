@@ -0,0 +1,107 @@
+use sqlx::SqlitePool;
+use crate::db::models::DbApiKey;
+use crate::error::AppError;
+
+pub async fn create_api_key(
+    pool: &SqlitePool,
+    user_id: i64,
+    name: &str,
+    prefix: &str,
+    key_hash: &str,
+    scopes: &str,
+) -> Result<DbApiKey, AppError> {
+    let result = sqlx::query_as::<_, DbApiKey>(
+        r#"
+        INSERT INTO api_keys (user_id, name, prefix, key_hash, scopes)
+        VALUES (?1, ?2, ?3, ?4, ?5)
+        RETURNING *
+        "#,
+    )
+    .bind(user_id)
+    .bind(name)
+    .bind(prefix)
+    .bind(key_hash)
+    .bind(scopes)
+    .fetch_one(pool)
+    .await?;
+    
+    Ok(result)
+}
+
+/// An unrevoked key of an enabled account by the digest of its secret
+pub async fn find_active_api_key(
+    pool: &SqlitePool,
+    key_hash: &str,
+) -> Result<Option<DbApiKey>, AppError> {
+    let result = sqlx::query_as::<_, DbApiKey>(
+        r#"
+        SELECT api_keys.* FROM api_keys
+        JOIN users ON users.id = api_keys.user_id
+        WHERE api_keys.key_hash = ?1
+          AND api_keys.revoked_at IS NULL
+          AND users.disabled_at IS NULL
+        "#,
+    )
+    .bind(key_hash)
+    .fetch_optional(pool)
+    .await?;
+    
+    Ok(result)
+}
+
+pub async fn get_user_api_keys(
+    pool: &SqlitePool,
+    user_id: i64,
+) -> Result<Vec<DbApiKey>, AppError> {
+    let result = sqlx::query_as::<_, DbApiKey>(
+        r#"
+        SELECT * FROM api_keys
+        WHERE user_id = ?1 AND revoked_at IS NULL
+        ORDER BY created_at DESC, id DESC
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+    
+    Ok(result)
+}
+
+/// Record a use of the key, at most once a minute to spare the database
+pub async fn touch_api_key(
+    pool: &SqlitePool,
+    id: i64,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        UPDATE api_keys SET last_used_at = CURRENT_TIMESTAMP
+        WHERE id = ?1
+          AND (last_used_at IS NULL OR last_used_at < datetime('now', '-1 minute'))
+        "#,
+    )
+    .bind(id)
+    .execute(pool)
+    .await?;
+    
+    Ok(())
+}
+
+/// Returns whether a key belonging to the user was revoked
+pub async fn revoke_api_key(
+    pool: &SqlitePool,
+    user_id: i64,
+    id: i64,
+) -> Result<bool, AppError> {
+    let result = sqlx::query(
+        r#"
+        UPDATE api_keys SET revoked_at = CURRENT_TIMESTAMP
+        WHERE id = ?1 AND user_id = ?2 AND revoked_at IS NULL
+        "#,
+    )
+    .bind(id)
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+    
+    Ok(result.rows_affected() > 0)
+}
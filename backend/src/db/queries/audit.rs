@@ -0,0 +1,47 @@
+use sqlx::SqlitePool;
+use crate::db::models::DbAuditEntry;
+use crate::error::AppError;
+
+pub async fn insert_audit_entry(
+    pool: &SqlitePool,
+    event: &str,
+    user_id: Option<i64>,
+    email: Option<&str>,
+    ip_address: Option<&str>,
+    user_agent: Option<&str>,
+    detail: Option<&str>,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        INSERT INTO audit_log (event, user_id, email, ip_address, user_agent, detail)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+        "#,
+    )
+    .bind(event)
+    .bind(user_id)
+    .bind(email)
+    .bind(ip_address)
+    .bind(user_agent)
+    .bind(detail)
+    .execute(pool)
+    .await?;
+    
+    Ok(())
+}
+
+/// Most recent entries first
+pub async fn get_audit_entries(
+    pool: &SqlitePool,
+    limit: i64,
+) -> Result<Vec<DbAuditEntry>, AppError> {
+    let entries = sqlx::query_as::<_, DbAuditEntry>(
+        r#"
+        SELECT * FROM audit_log ORDER BY id DESC LIMIT ?1
+        "#,
+    )
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+    
+    Ok(entries)
+}
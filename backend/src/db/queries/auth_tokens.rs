@@ -0,0 +1,93 @@
+use sqlx::SqlitePool;
+use crate::db::models::DbAuthToken;
+use crate::error::AppError;
+
+pub async fn create_auth_token(
+    pool: &SqlitePool,
+    user_id: i64,
+    purpose: &str,
+    token_hash: &str,
+    expires_at: i64,
+) -> Result<DbAuthToken, AppError> {
+    let token = sqlx::query_as::<_, DbAuthToken>(
+        r#"
+        INSERT INTO auth_tokens (user_id, purpose, token_hash, expires_at)
+        VALUES (?1, ?2, ?3, ?4)
+        RETURNING *
+        "#,
+    )
+    .bind(user_id)
+    .bind(purpose)
+    .bind(token_hash)
+    .bind(expires_at)
+    .fetch_one(pool)
+    .await?;
+    
+    Ok(token)
+}
+
+/// An unused token that hasn't expired at `now` (unix seconds)
+pub async fn find_auth_token(
+    pool: &SqlitePool,
+    purpose: &str,
+    token_hash: &str,
+    now: i64,
+) -> Result<Option<DbAuthToken>, AppError> {
+    let token = sqlx::query_as::<_, DbAuthToken>(
+        r#"
+        SELECT * FROM auth_tokens
+        WHERE token_hash = ?1 AND purpose = ?2 AND used_at IS NULL AND expires_at > ?3
+        "#,
+    )
+    .bind(token_hash)
+    .bind(purpose)
+    .bind(now)
+    .fetch_optional(pool)
+    .await?;
+    
+    Ok(token)
+}
+
+/// Mark a token as used, returning it only if it was unused and not
+/// expired at `now` (unix seconds)
+pub async fn consume_auth_token(
+    pool: &SqlitePool,
+    purpose: &str,
+    token_hash: &str,
+    now: i64,
+) -> Result<Option<DbAuthToken>, AppError> {
+    let token = sqlx::query_as::<_, DbAuthToken>(
+        r#"
+        UPDATE auth_tokens SET used_at = CURRENT_TIMESTAMP
+        WHERE token_hash = ?1 AND purpose = ?2 AND used_at IS NULL AND expires_at > ?3
+        RETURNING *
+        "#,
+    )
+    .bind(token_hash)
+    .bind(purpose)
+    .bind(now)
+    .fetch_optional(pool)
+    .await?;
+    
+    Ok(token)
+}
+
+/// Invalidate every outstanding token of one purpose for a user
+pub async fn invalidate_auth_tokens(
+    pool: &SqlitePool,
+    user_id: i64,
+    purpose: &str,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        UPDATE auth_tokens SET used_at = CURRENT_TIMESTAMP
+        WHERE user_id = ?1 AND purpose = ?2 AND used_at IS NULL
+        "#,
+    )
+    .bind(user_id)
+    .bind(purpose)
+    .execute(pool)
+    .await?;
+    
+    Ok(())
+}
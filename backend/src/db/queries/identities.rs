@@ -0,0 +1,66 @@
+use sqlx::SqlitePool;
+use crate::db::models::DbUserIdentity;
+use crate::error::AppError;
+
+pub async fn find_identity(
+    pool: &SqlitePool,
+    provider: &str,
+    subject: &str,
+) -> Result<Option<DbUserIdentity>, AppError> {
+    let result = sqlx::query_as::<_, DbUserIdentity>(
+        r#"
+        SELECT * FROM user_identities WHERE provider = ?1 AND subject = ?2
+        "#,
+    )
+    .bind(provider)
+    .bind(subject)
+    .fetch_optional(pool)
+    .await?;
+    
+    Ok(result)
+}
+
+pub async fn create_identity(
+    pool: &SqlitePool,
+    user_id: i64,
+    provider: &str,
+    subject: &str,
+    email: Option<&str>,
+) -> Result<DbUserIdentity, AppError> {
+    let result = sqlx::query_as::<_, DbUserIdentity>(
+        r#"
+        INSERT INTO user_identities (user_id, provider, subject, email, last_login_at)
+        VALUES (?1, ?2, ?3, ?4, CURRENT_TIMESTAMP)
+        RETURNING *
+        "#,
+    )
+    .bind(user_id)
+    .bind(provider)
+    .bind(subject)
+    .bind(email)
+    .fetch_one(pool)
+    .await?;
+    
+    Ok(result)
+}
+
+/// Note a sign-in through the identity and refresh the email the provider reports
+pub async fn touch_identity(
+    pool: &SqlitePool,
+    id: i64,
+    email: Option<&str>,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        UPDATE user_identities
+        SET last_login_at = CURRENT_TIMESTAMP, email = COALESCE(?2, email)
+        WHERE id = ?1
+        "#,
+    )
+    .bind(id)
+    .bind(email)
+    .execute(pool)
+    .await?;
+    
+    Ok(())
+}
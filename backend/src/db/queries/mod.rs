@@ -1,2 +1,10 @@
 pub mod users;
 pub mod routes;
+pub mod sessions;
+pub mod auth_tokens;
+pub mod audit;
+pub mod recovery_codes;
+pub mod identities;
+pub mod api_keys;
+pub mod public_routes;
+pub mod stats;
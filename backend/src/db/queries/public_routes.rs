@@ -0,0 +1,129 @@
+use sqlx::SqlitePool;
+use crate::db::models::DbSavedRoute;
+use crate::error::AppError;
+
+/// Add a route to the public corpus; publishing it again is a no-op
+pub async fn publish_route(
+    pool: &SqlitePool,
+    route_id: i64,
+    published_by: i64,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        INSERT INTO public_routes (route_id, published_by)
+        VALUES (?1, ?2)
+        ON CONFLICT(route_id) DO NOTHING
+        "#,
+    )
+    .bind(route_id)
+    .bind(published_by)
+    .execute(pool)
+    .await?;
+    
+    Ok(())
+}
+
+/// Take a route out of the public corpus; returns whether it was public
+pub async fn unpublish_route(
+    pool: &SqlitePool,
+    route_id: i64,
+) -> Result<bool, AppError> {
+    let result = sqlx::query(
+        r#"
+        DELETE FROM public_routes WHERE route_id = ?1
+        "#,
+    )
+    .bind(route_id)
+    .execute(pool)
+    .await?;
+    
+    Ok(result.rows_affected() > 0)
+}
+
+/// Most recently published first
+pub async fn get_public_routes(
+    pool: &SqlitePool,
+) -> Result<Vec<DbSavedRoute>, AppError> {
+    let routes = sqlx::query_as::<_, DbSavedRoute>(
+        r#"
+        SELECT saved_routes.* FROM saved_routes
+        JOIN public_routes ON public_routes.route_id = saved_routes.id
+        ORDER BY public_routes.published_at DESC, saved_routes.id DESC
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+    
+    Ok(routes)
+}
+
+/// Ids of all public routes, for loading the match index
+pub async fn get_public_route_ids(
+    pool: &SqlitePool,
+) -> Result<Vec<i64>, sqlx::Error> {
+    let ids = sqlx::query_scalar::<_, i64>(
+        r#"
+        SELECT route_id FROM public_routes
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+    
+    Ok(ids)
+}
+
+pub async fn get_public_route(
+    pool: &SqlitePool,
+    id: i64,
+) -> Result<Option<DbSavedRoute>, AppError> {
+    let route = sqlx::query_as::<_, DbSavedRoute>(
+        r#"
+        SELECT saved_routes.* FROM saved_routes
+        JOIN public_routes ON public_routes.route_id = saved_routes.id
+        WHERE saved_routes.id = ?1
+        "#,
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await?;
+    
+    Ok(route)
+}
+
+/// Rename a public route, whoever saved it; returns whether it was found
+pub async fn rename_public_route(
+    pool: &SqlitePool,
+    id: i64,
+    name: &str,
+) -> Result<bool, AppError> {
+    let result = sqlx::query(
+        r#"
+        UPDATE saved_routes SET name = ?1
+        WHERE id = ?2 AND id IN (SELECT route_id FROM public_routes)
+        "#,
+    )
+    .bind(name)
+    .bind(id)
+    .execute(pool)
+    .await?;
+    
+    Ok(result.rows_affected() > 0)
+}
+
+/// Delete a public route, whoever saved it; returns whether it was found
+pub async fn delete_public_route(
+    pool: &SqlitePool,
+    id: i64,
+) -> Result<bool, AppError> {
+    let result = sqlx::query(
+        r#"
+        DELETE FROM saved_routes
+        WHERE id = ?1 AND id IN (SELECT route_id FROM public_routes)
+        "#,
+    )
+    .bind(id)
+    .execute(pool)
+    .await?;
+    
+    Ok(result.rows_affected() > 0)
+}
@@ -0,0 +1,87 @@
+use sqlx::SqlitePool;
+use crate::error::AppError;
+
+/// Replace all of a user's recovery codes with new ones
+pub async fn replace_recovery_codes(
+    pool: &SqlitePool,
+    user_id: i64,
+    code_hashes: &[String],
+) -> Result<(), AppError> {
+    let mut tx = pool.begin().await?;
+    
+    sqlx::query(
+        r#"
+        DELETE FROM recovery_codes WHERE user_id = ?1
+        "#,
+    )
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+    
+    for code_hash in code_hashes {
+        sqlx::query(
+            r#"
+            INSERT INTO recovery_codes (user_id, code_hash) VALUES (?1, ?2)
+            "#,
+        )
+        .bind(user_id)
+        .bind(code_hash)
+        .execute(&mut *tx)
+        .await?;
+    }
+    
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Mark a code as used; false if it doesn't exist or was already used
+pub async fn consume_recovery_code(
+    pool: &SqlitePool,
+    user_id: i64,
+    code_hash: &str,
+) -> Result<bool, AppError> {
+    let result = sqlx::query(
+        r#"
+        UPDATE recovery_codes SET used_at = CURRENT_TIMESTAMP
+        WHERE user_id = ?1 AND code_hash = ?2 AND used_at IS NULL
+        "#,
+    )
+    .bind(user_id)
+    .bind(code_hash)
+    .execute(pool)
+    .await?;
+    
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn count_unused_recovery_codes(
+    pool: &SqlitePool,
+    user_id: i64,
+) -> Result<i64, AppError> {
+    let count: (i64,) = sqlx::query_as(
+        r#"
+        SELECT COUNT(*) FROM recovery_codes WHERE user_id = ?1 AND used_at IS NULL
+        "#,
+    )
+    .bind(user_id)
+    .fetch_one(pool)
+    .await?;
+    
+    Ok(count.0)
+}
+
+pub async fn delete_recovery_codes(
+    pool: &SqlitePool,
+    user_id: i64,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        DELETE FROM recovery_codes WHERE user_id = ?1
+        "#,
+    )
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+    
+    Ok(())
+}
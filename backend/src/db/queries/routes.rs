@@ -1,45 +1,58 @@
 use sqlx::SqlitePool;
 use crate::db::models::DbSavedRoute;
 use crate::error::AppError;
+use crate::matching::twistiness::Twistiness;
+
+/// Columns of a route row as it is saved
+pub struct NewRoute<'a> {
+    pub user_id: i64,
+    pub name: &'a str,
+    pub tag: &'a str,
+    pub distance_m: f64,
+    pub elevation_gain_m: f64,
+    pub gain_per_km: f64,
+    pub curve_score: f64,
+    pub match_pct: f64,
+    pub geom_wkt: &'a str,
+    pub elevation_profile_json: &'a str,
+    pub search_area_json: &'a str,
+    pub gpx_data: &'a [u8],
+    /// Left empty for the startup backfill to measure
+    pub twistiness: Option<&'a Twistiness>,
+}
 
 pub async fn save_route(
     pool: &SqlitePool,
-    user_id: i64,
-    name: &str,
-    tag: &str,
-    distance_m: f64,
-    elevation_gain_m: f64,
-    gain_per_km: f64,
-    curve_score: f64,
-    match_pct: f64,
-    geom_wkt: &str,
-    elevation_profile_json: &str,
-    search_area_json: &str,
-    gpx_data: &[u8],
+    route: NewRoute<'_>,
 ) -> Result<DbSavedRoute, AppError> {
     let result = sqlx::query_as::<_, DbSavedRoute>(
         r#"
         INSERT INTO saved_routes (
             user_id, name, tag, distance_m, elevation_gain_m,
             gain_per_km, curve_score, match_pct, geom_wkt,
-            elevation_profile_json, search_area_json, gpx_data
+            elevation_profile_json, search_area_json, gpx_data,
+            curvature_per_km, tight_bends, medium_bends, sweeping_bends
         )
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)
         RETURNING *
         "#,
     )
-    .bind(user_id)
-    .bind(name)
-    .bind(tag)
-    .bind(distance_m)
-    .bind(elevation_gain_m)
-    .bind(gain_per_km)
-    .bind(curve_score)
-    .bind(match_pct)
-    .bind(geom_wkt)
-    .bind(elevation_profile_json)
-    .bind(search_area_json)
-    .bind(gpx_data)
+    .bind(route.user_id)
+    .bind(route.name)
+    .bind(route.tag)
+    .bind(route.distance_m)
+    .bind(route.elevation_gain_m)
+    .bind(route.gain_per_km)
+    .bind(route.curve_score)
+    .bind(route.match_pct)
+    .bind(route.geom_wkt)
+    .bind(route.elevation_profile_json)
+    .bind(route.search_area_json)
+    .bind(route.gpx_data)
+    .bind(route.twistiness.map(|t| t.curvature_per_km))
+    .bind(route.twistiness.map(|t| t.tight_bends))
+    .bind(route.twistiness.map(|t| t.medium_bends))
+    .bind(route.twistiness.map(|t| t.sweeping_bends))
     .fetch_one(pool)
     .await?;
     
@@ -64,54 +77,63 @@ pub async fn get_user_routes(
     Ok(routes)
 }
 
+/// Fetch a route owned by `user_id`
 pub async fn get_route_by_id(
     pool: &SqlitePool,
     id: i64,
+    user_id: i64,
 ) -> Result<Option<DbSavedRoute>, AppError> {
     let route = sqlx::query_as::<_, DbSavedRoute>(
         r#"
-        SELECT * FROM saved_routes WHERE id = ?1
+        SELECT * FROM saved_routes WHERE id = ?1 AND user_id = ?2
         "#,
     )
     .bind(id)
+    .bind(user_id)
     .fetch_optional(pool)
     .await?;
     
     Ok(route)
 }
 
+/// Delete a route owned by `user_id`; returns whether a row was removed
 pub async fn delete_route_by_id(
     pool: &SqlitePool,
     id: i64,
-) -> Result<(), AppError> {
-    sqlx::query(
+    user_id: i64,
+) -> Result<bool, AppError> {
+    let result = sqlx::query(
         r#"
-        DELETE FROM saved_routes WHERE id = ?1
+        DELETE FROM saved_routes WHERE id = ?1 AND user_id = ?2
         "#,
     )
     .bind(id)
+    .bind(user_id)
     .execute(pool)
     .await?;
     
-    Ok(())
+    Ok(result.rows_affected() > 0)
 }
 
+/// Rename a route owned by `user_id`; returns whether a row was updated
 pub async fn update_route_name(
     pool: &SqlitePool,
     id: i64,
+    user_id: i64,
     name: &str,
-) -> Result<(), AppError> {
-    sqlx::query(
+) -> Result<bool, AppError> {
+    let result = sqlx::query(
         r#"
-        UPDATE saved_routes SET name = ?1 WHERE id = ?2
+        UPDATE saved_routes SET name = ?1 WHERE id = ?2 AND user_id = ?3
         "#,
     )
     .bind(name)
     .bind(id)
+    .bind(user_id)
     .execute(pool)
     .await?;
     
-    Ok(())
+    Ok(result.rows_affected() > 0)
 }
 
 
@@ -128,4 +150,47 @@ pub async fn get_all_routes(
     .await?;
     
     Ok(routes)
-}
\ No newline at end of file
+}
+
+/// Routes saved before twistiness was recorded
+pub async fn get_routes_without_twistiness(
+    pool: &SqlitePool,
+) -> Result<Vec<DbSavedRoute>, sqlx::Error> {
+    let routes = sqlx::query_as::<_, DbSavedRoute>(
+        r#"
+        SELECT * FROM saved_routes WHERE curvature_per_km IS NULL
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+    
+    Ok(routes)
+}
+
+/// Store the twistiness of a route saved before it was measured, and the
+/// curve score derived from it
+pub async fn set_route_twistiness(
+    pool: &SqlitePool,
+    id: i64,
+    twistiness: &Twistiness,
+) -> Result<Option<DbSavedRoute>, sqlx::Error> {
+    let route = sqlx::query_as::<_, DbSavedRoute>(
+        r#"
+        UPDATE saved_routes
+        SET curve_score = ?1, curvature_per_km = ?2, tight_bends = ?3,
+            medium_bends = ?4, sweeping_bends = ?5
+        WHERE id = ?6
+        RETURNING *
+        "#,
+    )
+    .bind(twistiness.curve_score())
+    .bind(twistiness.curvature_per_km)
+    .bind(twistiness.tight_bends)
+    .bind(twistiness.medium_bends)
+    .bind(twistiness.sweeping_bends)
+    .bind(id)
+    .fetch_optional(pool)
+    .await?;
+    
+    Ok(route)
+}
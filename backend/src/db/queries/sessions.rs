@@ -0,0 +1,204 @@
+use sqlx::SqlitePool;
+use crate::db::models::DbSession;
+use crate::error::AppError;
+
+/// Columns of a session row as it is opened
+pub struct NewSession<'a> {
+    pub id: &'a str,
+    pub user_id: i64,
+    pub refresh_token_hash: &'a str,
+    pub user_agent: Option<&'a str>,
+    pub access_jti: &'a str,
+    pub access_expires_at: i64,
+    pub expires_at: i64,
+}
+
+pub async fn create_session(
+    pool: &SqlitePool,
+    session: NewSession<'_>,
+) -> Result<DbSession, AppError> {
+    let session = sqlx::query_as::<_, DbSession>(
+        r#"
+        INSERT INTO sessions (
+            id, user_id, refresh_token_hash, user_agent,
+            access_jti, access_expires_at, expires_at
+        )
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+        RETURNING *
+        "#,
+    )
+    .bind(session.id)
+    .bind(session.user_id)
+    .bind(session.refresh_token_hash)
+    .bind(session.user_agent)
+    .bind(session.access_jti)
+    .bind(session.access_expires_at)
+    .bind(session.expires_at)
+    .fetch_one(pool)
+    .await?;
+    
+    Ok(session)
+}
+
+pub async fn find_session(
+    pool: &SqlitePool,
+    id: &str,
+) -> Result<Option<DbSession>, AppError> {
+    let session = sqlx::query_as::<_, DbSession>(
+        r#"
+        SELECT * FROM sessions WHERE id = ?1
+        "#,
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await?;
+    
+    Ok(session)
+}
+
+/// Replace a session's refresh token and access token id, provided its
+/// refresh token is still `old_refresh_token_hash`. Returns false if another
+/// refresh got there first.
+pub async fn rotate_session(
+    pool: &SqlitePool,
+    id: &str,
+    old_refresh_token_hash: &str,
+    refresh_token_hash: &str,
+    access_jti: &str,
+    access_expires_at: i64,
+) -> Result<bool, AppError> {
+    let result = sqlx::query(
+        r#"
+        UPDATE sessions
+        SET refresh_token_hash = ?1, access_jti = ?2, access_expires_at = ?3,
+            last_seen_at = CURRENT_TIMESTAMP
+        WHERE id = ?4 AND refresh_token_hash = ?5 AND revoked_at IS NULL
+        "#,
+    )
+    .bind(refresh_token_hash)
+    .bind(access_jti)
+    .bind(access_expires_at)
+    .bind(id)
+    .bind(old_refresh_token_hash)
+    .execute(pool)
+    .await?;
+    
+    Ok(result.rows_affected() == 1)
+}
+
+/// Record a use of the session's access token, at most once a minute to
+/// spare the database
+pub async fn touch_session(
+    pool: &SqlitePool,
+    id: &str,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        UPDATE sessions SET last_seen_at = CURRENT_TIMESTAMP
+        WHERE id = ?1 AND last_seen_at < datetime('now', '-1 minute')
+        "#,
+    )
+    .bind(id)
+    .execute(pool)
+    .await?;
+    
+    Ok(())
+}
+
+/// Sessions that are neither revoked nor expired at `now` (unix seconds)
+pub async fn get_active_sessions(
+    pool: &SqlitePool,
+    user_id: i64,
+    now: i64,
+) -> Result<Vec<DbSession>, AppError> {
+    let sessions = sqlx::query_as::<_, DbSession>(
+        r#"
+        SELECT * FROM sessions
+        WHERE user_id = ?1 AND revoked_at IS NULL AND expires_at > ?2
+        ORDER BY last_seen_at DESC
+        "#,
+    )
+    .bind(user_id)
+    .bind(now)
+    .fetch_all(pool)
+    .await?;
+    
+    Ok(sessions)
+}
+
+/// Revoke one of a user's sessions, returning it if it was active
+pub async fn revoke_session(
+    pool: &SqlitePool,
+    id: &str,
+    user_id: i64,
+) -> Result<Option<DbSession>, AppError> {
+    let session = sqlx::query_as::<_, DbSession>(
+        r#"
+        UPDATE sessions SET revoked_at = CURRENT_TIMESTAMP
+        WHERE id = ?1 AND user_id = ?2 AND revoked_at IS NULL
+        RETURNING *
+        "#,
+    )
+    .bind(id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+    
+    Ok(session)
+}
+
+/// Revoke every active session of a user, returning the revoked sessions
+pub async fn revoke_user_sessions(
+    pool: &SqlitePool,
+    user_id: i64,
+) -> Result<Vec<DbSession>, AppError> {
+    let sessions = sqlx::query_as::<_, DbSession>(
+        r#"
+        UPDATE sessions SET revoked_at = CURRENT_TIMESTAMP
+        WHERE user_id = ?1 AND revoked_at IS NULL
+        RETURNING *
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+    
+    Ok(sessions)
+}
+
+pub async fn insert_revoked_token(
+    pool: &SqlitePool,
+    jti: &str,
+    expires_at: i64,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        INSERT OR IGNORE INTO revoked_tokens (jti, expires_at) VALUES (?1, ?2)
+        "#,
+    )
+    .bind(jti)
+    .bind(expires_at)
+    .execute(pool)
+    .await?;
+    
+    Ok(())
+}
+
+/// Drop revocations for tokens that have expired anyway, then load the rest
+pub async fn get_revoked_tokens(
+    pool: &SqlitePool,
+    now: i64,
+) -> Result<Vec<(String, i64)>, sqlx::Error> {
+    sqlx::query("DELETE FROM revoked_tokens WHERE expires_at <= ?1")
+        .bind(now)
+        .execute(pool)
+        .await?;
+    
+    sqlx::query_as::<_, (String, i64)>(
+        r#"
+        SELECT jti, expires_at FROM revoked_tokens
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,27 @@
+use sqlx::SqlitePool;
+use crate::db::models::DbPlatformStats;
+use crate::error::AppError;
+
+pub async fn get_platform_stats(
+    pool: &SqlitePool,
+    now: i64,
+) -> Result<DbPlatformStats, AppError> {
+    let stats = sqlx::query_as::<_, DbPlatformStats>(
+        r#"
+        SELECT
+            (SELECT COUNT(*) FROM users) AS users,
+            (SELECT COUNT(*) FROM users WHERE role = 'curator') AS curators,
+            (SELECT COUNT(*) FROM users WHERE role = 'admin') AS admins,
+            (SELECT COUNT(*) FROM users WHERE disabled_at IS NOT NULL) AS disabled_users,
+            (SELECT COUNT(*) FROM saved_routes) AS saved_routes,
+            (SELECT COUNT(*) FROM public_routes) AS public_routes,
+            (SELECT COUNT(*) FROM sessions WHERE revoked_at IS NULL AND expires_at > ?1) AS active_sessions,
+            (SELECT COUNT(*) FROM api_keys WHERE revoked_at IS NULL) AS active_api_keys
+        "#,
+    )
+    .bind(now)
+    .fetch_one(pool)
+    .await?;
+    
+    Ok(stats)
+}
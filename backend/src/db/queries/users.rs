@@ -57,3 +57,214 @@ pub async fn find_user_by_id(
     
     Ok(result)
 }
+
+pub async fn find_user_by_username(
+    pool: &SqlitePool,
+    username: &str,
+) -> Result<Option<DbUser>, AppError> {
+    let result = sqlx::query_as::<_, DbUser>(
+        r#"
+        SELECT * FROM users WHERE username = ?1
+        "#,
+    )
+    .bind(username)
+    .fetch_optional(pool)
+    .await?;
+    
+    Ok(result)
+}
+
+pub async fn update_password(
+    pool: &SqlitePool,
+    id: i64,
+    password_salt: &str,
+    password_hash: &str,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        UPDATE users SET password_salt = ?1, password_hash = ?2 WHERE id = ?3
+        "#,
+    )
+    .bind(password_salt)
+    .bind(password_hash)
+    .bind(id)
+    .execute(pool)
+    .await?;
+    
+    Ok(())
+}
+
+/// Record that the user proved they own their email address
+pub async fn mark_email_verified(
+    pool: &SqlitePool,
+    id: i64,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        UPDATE users SET email_verified_at = CURRENT_TIMESTAMP
+        WHERE id = ?1 AND email_verified_at IS NULL
+        "#,
+    )
+    .bind(id)
+    .execute(pool)
+    .await?;
+    
+    Ok(())
+}
+
+/// Store a new TOTP secret awaiting activation; replaces any pending one
+pub async fn set_pending_totp_secret(
+    pool: &SqlitePool,
+    id: i64,
+    secret: &str,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        UPDATE users SET totp_secret = ?1, totp_last_step = NULL
+        WHERE id = ?2 AND totp_enabled_at IS NULL
+        "#,
+    )
+    .bind(secret)
+    .bind(id)
+    .execute(pool)
+    .await?;
+    
+    Ok(())
+}
+
+pub async fn enable_totp(
+    pool: &SqlitePool,
+    id: i64,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        UPDATE users SET totp_enabled_at = CURRENT_TIMESTAMP
+        WHERE id = ?1 AND totp_secret IS NOT NULL
+        "#,
+    )
+    .bind(id)
+    .execute(pool)
+    .await?;
+    
+    Ok(())
+}
+
+pub async fn disable_totp(
+    pool: &SqlitePool,
+    id: i64,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        UPDATE users SET totp_secret = NULL, totp_enabled_at = NULL, totp_last_step = NULL
+        WHERE id = ?1
+        "#,
+    )
+    .bind(id)
+    .execute(pool)
+    .await?;
+    
+    Ok(())
+}
+
+/// Record the time step of an accepted code; false if that step (or a
+/// later one) was already used
+pub async fn record_totp_step(
+    pool: &SqlitePool,
+    id: i64,
+    step: i64,
+) -> Result<bool, AppError> {
+    let result = sqlx::query(
+        r#"
+        UPDATE users SET totp_last_step = ?1
+        WHERE id = ?2 AND (totp_last_step IS NULL OR totp_last_step < ?1)
+        "#,
+    )
+    .bind(step)
+    .bind(id)
+    .execute(pool)
+    .await?;
+    
+    Ok(result.rows_affected() > 0)
+}
+
+
+/// Newest accounts first
+pub async fn list_users(
+    pool: &SqlitePool,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<DbUser>, AppError> {
+    let users = sqlx::query_as::<_, DbUser>(
+        r#"
+        SELECT * FROM users ORDER BY id DESC LIMIT ?1 OFFSET ?2
+        "#,
+    )
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await?;
+    
+    Ok(users)
+}
+
+/// Returns the updated user, or `None` if there is no such account
+pub async fn set_user_role(
+    pool: &SqlitePool,
+    id: i64,
+    role: &str,
+) -> Result<Option<DbUser>, AppError> {
+    let result = sqlx::query_as::<_, DbUser>(
+        r#"
+        UPDATE users SET role = ?1 WHERE id = ?2
+        RETURNING *
+        "#,
+    )
+    .bind(role)
+    .bind(id)
+    .fetch_optional(pool)
+    .await?;
+    
+    Ok(result)
+}
+
+/// Disable or re-enable an account; returns the updated user, or `None` if
+/// there is no such account
+pub async fn set_user_disabled(
+    pool: &SqlitePool,
+    id: i64,
+    disabled: bool,
+) -> Result<Option<DbUser>, AppError> {
+    let result = sqlx::query_as::<_, DbUser>(
+        r#"
+        UPDATE users
+        SET disabled_at = CASE WHEN ?1 THEN COALESCE(disabled_at, CURRENT_TIMESTAMP) ELSE NULL END
+        WHERE id = ?2
+        RETURNING *
+        "#,
+    )
+    .bind(disabled)
+    .bind(id)
+    .fetch_optional(pool)
+    .await?;
+    
+    Ok(result)
+}
+
+/// Make a verified account an admin; used at startup to bootstrap the
+/// first administrator. Returns whether such an account exists.
+pub async fn promote_to_admin(
+    pool: &SqlitePool,
+    email: &str,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        UPDATE users SET role = 'admin'
+        WHERE email = ?1 AND email_verified_at IS NOT NULL
+        "#,
+    )
+    .bind(email)
+    .execute(pool)
+    .await?;
+    
+    Ok(result.rows_affected() > 0)
+}
@@ -1,5 +1,5 @@
 use axum::{
-    http::StatusCode,
+    http::{header, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
@@ -11,6 +11,11 @@ pub enum AppError {
     // Authentication errors
     Unauthorized,
     Forbidden,
+    // Missing CSRF token, or one that doesn't match its cookie
+    InvalidCsrfToken,
+    
+    // Rate limiting; seconds until the client may retry
+    TooManyRequests(u64),
     
     // Database errors
     DatabaseError(sqlx::Error),
@@ -36,6 +41,8 @@ impl fmt::Display for AppError {
         match self {
             AppError::Unauthorized => write!(f, "Unauthorized"),
             AppError::Forbidden => write!(f, "Forbidden"),
+            AppError::InvalidCsrfToken => write!(f, "Invalid CSRF token"),
+            AppError::TooManyRequests(secs) => write!(f, "Too many requests, retry in {}s", secs),
             AppError::DatabaseError(e) => write!(f, "Database error: {}", e),
             AppError::BadRequest(msg) => write!(f, "Bad request: {}", msg),
             AppError::NotFound(msg) => write!(f, "Not found: {}", msg),
@@ -48,9 +55,16 @@ impl fmt::Display for AppError {
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
+        let retry_after = match self {
+            AppError::TooManyRequests(secs) => Some(secs),
+            _ => None,
+        };
+        
         let (status, error_message) = match self {
             AppError::Unauthorized => (StatusCode::UNAUTHORIZED, "Unauthorized"),
             AppError::Forbidden => (StatusCode::FORBIDDEN, "Forbidden"),
+            AppError::InvalidCsrfToken => (StatusCode::FORBIDDEN, "Invalid CSRF token"),
+            AppError::TooManyRequests(_) => (StatusCode::TOO_MANY_REQUESTS, "Too many failed attempts, please try again later"),
             AppError::DatabaseError(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Database error"),
             AppError::BadRequest(ref msg) => (StatusCode::BAD_REQUEST, msg.as_str()),
             AppError::NotFound(ref msg) => (StatusCode::NOT_FOUND, msg.as_str()),
@@ -63,7 +77,10 @@ impl IntoResponse for AppError {
             "error": error_message,
         }));
 
-        (status, body).into_response()
+        match retry_after {
+            Some(secs) => (status, [(header::RETRY_AFTER, secs.to_string())], body).into_response(),
+            None => (status, body).into_response(),
+        }
     }
 }
 
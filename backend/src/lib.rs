@@ -3,8 +3,10 @@ pub mod auth;
 pub mod config;
 pub mod db;
 pub mod error;
+pub mod mail;
 pub mod matching;
 pub mod models;
+pub mod state;
 pub mod utils;
 
 pub use error::AppError;
@@ -0,0 +1,63 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+use crate::config::{MailConfig, MailTransport};
+use crate::error::AppError;
+
+pub mod outbox;
+pub mod smtp;
+
+pub use outbox::OutboxMailer;
+pub use smtp::SmtpMailer;
+
+/// A plain-text message to a single recipient
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+impl Email {
+    pub fn password_reset(to: &str, username: &str, link: &str, ttl_minutes: i64) -> Self {
+        Self {
+            to: to.to_string(),
+            subject: "Reset your CurveMatch password".to_string(),
+            body: format!(
+                "Hi {},\n\n\
+                 Someone asked to reset the password for your CurveMatch account.\n\
+                 Use the link below within {} minutes to choose a new one:\n\n\
+                 {}\n\n\
+                 If this wasn't you, you can ignore this email; your password stays the same.\n",
+                username, ttl_minutes, link
+            ),
+        }
+    }
+
+    pub fn email_verification(to: &str, username: &str, link: &str, ttl_hours: i64) -> Self {
+        Self {
+            to: to.to_string(),
+            subject: "Confirm your CurveMatch email address".to_string(),
+            body: format!(
+                "Hi {},\n\n\
+                 Please confirm this email address for your CurveMatch account.\n\
+                 The link below is valid for {} hours:\n\n\
+                 {}\n",
+                username, ttl_hours, link
+            ),
+        }
+    }
+}
+
+/// Delivers outgoing email
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: &Email) -> Result<(), AppError>;
+}
+
+/// Build the mailer selected by `mail.transport`
+pub fn from_config(config: &MailConfig) -> anyhow::Result<Arc<dyn Mailer>> {
+    Ok(match config.transport {
+        MailTransport::Outbox => Arc::new(OutboxMailer::new(&config.outbox_dir)?),
+        MailTransport::Smtp => Arc::new(SmtpMailer::new(config)?),
+    })
+}
@@ -0,0 +1,69 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use std::path::{Path, PathBuf};
+use crate::error::AppError;
+use super::{Email, Mailer};
+
+/// Writes each message to a file instead of sending it, for local
+/// development and tests
+pub struct OutboxMailer {
+    dir: PathBuf,
+}
+
+impl OutboxMailer {
+    pub fn new(dir: impl AsRef<Path>) -> std::io::Result<Self> {
+        std::fs::create_dir_all(dir.as_ref())?;
+        Ok(Self {
+            dir: dir.as_ref().to_path_buf(),
+        })
+    }
+
+    /// Messages in the outbox, oldest first
+    pub fn messages(&self) -> std::io::Result<Vec<Email>> {
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "eml"))
+            .collect();
+        paths.sort();
+
+        paths.iter()
+            .map(|path| Ok(parse_message(&std::fs::read_to_string(path)?)))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl Mailer for OutboxMailer {
+    async fn send(&self, email: &Email) -> Result<(), AppError> {
+        // Timestamp first so file names sort in sending order
+        let name = format!(
+            "{}-{}.eml",
+            Utc::now().format("%Y%m%dT%H%M%S%.6f"),
+            uuid::Uuid::new_v4()
+        );
+        let contents = format!("To: {}\nSubject: {}\n\n{}", email.to, email.subject, email.body);
+
+        tokio::fs::write(self.dir.join(&name), contents)
+            .await
+            .map_err(|e| AppError::InternalServerError(anyhow::anyhow!("Failed to write {}: {}", name, e)))?;
+
+        tracing::info!("Wrote email to {} into the outbox: {}", email.to, name);
+        Ok(())
+    }
+}
+
+fn parse_message(contents: &str) -> Email {
+    let (headers, body) = contents.split_once("\n\n").unwrap_or((contents, ""));
+    let header = |name: &str| {
+        headers.lines()
+            .find_map(|line| line.strip_prefix(name))
+            .unwrap_or_default()
+            .to_string()
+    };
+
+    Email {
+        to: header("To: "),
+        subject: header("Subject: "),
+        body: body.to_string(),
+    }
+}
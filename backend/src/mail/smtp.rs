@@ -0,0 +1,58 @@
+use async_trait::async_trait;
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use crate::config::MailConfig;
+use crate::error::AppError;
+use super::{Email, Mailer};
+
+/// Sends mail through an SMTP relay
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    pub fn new(config: &MailConfig) -> anyhow::Result<Self> {
+        let builder = if config.smtp_starttls {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.smtp_host)?
+        } else {
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.smtp_host)
+        };
+        let mut builder = builder.port(config.smtp_port);
+
+        if let (Some(username), Some(password)) = (&config.smtp_username, &config.smtp_password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        Ok(Self {
+            transport: builder.build(),
+            from: config.from.parse()
+                .map_err(|e| anyhow::anyhow!("Invalid mail.from address: {}", e))?,
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, email: &Email) -> Result<(), AppError> {
+        let to: Mailbox = email.to.parse()
+            .map_err(|_| AppError::BadRequest(format!("Invalid email address: {}", email.to)))?;
+
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(email.subject.as_str())
+            .header(ContentType::TEXT_PLAIN)
+            .body(email.body.clone())
+            .map_err(|e| AppError::InternalServerError(anyhow::anyhow!("Failed to build email: {}", e)))?;
+
+        self.transport.send(message)
+            .await
+            .map_err(|e| AppError::InternalServerError(anyhow::anyhow!("Failed to send email: {}", e)))?;
+
+        Ok(())
+    }
+}
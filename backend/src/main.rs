@@ -1,9 +1,11 @@
 use axum::{
     Router,
     http::{Method, header, HeaderValue},
+    middleware,
 };
 use std::net::SocketAddr;
-use tower_http::cors::CorsLayer;
+use std::sync::Arc;
+use tower_http::cors::{AllowOrigin, CorsLayer};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 mod api;
@@ -11,12 +13,26 @@ mod auth;
 mod config;
 mod db;
 mod error;
+mod mail;
 mod matching;
 mod models;
+mod state;
 mod utils;
 
+use crate::auth::csrf;
+use crate::auth::oidc::OidcProviders;
+use crate::auth::password::PasswordPolicy;
+use crate::auth::session::RevocationList;
+use crate::auth::throttle::LoginThrottle;
 use crate::config::Config;
 use crate::db::pool::create_pool;
+use crate::db::queries::routes::{get_routes_without_twistiness, set_route_twistiness};
+use crate::db::queries::users::promote_to_admin;
+use crate::matching::road_class::RoadNetwork;
+use crate::matching::spatial_index::{parse_route_geometry, IndexSources, SharedSpatialIndex};
+use crate::matching::twistiness::Twistiness;
+use crate::state::AppState;
+use crate::utils::dem::DemProvider;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -29,17 +45,96 @@ async fn main() -> anyhow::Result<()> {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
-    // Load configuration
-    let config = Config::from_env()?;
+    // Load configuration: defaults, config file, environment, then CLI flags
+    let config = Config::load()?;
+    tracing::info!("Starting with the {:?} profile", config.profile);
     
     // Connect to database using create_pool function
-    let pool = create_pool(&config.database_url).await?;
+    let pool = create_pool(&config.database).await?;
     
     // Run migrations
     sqlx::migrate!("./migrations").run(&pool).await?;
     
+    // Bootstrap administrators named in the config
+    for email in &config.auth.admin_emails {
+        if !promote_to_admin(&pool, email).await? {
+            tracing::warn!("auth.admin_emails: no verified account for {}", email);
+        }
+    }
+    
+    // Measure routes saved before twistiness was recorded
+    let unmeasured = get_routes_without_twistiness(&pool).await?;
+    for route in &unmeasured {
+        let twistiness = parse_route_geometry(&route.geom_wkt)
+            .map(|line| Twistiness::of_route(&line))
+            .unwrap_or_default();
+        set_route_twistiness(&pool, route.id, &twistiness).await?;
+    }
+    if !unmeasured.is_empty() {
+        tracing::info!("Measured twistiness of {} saved routes", unmeasured.len());
+    }
+    
+    // Load the OSM road network used for safety mode filtering
+    let road_network = match config.data.osm_pbf_path {
+        Some(ref path) => {
+            let network = RoadNetwork::from_pbf(std::path::Path::new(path))
+                .map_err(|e| anyhow::anyhow!("Failed to load OSM extract: {}", e))?;
+            Some(Arc::new(network))
+        }
+        None => {
+            tracing::warn!("data.osm_pbf_path (OSM_PBF_PATH) not set, safety mode filtering is disabled");
+            None
+        }
+    };
+    
+    // Open the local DEM used to fill in missing elevation
+    let dem = match config.data.dem_dir {
+        Some(ref dir) => {
+            let provider = DemProvider::open(std::path::Path::new(dir))
+                .map_err(|e| anyhow::anyhow!("Failed to open DEM directory: {}", e))?;
+            Some(Arc::new(provider))
+        }
+        None => {
+            tracing::warn!("data.dem_dir (DEM_DIR) not set, tracks without elevation cannot be matched on elevation");
+            None
+        }
+    };
+    
+    // Build the route index once; handlers keep it in sync on writes
+    let sources = IndexSources {
+        road_network: road_network.clone(),
+        dem: dem.clone(),
+    };
+    let spatial_index = Arc::new(SharedSpatialIndex::load(&pool, sources).await?);
+    
+    // Revoked access tokens that haven't expired yet
+    let revoked_tokens = Arc::new(RevocationList::load(&pool).await?);
+    
+    // Password reset and verification emails
+    let mailer = mail::from_config(&config.mail)?;
+    
+    let password_policy = Arc::new(PasswordPolicy::load(&config.password)?);
+    let login_throttle = Arc::new(LoginThrottle::new(config.login.clone()));
+    let oidc = Arc::new(OidcProviders::new(&config.oidc)?);
+    
+    let config = Arc::new(config);
+    let state = AppState {
+        config: config.clone(),
+        pool,
+        road_network,
+        dem,
+        spatial_index,
+        revoked_tokens,
+        mailer,
+        password_policy,
+        login_throttle,
+        oidc,
+    };
+    
     // Set up CORS with more permissive settings for multipart
-    let frontend_url = config.frontend_url.parse::<HeaderValue>()?;
+    let origins = config.server.cors_origins.iter()
+        .map(|origin| origin.parse::<HeaderValue>())
+        .collect::<Result<Vec<_>, _>>()?;
     
     let cors = CorsLayer::new()
         .allow_methods([
@@ -50,7 +145,7 @@ async fn main() -> anyhow::Result<()> {
             Method::PATCH,
             Method::OPTIONS,
         ])
-        .allow_origin(frontend_url)
+        .allow_origin(AllowOrigin::list(origins))
         .allow_headers([
             header::CONTENT_TYPE,
             header::AUTHORIZATION,
@@ -58,6 +153,7 @@ async fn main() -> anyhow::Result<()> {
             header::ORIGIN,
             header::ACCESS_CONTROL_REQUEST_METHOD,
             header::ACCESS_CONTROL_REQUEST_HEADERS,
+            csrf::CSRF_HEADER,
         ])
         .allow_credentials(true)
         .expose_headers([
@@ -68,17 +164,17 @@ async fn main() -> anyhow::Result<()> {
     
     // Build our application with routes
     let app = Router::new()
-        .nest("/api", api::routes())
+        .nest("/api", api::routes(state.clone()))
+        .layer(middleware::from_fn_with_state(state.clone(), csrf::protect))
         .layer(cors)
-        .with_state(pool);
+        .with_state(state);
     
     // Run the server
-    let addr = SocketAddr::from(([127, 0, 0, 1], config.server_port));
-    tracing::info!("Server listening on {}", addr);
-    tracing::info!("Accepting requests from: {}", config.frontend_url);
+    let listener = tokio::net::TcpListener::bind((config.server.host.as_str(), config.server.port)).await?;
+    tracing::info!("Server listening on {}", listener.local_addr()?);
+    tracing::info!("Accepting requests from: {}", config.server.cors_origins.join(", "));
     
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
     
     Ok(())
 }
\ No newline at end of file
@@ -1,7 +1,10 @@
 use geo::{LineString, Point};
 use std::f64::consts::PI;
+use crate::utils::elevation::calculate_elevation_stats;
+use super::projection::{planar_distance, project_together, LocalProjection};
+use super::turns::TurnSequence;
 
-/// Calculate turn angle between three consecutive points
+/// Signed turn angle between three consecutive points, positive to the left
 fn calculate_turn_angle(p1: &Point<f64>, p2: &Point<f64>, p3: &Point<f64>) -> f64 {
     let dx1 = p2.x() - p1.x();
     let dy1 = p2.y() - p1.y();
@@ -21,7 +24,7 @@ fn calculate_turn_angle(p1: &Point<f64>, p2: &Point<f64>, p3: &Point<f64>) -> f6
         angle_diff += 2.0 * PI;
     }
     
-    angle_diff.abs()
+    angle_diff
 }
 
 /// Count significant turns in a route (turns > threshold radians)
@@ -35,7 +38,7 @@ pub fn count_turns(line: &LineString<f64>, threshold_degrees: f64) -> usize {
     
     let mut turn_count = 0;
     for i in 1..points.len() - 1 {
-        let angle = calculate_turn_angle(&points[i-1], &points[i], &points[i+1]);
+        let angle = calculate_turn_angle(&points[i-1], &points[i], &points[i+1]).abs();
         if angle > threshold_rad {
             turn_count += 1;
         }
@@ -44,23 +47,6 @@ pub fn count_turns(line: &LineString<f64>, threshold_degrees: f64) -> usize {
     turn_count
 }
 
-/// Calculate total curvature of a route
-pub fn calculate_curvature(line: &LineString<f64>) -> f64 {
-    let points: Vec<Point<f64>> = line.points().collect();
-    
-    if points.len() < 3 {
-        return 0.0;
-    }
-    
-    let mut total_curvature = 0.0;
-    for i in 1..points.len() - 1 {
-        let angle = calculate_turn_angle(&points[i-1], &points[i], &points[i+1]);
-        total_curvature += angle;
-    }
-    
-    total_curvature
-}
-
 /// Calculate rolling gradients for a route
 pub fn calculate_rolling_gradients(
     elevations: &[f64],
@@ -251,12 +237,21 @@ pub fn rolling_gradient_elevation_similarity(
     gradient_profile_similarity(&gradients1, &gradients2)
 }
 
-/// Original Hausdorff distance for shape matching (optional)
+/// Hausdorff shape similarity (0-1) between two lon/lat lines
 pub fn hausdorff_distance(line1: &LineString<f64>, line2: &LineString<f64>) -> f64 {
+    match project_together([line1, line2]) {
+        Some([projected1, projected2]) => shape_similarity(discrete_hausdorff_distance(&projected1, &projected2)),
+        None => 0.0,
+    }
+}
+
+/// Hausdorff distance in metres between the vertices of two lines projected
+/// into the same local frame (see [`super::projection`])
+pub fn discrete_hausdorff_distance(line1: &LineString<f64>, line2: &LineString<f64>) -> f64 {
     let max_dist1 = line1.points()
         .map(|p1| {
             line2.points()
-                .map(|p2| euclidean_distance(&p1, &p2))
+                .map(|p2| planar_distance(&p1, &p2))
                 .fold(f64::INFINITY, f64::min)
         })
         .fold(0.0, f64::max);
@@ -264,40 +259,134 @@ pub fn hausdorff_distance(line1: &LineString<f64>, line2: &LineString<f64>) -> f
     let max_dist2 = line2.points()
         .map(|p2| {
             line1.points()
-                .map(|p1| euclidean_distance(&p1, &p2))
+                .map(|p1| planar_distance(&p1, &p2))
                 .fold(f64::INFINITY, f64::min)
         })
         .fold(0.0, f64::max);
     
-    let hausdorff = max_dist1.max(max_dist2);
-    
-    // Convert to similarity score (inverse)
-    1.0 / (1.0 + hausdorff / 1000.0)
+    max_dist1.max(max_dist2)
 }
 
-/// Turn sequence similarity
+/// Turn sequence similarity between two lon/lat lines: compares the order,
+/// direction, size and position of their turns (see [`TurnSequence`])
 pub fn turn_sequence_similarity(line1: &LineString<f64>, line2: &LineString<f64>) -> f64 {
-    let turns1 = count_turns(line1, 30.0);
-    let turns2 = count_turns(line2, 30.0);
-    
-    if turns1 == 0 && turns2 == 0 {
-        return 1.0;
+    turn_sequence(line1).similarity(&turn_sequence(line2))
+}
+
+/// Turns of a lon/lat line, read in a frame centred on the line
+pub fn turn_sequence(line: &LineString<f64>) -> TurnSequence {
+    LocalProjection::centred_on(&[line])
+        .map(|projection| TurnSequence::from_projected(&projection.project_line(line)))
+        .unwrap_or_default()
+}
+
+/// Distance (metres) at which a shape score falls to one half
+const SHAPE_SCALE_M: f64 = 1000.0;
+
+/// Convert a shape distance in metres to a 0-1 similarity score
+pub fn shape_similarity(distance_m: f64) -> f64 {
+    1.0 / (1.0 + distance_m / SHAPE_SCALE_M)
+}
+
+/// Largest distance (metres) that still scores at least `similarity`
+pub fn shape_distance_limit(similarity: f64) -> f64 {
+    SHAPE_SCALE_M * (1.0 / similarity - 1.0)
+}
+
+/// Discrete Fréchet distance in metres between the vertices of two lines
+/// projected into the same local frame (see [`super::projection`]).
+///
+/// Both lines are walked from start to end without backtracking, so unlike
+/// Hausdorff a route ridden in reverse, or a figure-eight taken the other
+/// way round, ends up far from the original. Keeps the whole coupling
+/// table: O(n·m) time and memory.
+pub fn discrete_frechet_distance(line1: &LineString<f64>, line2: &LineString<f64>) -> f64 {
+    let a: Vec<Point<f64>> = line1.points().collect();
+    let b: Vec<Point<f64>> = line2.points().collect();
+    if a.is_empty() || b.is_empty() {
+        return f64::INFINITY;
     }
     
-    let max_turns = turns1.max(turns2) as f64;
-    let diff = (turns1 as f64 - turns2 as f64).abs();
+    let mut coupling = vec![vec![0.0; b.len()]; a.len()];
+    for (i, pa) in a.iter().enumerate() {
+        for (j, pb) in b.iter().enumerate() {
+            let d = planar_distance(pa, pb);
+            let previous = match (i, j) {
+                (0, 0) => d,
+                (0, _) => coupling[0][j - 1],
+                (_, 0) => coupling[i - 1][0],
+                _ => coupling[i - 1][j].min(coupling[i - 1][j - 1]).min(coupling[i][j - 1]),
+            };
+            coupling[i][j] = d.max(previous);
+        }
+    }
     
-    1.0 - (diff / max_turns).min(1.0)
+    coupling[a.len() - 1][b.len() - 1]
 }
 
-fn euclidean_distance(p1: &Point<f64>, p2: &Point<f64>) -> f64 {
-    let dx = p1.x() - p2.x();
-    let dy = p1.y() - p2.y();
-    (dx * dx + dy * dy).sqrt()
+/// [`discrete_frechet_distance`] keeping only two rows of the coupling
+/// table, so memory is linear in the shorter line.
+///
+/// Returns `None` if the distance exceeds `abandon_above`, usually long
+/// before the table is complete: the smallest value in a row never
+/// decreases in later rows, so once a whole row is above the threshold the
+/// result is too.
+pub fn discrete_frechet_distance_bounded(
+    line1: &LineString<f64>,
+    line2: &LineString<f64>,
+    abandon_above: Option<f64>,
+) -> Option<f64> {
+    // The distance is symmetric; keep the rows along the shorter line
+    let (outer, inner) = if line1.0.len() >= line2.0.len() { (line1, line2) } else { (line2, line1) };
+    let a: Vec<Point<f64>> = outer.points().collect();
+    let b: Vec<Point<f64>> = inner.points().collect();
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+    let limit = abandon_above.unwrap_or(f64::INFINITY);
+    
+    let mut previous = vec![0.0; b.len()];
+    let mut current = vec![0.0; b.len()];
+    for (i, pa) in a.iter().enumerate() {
+        let mut row_min = f64::INFINITY;
+        for (j, pb) in b.iter().enumerate() {
+            let d = planar_distance(pa, pb);
+            let reachable = match (i, j) {
+                (0, 0) => d,
+                (0, _) => current[j - 1],
+                (_, 0) => previous[0],
+                _ => previous[j].min(previous[j - 1]).min(current[j - 1]),
+            };
+            current[j] = d.max(reachable);
+            row_min = row_min.min(current[j]);
+        }
+        if row_min > limit {
+            return None;
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    
+    Some(previous[b.len() - 1]).filter(|distance| *distance <= limit)
 }
 
+/// Fréchet counterpart of [`hausdorff_distance`]: a 0-1 similarity between
+/// two lon/lat lines that respects direction of travel
 pub fn frechet_distance(line1: &LineString<f64>, line2: &LineString<f64>) -> f64 {
-    hausdorff_distance(line1, line2)
+    project_together([line1, line2])
+        .and_then(|[projected1, projected2]| discrete_frechet_distance_bounded(&projected1, &projected2, None))
+        .map(shape_similarity)
+        .unwrap_or(0.0)
+}
+
+/// Smallest tolerance (metres) of an elevation window, so that flat routes
+/// are not held to a sub-metre match
+pub const ELEVATION_WINDOW_FLOOR_M: f64 = 25.0;
+
+/// Check whether a candidate elevation figure lies within `flexibility`
+/// percent of the input figure (never tighter than the window floor)
+pub fn within_elevation_window(input: f64, candidate: f64, flexibility: f64) -> bool {
+    let tolerance = (input.abs() * flexibility / 100.0).max(ELEVATION_WINDOW_FLOOR_M);
+    (candidate - input).abs() <= tolerance
 }
 
 /// Legacy elevation similarity for backward compatibility
@@ -306,6 +395,12 @@ pub fn elevation_similarity(
     profile2: &[f64],
     flexibility: f64,
 ) -> f64 {
+    let gain1 = calculate_elevation_stats(profile1).total_gain;
+    let gain2 = calculate_elevation_stats(profile2).total_gain;
+    if !within_elevation_window(gain1, gain2, flexibility) {
+        return 0.0;
+    }
+    
     elevation_profile_dtw(profile1, profile2, 50)
 }
 
@@ -0,0 +1,129 @@
+use geo::{Coord, LineString, Point};
+use serde::Serialize;
+use super::projection::planar_distance;
+
+/// Points each line is resampled to before alignment
+pub const ALIGNMENT_SAMPLES: usize = 128;
+
+/// Rotation, uniform scale and translation in a local metric frame:
+/// `p' = scale · R(rotation) · p + translation`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SimilarityTransform {
+    /// Anticlockwise, in radians
+    pub rotation: f64,
+    pub scale: f64,
+    /// East and north offset in metres, applied after rotating and scaling
+    pub translation: [f64; 2],
+}
+
+impl SimilarityTransform {
+    pub fn apply(&self, point: Point<f64>) -> Point<f64> {
+        let (sin, cos) = self.rotation.sin_cos();
+        Point::new(
+            self.scale * (cos * point.x() - sin * point.y()) + self.translation[0],
+            self.scale * (sin * point.x() + cos * point.y()) + self.translation[1],
+        )
+    }
+
+    pub fn apply_line(&self, line: &LineString<f64>) -> LineString<f64> {
+        line.points()
+            .map(|point| Coord::from(self.apply(point)))
+            .collect()
+    }
+}
+
+/// Best fit of one shape onto another, whatever their position
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ShapeAlignment {
+    /// Maps the source line onto the target line
+    pub transform: SimilarityTransform,
+    /// Root-mean-square distance (metres) between the aligned samples
+    #[serde(rename = "rmsDistance")]
+    pub rms_distance: f64,
+}
+
+/// Resample a projected line to `count` points evenly spaced along its
+/// length. `None` for lines without length.
+pub fn resample_by_arc_length(line: &LineString<f64>, count: usize) -> Option<Vec<Point<f64>>> {
+    let points: Vec<Point<f64>> = line.points().collect();
+    if points.len() < 2 || count < 2 {
+        return None;
+    }
+
+    let mut cumulative = Vec::with_capacity(points.len());
+    cumulative.push(0.0);
+    for pair in points.windows(2) {
+        cumulative.push(cumulative[cumulative.len() - 1] + planar_distance(&pair[0], &pair[1]));
+    }
+    let total = cumulative[cumulative.len() - 1];
+    if total <= 0.0 {
+        return None;
+    }
+
+    let mut segment = 0;
+    let samples = (0..count)
+        .map(|k| {
+            let target = total * k as f64 / (count - 1) as f64;
+            while segment < points.len() - 2 && cumulative[segment + 1] < target {
+                segment += 1;
+            }
+            let length = cumulative[segment + 1] - cumulative[segment];
+            let t = if length > 0.0 { ((target - cumulative[segment]) / length).clamp(0.0, 1.0) } else { 0.0 };
+            let (a, b) = (points[segment], points[segment + 1]);
+            Point::new(a.x() + (b.x() - a.x()) * t, a.y() + (b.y() - a.y()) * t)
+        })
+        .collect();
+
+    Some(samples)
+}
+
+/// Procrustes (Kabsch) alignment of `source` onto `target`, both projected
+/// into local metric frames (see [`super::projection`]); the frames need
+/// not be the same.
+///
+/// Both lines are resampled to equal arc-length points which are paired in
+/// order, so like Fréchet the routes must run the same way round. Rotation
+/// and translation are always fitted; scale only with `allow_scaling`.
+pub fn procrustes_align(
+    source: &LineString<f64>,
+    target: &LineString<f64>,
+    allow_scaling: bool,
+) -> Option<ShapeAlignment> {
+    let source = resample_by_arc_length(source, ALIGNMENT_SAMPLES)?;
+    let target = resample_by_arc_length(target, ALIGNMENT_SAMPLES)?;
+    let n = ALIGNMENT_SAMPLES as f64;
+
+    let centroid = |points: &[Point<f64>]| {
+        let (x, y) = points.iter().fold((0.0, 0.0), |(x, y), p| (x + p.x(), y + p.y()));
+        Point::new(x / n, y / n)
+    };
+    let (source_centre, target_centre) = (centroid(&source), centroid(&target));
+
+    // Cross-covariance terms of the centred point sets; in 2D the optimal
+    // rotation follows directly from them
+    let (mut dot, mut cross, mut source_norm, mut target_norm) = (0.0, 0.0, 0.0, 0.0);
+    for (s, t) in source.iter().zip(&target) {
+        let (sx, sy) = (s.x() - source_centre.x(), s.y() - source_centre.y());
+        let (tx, ty) = (t.x() - target_centre.x(), t.y() - target_centre.y());
+        dot += sx * tx + sy * ty;
+        cross += sx * ty - sy * tx;
+        source_norm += sx * sx + sy * sy;
+        target_norm += tx * tx + ty * ty;
+    }
+
+    let rotation = cross.atan2(dot);
+    let fit = (dot * dot + cross * cross).sqrt();
+    let scale = if allow_scaling && source_norm > 0.0 { fit / source_norm } else { 1.0 };
+    let squared_error = (scale * scale * source_norm - 2.0 * scale * fit + target_norm).max(0.0);
+
+    let (sin, cos) = rotation.sin_cos();
+    let translation = [
+        target_centre.x() - scale * (cos * source_centre.x() - sin * source_centre.y()),
+        target_centre.y() - scale * (sin * source_centre.x() + cos * source_centre.y()),
+    ];
+
+    Some(ShapeAlignment {
+        transform: SimilarityTransform { rotation, scale, translation },
+        rms_distance: (squared_error / n).sqrt(),
+    })
+}
@@ -1,21 +1,88 @@
 use geo::LineString;
+use serde::{Deserialize, Serialize};
 use sqlx::SqlitePool;
+use std::sync::Arc;
 use crate::error::AppError;
 use super::algorithms::{
-    hausdorff_distance, elevation_similarity, frechet_distance,
-    rolling_gradient_elevation_similarity, turn_sequence_similarity,
-    count_turns, elevation_profile_dtw
+    discrete_hausdorff_distance, discrete_frechet_distance_bounded,
+    rolling_gradient_elevation_similarity, turn_sequence, within_elevation_window,
+    shape_similarity, shape_distance_limit
 };
-use super::spatial_index::SpatialIndex;
+use super::alignment::{procrustes_align, ShapeAlignment};
+use super::projection::{LocalProjection, EARTH_RADIUS_M};
+use super::road_class::{RoadClassProfile, SafetyMode};
+use super::spatial_index::{IndexSources, SpatialIndex};
+use super::twistiness::Twistiness;
+use crate::utils::elevation::calculate_elevation_stats;
 
-#[derive(Debug, Clone)]
+/// How route shapes are compared when `shape_importance` is set
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ShapeMetric {
+    /// Closest-point distance; ignores direction of travel
+    #[default]
+    #[serde(alias = "hausdorff")]
+    Hausdorff,
+    /// Discrete Fréchet distance; routes must be ridden the same way round
+    #[serde(alias = "frechet")]
+    Frechet,
+    /// Procrustes alignment; compares shape wherever the route is and
+    /// however it is rotated
+    #[serde(alias = "procrustes")]
+    Procrustes,
+}
+
+impl ShapeMetric {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "Hausdorff" | "hausdorff" => Some(ShapeMetric::Hausdorff),
+            "Frechet" | "frechet" => Some(ShapeMetric::Frechet),
+            "Procrustes" | "procrustes" => Some(ShapeMetric::Procrustes),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct MatchingConfig {
+    #[serde(rename = "distanceFlexibility")]
     pub distance_flexibility: f64,
+    #[serde(rename = "elevationFlexibility")]
     pub elevation_flexibility: f64,
+    #[serde(rename = "shapeImportance")]
     pub shape_importance: f64,
+    #[serde(rename = "shapeMetric")]
+    pub shape_metric: ShapeMetric,
+    /// Let [`ShapeMetric::Procrustes`] also fit scale, so a smaller or
+    /// larger copy of the shape still matches
+    #[serde(rename = "shapeScaling")]
+    pub shape_scaling: bool,
+    #[serde(rename = "turnsImportance")]
     pub turns_importance: f64,
+    /// Weight of matching the input route's twistiness
+    #[serde(rename = "curvatureImportance")]
+    pub curvature_importance: f64,
+    /// Only routes at least this twisty (degrees per km)
+    #[serde(rename = "minCurvaturePerKm")]
+    pub min_curvature_per_km: Option<f64>,
+    /// Only routes at most this twisty (degrees per km)
+    #[serde(rename = "maxCurvaturePerKm")]
+    pub max_curvature_per_km: Option<f64>,
+    #[serde(rename = "elevationImportance")]
     pub elevation_importance: f64,
+    #[serde(rename = "granularityMeters")]
     pub granularity_meters: f64,  // New field for gradient calculation granularity
+    #[serde(rename = "safetyMode")]
+    pub safety_mode: SafetyMode,
+    #[serde(rename = "minMatchPercentage")]
+    pub min_match_percentage: f64,
+    #[serde(rename = "maxResults")]
+    pub max_results: usize,
+    /// Also require total descent within `elevation_flexibility`
+    #[serde(rename = "matchElevationLoss")]
+    pub match_elevation_loss: bool,
+    /// Also require the min-to-max altitude range within `elevation_flexibility`
+    #[serde(rename = "matchAltitudeRange")]
+    pub match_altitude_range: bool,
 }
 
 impl Default for MatchingConfig {
@@ -24,27 +91,118 @@ impl Default for MatchingConfig {
             distance_flexibility: 20.0,
             elevation_flexibility: 20.0,
             shape_importance: 0.0,
+            shape_metric: ShapeMetric::default(),
+            shape_scaling: false,
             turns_importance: 0.0,
+            curvature_importance: 0.0,
+            min_curvature_per_km: None,
+            max_curvature_per_km: None,
             elevation_importance: 100.0,
             granularity_meters: 100.0,  // Default 100m granularity
+            safety_mode: SafetyMode::default(),
+            min_match_percentage: 25.0,  // Lowered threshold for gradient matching
+            max_results: 20,
+            match_elevation_loss: false,
+            match_altitude_range: false,
         }
     }
 }
 
+impl MatchingConfig {
+    /// Reject settings outside the ranges the engine is tuned for
+    pub fn validate(&self) -> Result<(), AppError> {
+        check_range("distanceFlexibility", self.distance_flexibility, 0.0, 100.0)?;
+        check_range("elevationFlexibility", self.elevation_flexibility, 0.0, 100.0)?;
+        check_range("shapeImportance", self.shape_importance, 0.0, 100.0)?;
+        check_range("turnsImportance", self.turns_importance, 0.0, 100.0)?;
+        check_range("curvatureImportance", self.curvature_importance, 0.0, 100.0)?;
+        for (name, value) in [
+            ("minCurvaturePerKm", self.min_curvature_per_km),
+            ("maxCurvaturePerKm", self.max_curvature_per_km),
+        ] {
+            if value.is_some_and(|value| !value.is_finite() || value < 0.0) {
+                return Err(AppError::BadRequest(format!("{} must be zero or more", name)));
+            }
+        }
+        if let (Some(min), Some(max)) = (self.min_curvature_per_km, self.max_curvature_per_km) {
+            if max < min {
+                return Err(AppError::BadRequest(
+                    "maxCurvaturePerKm must not be below minCurvaturePerKm".to_string(),
+                ));
+            }
+        }
+        check_range("elevationImportance", self.elevation_importance, 0.0, 100.0)?;
+        check_range("granularityMeters", self.granularity_meters, 10.0, 5000.0)?;
+        check_range("minMatchPercentage", self.min_match_percentage, 0.0, 100.0)?;
+        
+        if self.max_results == 0 || self.max_results > 200 {
+            return Err(AppError::BadRequest(
+                "maxResults must be between 1 and 200".to_string(),
+            ));
+        }
+        
+        Ok(())
+    }
+}
+
+/// Smallest shape score that could still reach `min_match_percentage`,
+/// assuming every score not yet computed (turns, curvature) comes out perfect
+fn required_shape_score(config: &MatchingConfig, score_so_far: f64, weight_so_far: f64, safety_factor: f64) -> f64 {
+    let threshold = config.min_match_percentage / 100.0;
+    if threshold <= 0.0 {
+        return 0.0;
+    }
+    if safety_factor <= 0.0 {
+        return f64::INFINITY;
+    }
+    
+    let remaining = config.turns_importance.max(0.0) + config.curvature_importance.max(0.0);
+    let total_weight = weight_so_far + config.shape_importance + remaining;
+    (threshold / safety_factor * total_weight - score_so_far - remaining) / config.shape_importance
+}
+
+fn check_range(name: &str, value: f64, min: f64, max: f64) -> Result<(), AppError> {
+    if !value.is_finite() || value < min || value > max {
+        return Err(AppError::BadRequest(format!(
+            "{} must be between {} and {}",
+            name, min, max
+        )));
+    }
+    Ok(())
+}
+
 pub struct MatchingEngine {
-    spatial_index: SpatialIndex,
+    spatial_index: Arc<SpatialIndex>,
+    // When set, only this user's routes and public routes are candidates
+    user_id: Option<i64>,
 }
 
 impl MatchingEngine {
     pub fn new() -> Self {
         Self {
-            spatial_index: SpatialIndex::new(),
+            spatial_index: Arc::new(SpatialIndex::new()),
+            user_id: None,
         }
     }
     
-    pub async fn from_database(pool: &SqlitePool) -> Result<Self, sqlx::Error> {
+    /// Match against a snapshot of the shared, long-lived index
+    pub fn from_index(spatial_index: Arc<SpatialIndex>) -> Self {
+        Self { spatial_index, user_id: None }
+    }
+    
+    /// Restrict candidates to the user's own routes and the public corpus
+    pub fn for_user(mut self, user_id: i64) -> Self {
+        self.user_id = Some(user_id);
+        self
+    }
+    
+    pub async fn from_database(
+        pool: &SqlitePool,
+        sources: &IndexSources,
+    ) -> Result<Self, sqlx::Error> {
         Ok(Self {
-            spatial_index: SpatialIndex::from_database(pool).await?,
+            spatial_index: Arc::new(SpatialIndex::from_database(pool, sources).await?),
+            user_id: None,
         })
     }
     
@@ -56,26 +214,45 @@ impl MatchingEngine {
         config: MatchingConfig,
     ) -> Result<Vec<MatchResult>, AppError> {
         let input_distance = calculate_distance(input_route);
-        let input_elevation_gain = calculate_elevation_gain(input_elevation);
-        let input_turns = count_turns(input_route, 30.0);
+        let input_stats = calculate_elevation_stats(input_elevation);
+        let input_elevation_gain = input_stats.total_gain;
+        let input_turns = turn_sequence(input_route);
+        let input_twistiness = Twistiness::of_route(input_route);
         
         // Create distance array for gradient matching
         let input_distances = create_distance_array(input_route);
         
+        // Shapes are compared in metres, in a frame around the input route
+        let shape_frame = (config.shape_importance > 0.0)
+            .then(|| LocalProjection::centred_on(&[input_route]))
+            .flatten()
+            .map(|projection| (projection, projection.project_line(input_route)));
+        
         tracing::info!(
-            "Searching for routes: distance={:.0}m, gain={:.0}m, turns={}, granularity={:.0}m",
-            input_distance, input_elevation_gain, input_turns, config.granularity_meters
+            "Searching for routes: distance={:.0}m, gain={:.0}m, turns={}, curvature={:.0}°/km, granularity={:.0}m",
+            input_distance, input_elevation_gain, input_turns.turns.len(),
+            input_twistiness.curvature_per_km, config.granularity_meters
         );
         
         // Define acceptable ranges
         let min_distance = input_distance * (1.0 - config.distance_flexibility / 100.0);
         let max_distance = input_distance * (1.0 + config.distance_flexibility / 100.0);
         
+        // Without input elevation there is nothing to window on
+        let apply_elevation_window = !input_elevation.is_empty();
+        if !apply_elevation_window {
+            tracing::warn!("Input route has no elevation data, skipping elevation window");
+        }
+        
         // Query spatial index for candidates within bounds
-        let candidates = self.spatial_index.query_bounds(search_bounds);
+        let candidates: Vec<_> = self.spatial_index.query_bounds(search_bounds)
+            .into_iter()
+            .filter(|candidate| self.user_id.is_none_or(|user_id| candidate.is_visible_to(user_id)))
+            .collect();
         tracing::info!("Found {} candidate routes in search area", candidates.len());
         
         let mut results = Vec::new();
+        let mut unclassified = 0;
         
         for candidate in candidates {
             // Check distance constraint
@@ -83,6 +260,66 @@ impl MatchingEngine {
                 continue;
             }
             
+            // Check twistiness range
+            let curvature = candidate.twistiness.curvature_per_km;
+            if config.min_curvature_per_km.is_some_and(|min| curvature < min)
+                || config.max_curvature_per_km.is_some_and(|max| curvature > max)
+            {
+                continue;
+            }
+            
+            // Check elevation constraints
+            if apply_elevation_window {
+                let flexibility = config.elevation_flexibility;
+                if !within_elevation_window(input_elevation_gain, candidate.elevation_gain, flexibility) {
+                    continue;
+                }
+                
+                let stats = &candidate.elevation_stats;
+                if config.match_elevation_loss
+                    && !within_elevation_window(input_stats.total_loss, stats.total_loss, flexibility)
+                {
+                    continue;
+                }
+                
+                if config.match_altitude_range
+                    && !within_elevation_window(
+                        input_stats.max_elevation - input_stats.min_elevation,
+                        stats.max_elevation - stats.min_elevation,
+                        flexibility,
+                    )
+                {
+                    continue;
+                }
+            }
+            
+            // Drop candidates with too much road the safety mode disallows,
+            // and penalise the rest by their disallowed share
+            let mut safety_factor = 1.0;
+            if config.safety_mode != SafetyMode::None {
+                match candidate.road_classes {
+                    Some(ref road_classes) => {
+                        let unsafe_share = road_classes.unsafe_share(config.safety_mode);
+                        if unsafe_share > config.safety_mode.max_unsafe_share() {
+                            tracing::debug!(
+                                "Route {} rejected by {:?} safety mode: {:.0}% disallowed roads",
+                                candidate.name, config.safety_mode, unsafe_share * 100.0
+                            );
+                            continue;
+                        }
+                        safety_factor = 1.0 - unsafe_share;
+                    }
+                    None if config.safety_mode.allows_unclassified() => unclassified += 1,
+                    None => {
+                        tracing::debug!(
+                            "Route {} rejected by {:?} safety mode: no road classification",
+                            candidate.name, config.safety_mode
+                        );
+                        continue;
+                    }
+                }
+            }
+            
             // Calculate individual scores based on importance settings
             let mut total_score = 0.0;
             let mut total_weight = 0.0;
@@ -108,25 +345,67 @@ impl MatchingEngine {
             }
             
             // Shape matching (optional)
-            if config.shape_importance > 0.0 {
-                let shape_score = hausdorff_distance(input_route, &candidate.geometry);
+            let mut shape_alignment = None;
+            let mut aligned_input = None;
+            if let Some((projection, projected_input)) = &shape_frame {
+                let shape_score = match config.shape_metric {
+                    ShapeMetric::Hausdorff => {
+                        let projected = projection.project_line(&candidate.geometry);
+                        shape_similarity(discrete_hausdorff_distance(projected_input, &projected))
+                    }
+                    ShapeMetric::Frechet => {
+                        // Stop as soon as the shape alone rules the candidate out
+                        let needed = required_shape_score(&config, total_score, total_weight, safety_factor);
+                        if needed > 1.0 {
+                            continue;
+                        }
+                        let abandon_above = (needed > 0.0).then(|| shape_distance_limit(needed));
+                        let projected = projection.project_line(&candidate.geometry);
+                        match discrete_frechet_distance_bounded(projected_input, &projected, abandon_above) {
+                            Some(distance) => shape_similarity(distance),
+                            None => continue,
+                        }
+                    }
+                    ShapeMetric::Procrustes => {
+                        // The candidate gets a frame of its own, as it may be
+                        // far from the input route
+                        let Some(frame) = LocalProjection::centred_on(&[&candidate.geometry]) else {
+                            continue;
+                        };
+                        let projected = frame.project_line(&candidate.geometry);
+                        let Some(alignment) = procrustes_align(projected_input, &projected, config.shape_scaling) else {
+                            continue;
+                        };
+                        aligned_input = Some(frame.unproject_line(&alignment.transform.apply_line(projected_input)));
+                        shape_alignment = Some(alignment);
+                        shape_similarity(alignment.rms_distance)
+                    }
+                };
                 total_score += shape_score * config.shape_importance;
                 total_weight += config.shape_importance;
             }
             
             // Turn sequence matching (optional)
             if config.turns_importance > 0.0 {
-                let turn_score = turn_sequence_similarity(input_route, &candidate.geometry);
+                let turn_score = input_turns.similarity(&turn_sequence(&candidate.geometry));
                 total_score += turn_score * config.turns_importance;
                 total_weight += config.turns_importance;
             }
             
+            // Twistiness matching (optional)
+            if config.curvature_importance > 0.0 {
+                let curvature_score = input_twistiness.similarity(&candidate.twistiness);
+                total_score += curvature_score * config.curvature_importance;
+                total_weight += config.curvature_importance;
+            }
+            
             // Calculate final score
-            let final_score = if total_weight > 0.0 {
+            let weighted_score = if total_weight > 0.0 {
                 total_score / total_weight
             } else {
                 0.5
             };
+            let final_score = weighted_score * safety_factor;
             
             let match_percentage = final_score * 100.0;
             
@@ -135,8 +414,8 @@ impl MatchingEngine {
                 candidate.name, final_score, match_percentage
             );
             
-            // Only include matches above 25% (lowered threshold for gradient matching)
-            if match_percentage >= 25.0 {
+            // Only include matches above the configured threshold
+            if match_percentage >= config.min_match_percentage {
                 results.push(MatchResult {
                     id: candidate.id.clone(),
                     name: candidate.name.clone(),
@@ -144,17 +423,33 @@ impl MatchingEngine {
                     elevation_gain: candidate.elevation_gain,
                     gain_per_km: candidate.elevation_gain / (candidate.distance / 1000.0),
                     match_percentage,
-                    curve_score: final_score,
+                    curve_score: candidate.twistiness.curve_score(),
+                    twistiness: candidate.twistiness,
                     geometry: candidate.geometry.clone(),
                     elevation_profile: candidate.elevation_profile.clone(),
+                    road_classes: candidate.road_classes.clone(),
+                    shape_alignment,
+                    aligned_input,
                 });
             }
         }
         
+        if unclassified > 0 {
+            tracing::warn!(
+                "{:?} safety mode not applied to {} routes without road classification",
+                config.safety_mode, unclassified
+            );
+        }
+        
         // Sort by match percentage descending
         results.sort_by(|a, b| b.match_percentage.partial_cmp(&a.match_percentage).unwrap());
         
-        tracing::info!("Found {} matching routes above 25% threshold", results.len());
+        tracing::info!(
+            "Found {} matching routes above {:.0}% threshold",
+            results.len(), config.min_match_percentage
+        );
+        
+        results.truncate(config.max_results);
         
         Ok(results)
     }
@@ -185,9 +480,16 @@ pub struct MatchResult {
     pub elevation_gain: f64,
     pub gain_per_km: f64,
     pub match_percentage: f64,
+    /// 0-1, from how twisty the route is (see [`Twistiness::curve_score`])
     pub curve_score: f64,
+    pub twistiness: Twistiness,
     pub geometry: LineString<f64>,
     pub elevation_profile: Vec<f64>,
+    pub road_classes: Option<RoadClassProfile>,
+    /// Procrustes fit of the input route onto this one
+    pub shape_alignment: Option<ShapeAlignment>,
+    /// The input route moved onto this one by `shape_alignment`, for overlaying
+    pub aligned_input: Option<LineString<f64>>,
 }
 
 pub fn calculate_distance(line: &LineString<f64>) -> f64 {
@@ -218,8 +520,6 @@ fn create_distance_array(line: &LineString<f64>) -> Vec<f64> {
 }
 
 pub fn haversine_distance(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
-    const R: f64 = 6371000.0;
-    
     let lat1_rad = lat1.to_radians();
     let lat2_rad = lat2.to_radians();
     let delta_lat = (lat2 - lat1).to_radians();
@@ -229,7 +529,7 @@ pub fn haversine_distance(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
         + lat1_rad.cos() * lat2_rad.cos() * (delta_lon / 2.0).sin().powi(2);
     let c = 2.0 * a.sqrt().atan2((1.0 - a).sqrt());
     
-    R * c
+    EARTH_RADIUS_M * c
 }
 
 pub fn calculate_elevation_gain(elevations: &[f64]) -> f64 {
@@ -1,3 +1,8 @@
 pub mod engine;
 pub mod algorithms;
-pub mod spatial_index;
\ No newline at end of file
+pub mod spatial_index;
+pub mod road_class;
+pub mod projection;
+pub mod alignment;
+pub mod turns;
+pub mod twistiness;
\ No newline at end of file
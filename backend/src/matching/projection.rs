@@ -0,0 +1,99 @@
+use geo::{Coord, LineString, Point};
+use super::engine::haversine_distance;
+
+/// Mean earth radius (metres) shared by the haversine and projection maths
+pub const EARTH_RADIUS_M: f64 = 6371000.0;
+
+/// Azimuthal equidistant projection around a centre point.
+///
+/// Maps lon/lat degrees to x (east) and y (north) metres. Distances from
+/// the centre are exact and other distances stay within a few millimetres
+/// per kilometre over the extent of a search area, so shape metrics can use
+/// plain euclidean distance on projected lines.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LocalProjection {
+    centre_lon: f64,
+    centre_lat: f64,
+    sin_lat0: f64,
+    cos_lat0: f64,
+}
+
+impl LocalProjection {
+    pub fn new(centre_lon: f64, centre_lat: f64) -> Self {
+        let lat0 = centre_lat.to_radians();
+        Self {
+            centre_lon,
+            centre_lat,
+            sin_lat0: lat0.sin(),
+            cos_lat0: lat0.cos(),
+        }
+    }
+
+    /// Centred on the mean vertex of `lines`; `None` if they have no points
+    pub fn centred_on(lines: &[&LineString<f64>]) -> Option<Self> {
+        let (mut lon, mut lat, mut count) = (0.0, 0.0, 0usize);
+        for coord in lines.iter().flat_map(|line| line.coords()) {
+            lon += coord.x;
+            lat += coord.y;
+            count += 1;
+        }
+
+        (count > 0).then(|| Self::new(lon / count as f64, lat / count as f64))
+    }
+
+    /// Project a lon/lat point to local x/y metres
+    pub fn project(&self, point: Point<f64>) -> Point<f64> {
+        let (lon, lat) = (point.x(), point.y());
+        let angle = haversine_distance(self.centre_lat, self.centre_lon, lat, lon) / EARTH_RADIUS_M;
+        let scale = if angle < 1e-12 { 1.0 } else { angle / angle.sin() };
+
+        let lat = lat.to_radians();
+        let delta_lon = (lon - self.centre_lon).to_radians();
+        let x = lat.cos() * delta_lon.sin();
+        let y = self.cos_lat0 * lat.sin() - self.sin_lat0 * lat.cos() * delta_lon.cos();
+
+        Point::new(EARTH_RADIUS_M * scale * x, EARTH_RADIUS_M * scale * y)
+    }
+
+    pub fn project_line(&self, line: &LineString<f64>) -> LineString<f64> {
+        line.points()
+            .map(|point| Coord::from(self.project(point)))
+            .collect()
+    }
+
+    /// Inverse of [`Self::project`]: local x/y metres back to lon/lat
+    pub fn unproject(&self, point: Point<f64>) -> Point<f64> {
+        let (x, y) = (point.x(), point.y());
+        let rho = (x * x + y * y).sqrt();
+        if rho < 1e-9 {
+            return Point::new(self.centre_lon, self.centre_lat);
+        }
+
+        let angle = rho / EARTH_RADIUS_M;
+        let (sin_c, cos_c) = angle.sin_cos();
+        let lat = (cos_c * self.sin_lat0 + y * sin_c * self.cos_lat0 / rho).asin();
+        let delta_lon = (x * sin_c).atan2(rho * self.cos_lat0 * cos_c - y * self.sin_lat0 * sin_c);
+
+        Point::new(self.centre_lon + delta_lon.to_degrees(), lat.to_degrees())
+    }
+
+    pub fn unproject_line(&self, line: &LineString<f64>) -> LineString<f64> {
+        line.points()
+            .map(|point| Coord::from(self.unproject(point)))
+            .collect()
+    }
+}
+
+/// Project lines into one frame centred on all of them, for comparing
+/// lon/lat geometries without a frame of their own
+pub fn project_together(lines: [&LineString<f64>; 2]) -> Option<[LineString<f64>; 2]> {
+    let projection = LocalProjection::centred_on(&lines)?;
+    Some(lines.map(|line| projection.project_line(line)))
+}
+
+/// Straight-line distance between two projected points
+pub fn planar_distance(p1: &Point<f64>, p2: &Point<f64>) -> f64 {
+    let dx = p1.x() - p2.x();
+    let dy = p1.y() - p2.y();
+    (dx * dx + dy * dy).sqrt()
+}
@@ -0,0 +1,217 @@
+use geo::LineString;
+use rstar::{RTree, RTreeObject, AABB};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+use crate::error::AppError;
+use crate::utils::osm_pbf::read_highway_ways;
+use super::engine::haversine_distance;
+
+/// Maximum distance (metres) between a route sample and a road for the
+/// sample to be attributed to that road
+const SNAP_TOLERANCE_M: f64 = 20.0;
+
+/// Safety category of an OSM `highway=*` value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoadSafety {
+    /// Foot, cycle and trail infrastructure
+    Trail,
+    /// Quiet streets shared with low-speed traffic
+    Residential,
+    /// Through roads carrying faster traffic
+    Arterial,
+}
+
+impl RoadSafety {
+    pub fn from_highway(highway: &str) -> Self {
+        match highway {
+            "footway" | "path" | "track" | "cycleway" | "bridleway" | "pedestrian" | "steps"
+            | "corridor" => RoadSafety::Trail,
+            "residential" | "living_street" | "service" | "unclassified" | "road" => {
+                RoadSafety::Residential
+            }
+            _ => RoadSafety::Arterial,
+        }
+    }
+}
+
+/// Safety mode selected in the filter panel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub enum SafetyMode {
+    /// Foot/trail only
+    Strict,
+    /// Trails plus residential streets
+    #[default]
+    Moderate,
+    /// No road class restrictions
+    None,
+}
+
+impl SafetyMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "Strict" | "strict" => Some(SafetyMode::Strict),
+            "Moderate" | "moderate" => Some(SafetyMode::Moderate),
+            "None" | "none" => Some(SafetyMode::None),
+            _ => None,
+        }
+    }
+
+    /// Whether a road of the given category is acceptable in this mode
+    pub fn allows(&self, safety: RoadSafety) -> bool {
+        match self {
+            SafetyMode::Strict => safety == RoadSafety::Trail,
+            SafetyMode::Moderate => safety != RoadSafety::Arterial,
+            SafetyMode::None => true,
+        }
+    }
+
+    /// Whether routes that couldn't be matched to the road network are kept.
+    /// Nothing is known about their roads, so Strict leaves them out.
+    pub fn allows_unclassified(&self) -> bool {
+        *self != SafetyMode::Strict
+    }
+
+    /// Largest share of disallowed road length tolerated before a
+    /// candidate is dropped; below it the score is penalised instead
+    pub fn max_unsafe_share(&self) -> f64 {
+        match self {
+            SafetyMode::Strict => 0.10,
+            SafetyMode::Moderate => 0.20,
+            SafetyMode::None => 1.0,
+        }
+    }
+}
+
+/// Share of a route's length on each `highway=*` class
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RoadClassProfile {
+    /// Fraction of route length per highway value
+    pub shares: HashMap<String, f64>,
+    /// Fraction of route length not within snapping distance of any road
+    pub unmatched_share: f64,
+}
+
+impl RoadClassProfile {
+    /// Share of the route length on roads the given mode does not allow
+    pub fn unsafe_share(&self, mode: SafetyMode) -> f64 {
+        self.shares
+            .iter()
+            .filter(|(highway, _)| !mode.allows(RoadSafety::from_highway(highway)))
+            .map(|(_, share)| share)
+            .sum()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct RoadSegment {
+    start: [f64; 2],
+    end: [f64; 2],
+    highway: String,
+}
+
+impl RTreeObject for RoadSegment {
+    type Envelope = AABB<[f64; 2]>;
+
+    fn envelope(&self) -> Self::Envelope {
+        AABB::from_corners(self.start, self.end)
+    }
+}
+
+/// Road network built from a local OSM extract, used to classify routes
+pub struct RoadNetwork {
+    rtree: RTree<RoadSegment>,
+}
+
+impl RoadNetwork {
+    pub fn from_pbf(path: &Path) -> Result<Self, AppError> {
+        let ways = read_highway_ways(path)?;
+
+        let segments: Vec<RoadSegment> = ways
+            .iter()
+            .flat_map(|way| {
+                way.coords.windows(2).map(move |pair| RoadSegment {
+                    start: [pair[0].0, pair[0].1],
+                    end: [pair[1].0, pair[1].1],
+                    highway: way.highway.clone(),
+                })
+            })
+            .collect();
+
+        tracing::info!("Built road network with {} segments", segments.len());
+
+        Ok(Self {
+            rtree: RTree::bulk_load(segments),
+        })
+    }
+
+    /// Highway class of the nearest road within snapping distance
+    pub fn classify_point(&self, lon: f64, lat: f64) -> Option<&str> {
+        let lat_delta = SNAP_TOLERANCE_M / 111_320.0;
+        let lon_delta = lat_delta / lat.to_radians().cos().max(0.01);
+        let query = AABB::from_corners(
+            [lon - lon_delta, lat - lat_delta],
+            [lon + lon_delta, lat + lat_delta],
+        );
+
+        self.rtree
+            .locate_in_envelope_intersecting(&query)
+            .map(|segment| (segment, point_segment_distance(lon, lat, segment)))
+            .filter(|(_, distance)| *distance <= SNAP_TOLERANCE_M)
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+            .map(|(segment, _)| segment.highway.as_str())
+    }
+
+    /// Attribute each segment of a route to the road under its midpoint
+    pub fn classify_route(&self, line: &LineString<f64>) -> RoadClassProfile {
+        let mut lengths: HashMap<String, f64> = HashMap::new();
+        let mut unmatched = 0.0;
+        let mut total = 0.0;
+
+        for segment in line.lines() {
+            let length = haversine_distance(segment.start.y, segment.start.x, segment.end.y, segment.end.x);
+            let mid_lon = (segment.start.x + segment.end.x) / 2.0;
+            let mid_lat = (segment.start.y + segment.end.y) / 2.0;
+
+            match self.classify_point(mid_lon, mid_lat) {
+                Some(highway) => *lengths.entry(highway.to_string()).or_default() += length,
+                None => unmatched += length,
+            }
+            total += length;
+        }
+
+        if total <= 0.0 {
+            return RoadClassProfile::default();
+        }
+
+        RoadClassProfile {
+            shares: lengths.into_iter().map(|(k, v)| (k, v / total)).collect(),
+            unmatched_share: unmatched / total,
+        }
+    }
+}
+
+/// Approximate distance in metres from a point to a segment, using an
+/// equirectangular projection around the point
+fn point_segment_distance(lon: f64, lat: f64, segment: &RoadSegment) -> f64 {
+    let scale_x = 111_320.0 * lat.to_radians().cos();
+    let scale_y = 110_540.0;
+
+    let ax = (segment.start[0] - lon) * scale_x;
+    let ay = (segment.start[1] - lat) * scale_y;
+    let bx = (segment.end[0] - lon) * scale_x;
+    let by = (segment.end[1] - lat) * scale_y;
+
+    let dx = bx - ax;
+    let dy = by - ay;
+    let len2 = dx * dx + dy * dy;
+    let t = if len2 > 0.0 {
+        (-(ax * dx + ay * dy) / len2).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+
+    let px = ax + t * dx;
+    let py = ay + t * dy;
+    (px * px + py * py).sqrt()
+}
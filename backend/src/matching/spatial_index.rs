@@ -1,10 +1,28 @@
 use geo::LineString;
 use rstar::{RTree, AABB, RTreeObject};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+use crate::db::models::DbSavedRoute;
+use crate::db::queries::public_routes::get_public_route_ids;
 use crate::db::queries::routes::get_all_routes;
 use sqlx::SqlitePool;
+use super::road_class::{RoadClassProfile, RoadNetwork};
+use super::twistiness::Twistiness;
+use crate::utils::dem::DemProvider;
+use crate::utils::elevation::{calculate_elevation_stats, interpolate_elevation_profile, ElevationStats};
 
+/// External data used to enrich routes as they are indexed
+#[derive(Clone, Default)]
+pub struct IndexSources {
+    pub road_network: Option<Arc<RoadNetwork>>,
+    pub dem: Option<Arc<DemProvider>>,
+}
+
+#[derive(Clone)]
 pub struct SpatialIndex {
-    rtree: RTree<RouteEntry>,
+    rtree: RTree<IndexedRoute>,
+    // Envelope of each indexed route, needed to locate entries for removal
+    envelopes: HashMap<String, AABB<[f64; 2]>>,
 }
 
 impl SpatialIndex {
@@ -12,88 +30,259 @@ impl SpatialIndex {
         // Empty index - no mock data
         Self {
             rtree: RTree::new(),
+            envelopes: HashMap::new(),
         }
     }
     
-    pub async fn from_database(pool: &SqlitePool) -> Result<Self, sqlx::Error> {
+    pub async fn from_database(
+        pool: &SqlitePool,
+        sources: &IndexSources,
+    ) -> Result<Self, sqlx::Error> {
         let db_routes = get_all_routes(pool).await?;
+        let public_ids: HashSet<i64> = get_public_route_ids(pool).await?.into_iter().collect();
         
-        let mut routes = Vec::new();
-        for route in db_routes {
-            // Parse geometry from WKT or GeoJSON
-            if let Ok(geometry) = serde_json::from_str::<serde_json::Value>(&route.geom_wkt) {
-                if let Some(coords) = geometry.get("coordinates").and_then(|c| c.as_array()) {
-                    let points: Vec<(f64, f64)> = coords.iter()
-                        .filter_map(|coord| {
-                            let arr = coord.as_array()?;
-                            Some((arr.get(0)?.as_f64()?, arr.get(1)?.as_f64()?))
-                        })
-                        .collect();
-                    
-                    if points.len() >= 2 {
-                        let line_string = LineString::from(points.clone());
-                        let elevation_profile: Vec<f64> = serde_json::from_str(&route.elevation_profile_json)
-                            .unwrap_or_default();
-                        
-                        // Calculate bounding box
-                        let (min_x, max_x) = points.iter()
-                            .map(|(x, _)| x)
-                            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), x| {
-                                (min.min(*x), max.max(*x))
-                            });
-                        let (min_y, max_y) = points.iter()
-                            .map(|(_, y)| y)
-                            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), y| {
-                                (min.min(*y), max.max(*y))
-                            });
-                        
-                        routes.push(RouteEntry {
-                            id: route.id.to_string(),
-                            name: route.name,
-                            distance: route.distance_m,
-                            elevation_gain: route.elevation_gain_m,
-                            geometry: line_string,
-                            elevation_profile,
-                            bbox: AABB::from_corners([min_x, min_y], [max_x, max_y]),
-                        });
-                    }
-                }
-            }
-        }
+        let routes: Vec<IndexedRoute> = db_routes
+            .into_iter()
+            .filter_map(|route| {
+                let public = public_ids.contains(&route.id);
+                let mut entry = RouteEntry::from_db(route, sources)?;
+                entry.public = public;
+                Some(IndexedRoute(Arc::new(entry)))
+            })
+            .collect();
         
         tracing::info!("Loaded {} routes from database", routes.len());
         
+        let envelopes = routes.iter().map(|r| (r.0.id.clone(), r.0.bbox)).collect();
+        
         Ok(Self {
             rtree: RTree::bulk_load(routes),
+            envelopes,
         })
     }
     
-    pub fn query_bounds(&self, bounds: (f64, f64, f64, f64)) -> Vec<RouteEntry> {
+    pub fn query_bounds(&self, bounds: (f64, f64, f64, f64)) -> Vec<Arc<RouteEntry>> {
         let (west, south, east, north) = bounds;
         let query_bbox = AABB::from_corners([west, south], [east, north]);
         
         self.rtree
             .locate_in_envelope_intersecting(&query_bbox)
-            .cloned()
+            .map(|route| route.0.clone())
             .collect()
     }
+    
+    pub fn len(&self) -> usize {
+        self.rtree.size()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.rtree.size() == 0
+    }
+    
+    /// Add a route, replacing any existing entry with the same id
+    pub fn insert(&mut self, entry: RouteEntry) {
+        self.remove(&entry.id);
+        self.envelopes.insert(entry.id.clone(), entry.bbox);
+        self.rtree.insert(IndexedRoute(Arc::new(entry)));
+    }
+    
+    pub fn remove(&mut self, id: &str) -> Option<Arc<RouteEntry>> {
+        let envelope = self.envelopes.remove(id)?;
+        let entry = self.rtree
+            .locate_in_envelope(&envelope)
+            .find(|entry| entry.0.id == id)
+            .cloned()?;
+        self.rtree.remove(&entry).map(|entry| entry.0)
+    }
+    
+    pub fn rename(&mut self, id: &str, name: &str) -> bool {
+        self.update(id, |entry| entry.name = name.to_string())
+    }
+    
+    pub fn set_public(&mut self, id: &str, public: bool) -> bool {
+        self.update(id, |entry| entry.public = public)
+    }
+    
+    /// Replace an entry with a changed copy; snapshots keep the original
+    fn update(&mut self, id: &str, change: impl FnOnce(&mut RouteEntry)) -> bool {
+        match self.remove(id) {
+            Some(entry) => {
+                let mut entry = Arc::unwrap_or_clone(entry);
+                change(&mut entry);
+                self.insert(entry);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl Default for SpatialIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Long-lived index shared across requests.
+///
+/// Readers take an `Arc` snapshot that stays consistent for the whole
+/// request; writers copy-on-write so in-flight snapshots are never mutated.
+/// Routes themselves are shared between copies, so a write only copies
+/// the tree structure, not any geometry.
+pub struct SharedSpatialIndex {
+    current: RwLock<Arc<SpatialIndex>>,
+    sources: IndexSources,
+}
+
+impl SharedSpatialIndex {
+    pub async fn load(pool: &SqlitePool, sources: IndexSources) -> Result<Self, sqlx::Error> {
+        let index = SpatialIndex::from_database(pool, &sources).await?;
+        Ok(Self {
+            current: RwLock::new(Arc::new(index)),
+            sources,
+        })
+    }
+    
+    pub fn snapshot(&self) -> Arc<SpatialIndex> {
+        self.current.read().unwrap().clone()
+    }
+    
+    /// Index a newly saved route (or refresh an existing one)
+    pub fn upsert(&self, route: DbSavedRoute) {
+        let Some(entry) = RouteEntry::from_db(route, &self.sources) else {
+            return;
+        };
+        let mut current = self.current.write().unwrap();
+        Arc::make_mut(&mut current).insert(entry);
+    }
+    
+    pub fn remove(&self, id: i64) {
+        let mut current = self.current.write().unwrap();
+        Arc::make_mut(&mut current).remove(&id.to_string());
+    }
+    
+    pub fn rename(&self, id: i64, name: &str) {
+        let mut current = self.current.write().unwrap();
+        Arc::make_mut(&mut current).rename(&id.to_string(), name);
+    }
+    
+    /// Track a route joining or leaving the public corpus
+    pub fn set_public(&self, id: i64, public: bool) {
+        let mut current = self.current.write().unwrap();
+        Arc::make_mut(&mut current).set_public(&id.to_string(), public);
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct RouteEntry {
     pub id: String,
+    /// Owner; other users only see the route once it is public
+    pub user_id: i64,
+    pub public: bool,
     pub name: String,
     pub distance: f64,
     pub elevation_gain: f64,
     pub geometry: LineString<f64>,
     pub elevation_profile: Vec<f64>,
+    pub elevation_stats: ElevationStats,
+    pub road_classes: Option<RoadClassProfile>,
+    pub twistiness: Twistiness,
     bbox: AABB<[f64; 2]>,
 }
 
-impl RTreeObject for RouteEntry {
+/// Parse a stored GeoJSON LineString; `None` without at least two points
+pub fn parse_route_geometry(geom_json: &str) -> Option<LineString<f64>> {
+    let geometry = serde_json::from_str::<serde_json::Value>(geom_json).ok()?;
+    let coords = geometry.get("coordinates").and_then(|c| c.as_array())?;
+    let points: Vec<(f64, f64)> = coords.iter()
+        .filter_map(|coord| {
+            let arr = coord.as_array()?;
+            Some((arr.first()?.as_f64()?, arr.get(1)?.as_f64()?))
+        })
+        .collect();
+    
+    (points.len() >= 2).then(|| LineString::from(points))
+}
+
+impl RouteEntry {
+    /// Build an index entry from a stored route, or `None` if its geometry
+    /// cannot be parsed
+    pub fn from_db(route: DbSavedRoute, sources: &IndexSources) -> Option<Self> {
+        let line_string = parse_route_geometry(&route.geom_wkt)?;
+        let mut elevation_profile: Vec<f64> = serde_json::from_str(&route.elevation_profile_json)
+            .unwrap_or_default();
+        let mut elevation_gain = route.elevation_gain_m;
+        
+        // Fill in missing elevation from the local DEM
+        if elevation_profile.is_empty() {
+            if let Some(profile) = sources.dem.as_deref()
+                .and_then(|dem| interpolate_elevation_profile(&line_string, dem))
+            {
+                elevation_gain = calculate_elevation_stats(&profile).total_gain;
+                elevation_profile = profile;
+            }
+        }
+        
+        // Calculate bounding box
+        let (min_x, max_x) = line_string.coords()
+            .map(|coord| coord.x)
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), x| {
+                (min.min(x), max.max(x))
+            });
+        let (min_y, max_y) = line_string.coords()
+            .map(|coord| coord.y)
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), y| {
+                (min.min(y), max.max(y))
+            });
+        
+        // Annotate with OSM road classes when an extract is configured
+        let road_classes = sources.road_network.as_deref()
+            .map(|network| network.classify_route(&line_string));
+        
+        let elevation_stats = calculate_elevation_stats(&elevation_profile);
+        
+        // Routes not yet backfilled are measured on the fly
+        let twistiness = Twistiness::from_db(&route)
+            .unwrap_or_else(|| Twistiness::of_route(&line_string));
+        
+        Some(RouteEntry {
+            id: route.id.to_string(),
+            user_id: route.user_id,
+            public: false,
+            name: route.name,
+            distance: route.distance_m,
+            elevation_gain,
+            geometry: line_string,
+            elevation_profile,
+            elevation_stats,
+            road_classes,
+            twistiness,
+            bbox: AABB::from_corners([min_x, min_y], [max_x, max_y]),
+        })
+    }
+}
+
+impl RouteEntry {
+    /// Users match against their own routes and the public corpus
+    pub fn is_visible_to(&self, user_id: i64) -> bool {
+        self.public || self.user_id == user_id
+    }
+}
+
+/// Tree node for a route, shared between index snapshots
+#[derive(Clone, Debug)]
+struct IndexedRoute(Arc<RouteEntry>);
+
+impl PartialEq for IndexedRoute {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.id == other.0.id
+    }
+}
+
+impl RTreeObject for IndexedRoute {
     type Envelope = AABB<[f64; 2]>;
     
     fn envelope(&self) -> Self::Envelope {
-        self.bbox
+        self.0.bbox
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,198 @@
+use geo::{LineString, Point};
+use serde::Serialize;
+use std::f64::consts::PI;
+use super::alignment::resample_by_arc_length;
+use super::projection::planar_distance;
+
+/// Spacing (metres) lines are resampled to before reading headings, so
+/// point density and GPS jitter don't create turns
+const TURN_SAMPLE_SPACING_M: f64 = 25.0;
+
+/// Stretches curving less than this (degrees per kilometre, a radius of
+/// about 950 m) count as straight
+const MIN_BEND_CURVATURE_DEG_PER_KM: f64 = 60.0;
+
+/// A straight stretch this long (metres) ends a bend
+const BEND_GAP_M: f64 = 75.0;
+
+/// Bends smaller than this (degrees) are not turns
+pub const MIN_TURN_DEG: f64 = 30.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum TurnDirection {
+    Left,
+    Right,
+}
+
+/// One bend of a route
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Turn {
+    /// Total heading change in degrees; positive turns left
+    pub angle: f64,
+    /// Metres from the start of the route to the middle of the bend
+    pub position: f64,
+    /// Metres of route over which the heading changes
+    pub length: f64,
+}
+
+impl Turn {
+    pub fn direction(&self) -> TurnDirection {
+        if self.angle >= 0.0 { TurnDirection::Left } else { TurnDirection::Right }
+    }
+
+    /// Cost of a turn with no counterpart on the other route; a hairpin costs 1
+    fn weight(&self) -> f64 {
+        self.angle.abs() / 180.0
+    }
+}
+
+/// The turns of a route in order, with its length for relative positions
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TurnSequence {
+    pub turns: Vec<Turn>,
+    pub length: f64,
+}
+
+impl TurnSequence {
+    /// Turns of a line projected into a local metric frame (see
+    /// [`super::projection`]); any frame works as turns don't depend on
+    /// position or rotation
+    pub fn from_projected(line: &LineString<f64>) -> Self {
+        Self::bends(line, MIN_TURN_DEG)
+    }
+
+    /// Like [`Self::from_projected`], keeping every bend of at least
+    /// `min_angle` degrees
+    pub fn bends(line: &LineString<f64>, min_angle: f64) -> Self {
+        let points: Vec<Point<f64>> = line.points().collect();
+        let length: f64 = points.windows(2).map(|pair| planar_distance(&pair[0], &pair[1])).sum();
+        let count = (length / TURN_SAMPLE_SPACING_M).ceil() as usize + 1;
+        let samples = match resample_by_arc_length(line, count) {
+            Some(samples) if samples.len() >= 3 => samples,
+            _ => return Self { turns: vec![], length },
+        };
+        let step = length / (samples.len() - 1) as f64;
+
+        let headings: Vec<f64> = samples.windows(2)
+            .map(|pair| (pair[1].y() - pair[0].y()).atan2(pair[1].x() - pair[0].x()))
+            .collect();
+        let changes: Vec<f64> = headings.windows(2)
+            .map(|pair| normalize_angle(pair[1] - pair[0]).to_degrees())
+            .collect();
+
+        // Spread each change over its neighbours so zig-zag jitter cancels
+        // out while a real bend keeps its total
+        let smoothed: Vec<f64> = (0..changes.len())
+            .map(|i| changes[i.saturating_sub(1)..(i + 2).min(changes.len())].iter().sum::<f64>() / 3.0)
+            .collect();
+
+        // Per sample, so that wide bends aren't lost at any spacing
+        let noise_threshold = MIN_BEND_CURVATURE_DEG_PER_KM * step / 1000.0;
+
+        let mut turns = Vec::new();
+        let mut bend = BendBuilder::default();
+        for (i, change) in smoothed.into_iter().enumerate() {
+            // Change i is at sample i + 1
+            let position = (i + 1) as f64 * step;
+            if change.abs() < noise_threshold {
+                bend.straight += step;
+                if bend.straight > BEND_GAP_M {
+                    turns.extend(bend.finish(min_angle));
+                }
+                continue;
+            }
+            if bend.angle != 0.0 && bend.angle.signum() != change.signum() {
+                turns.extend(bend.finish(min_angle));
+            }
+            bend.add(change, position, step);
+        }
+        turns.extend(bend.finish(min_angle));
+
+        Self { turns, length }
+    }
+
+    /// 0-1 similarity of the turn patterns. Turns are paired in order with a
+    /// weighted edit distance: pairing turns the same way costs their
+    /// difference in angle and relative position, and a turn left unpaired
+    /// (or paired against one the other way) costs its size.
+    pub fn similarity(&self, other: &TurnSequence) -> f64 {
+        let (a, b) = (&self.turns, &other.turns);
+        let worst: f64 = a.iter().chain(b).map(Turn::weight).sum();
+        if worst <= 0.0 {
+            return 1.0;
+        }
+
+        let substitute = |ta: &Turn, tb: &Turn| {
+            let unpaired = ta.weight() + tb.weight();
+            if ta.direction() != tb.direction() {
+                return unpaired;
+            }
+            let angle = (ta.angle - tb.angle).abs() / 180.0;
+            let position = (relative(ta.position, self.length) - relative(tb.position, other.length)).abs();
+            (angle + position).min(unpaired)
+        };
+
+        let mut previous: Vec<f64> = std::iter::once(0.0)
+            .chain(b.iter().scan(0.0, |cost, turn| {
+                *cost += turn.weight();
+                Some(*cost)
+            }))
+            .collect();
+        for ta in a {
+            let mut current = vec![previous[0] + ta.weight(); b.len() + 1];
+            for (j, tb) in b.iter().enumerate() {
+                current[j + 1] = (previous[j] + substitute(ta, tb))
+                    .min(previous[j + 1] + ta.weight())
+                    .min(current[j] + tb.weight());
+            }
+            previous = current;
+        }
+
+        1.0 - (previous[b.len()] / worst).min(1.0)
+    }
+}
+
+#[derive(Default)]
+struct BendBuilder {
+    angle: f64,
+    // Sum of position × |change|, for the middle of the bend
+    weighted_position: f64,
+    length: f64,
+    step: f64,
+    straight: f64,
+}
+
+impl BendBuilder {
+    fn add(&mut self, change: f64, position: f64, step: f64) {
+        self.angle += change;
+        self.weighted_position += position * change.abs();
+        self.length += step;
+        self.step = step;
+        self.straight = 0.0;
+    }
+
+    fn finish(&mut self, min_angle: f64) -> Option<Turn> {
+        let bend = std::mem::take(self);
+        (bend.angle.abs() >= min_angle).then(|| Turn {
+            angle: bend.angle,
+            position: bend.weighted_position / bend.angle.abs(),
+            // Smoothing spreads a bend over one extra sample at each end
+            length: (bend.length - 2.0 * bend.step).max(bend.step),
+        })
+    }
+}
+
+fn relative(position: f64, length: f64) -> f64 {
+    if length > 0.0 { position / length } else { 0.0 }
+}
+
+/// Normalize to [-π, π]
+fn normalize_angle(mut angle: f64) -> f64 {
+    while angle > PI {
+        angle -= 2.0 * PI;
+    }
+    while angle < -PI {
+        angle += 2.0 * PI;
+    }
+    angle
+}
@@ -0,0 +1,90 @@
+use geo::LineString;
+use serde::{Deserialize, Serialize};
+use crate::db::models::DbSavedRoute;
+use super::projection::LocalProjection;
+use super::turns::TurnSequence;
+
+/// Bends gentler than this (degrees) are ignored
+const MIN_BEND_DEG: f64 = 20.0;
+
+/// Bends with a smaller radius (metres) are tight: hairpins and corners
+const TIGHT_RADIUS_M: f64 = 50.0;
+
+/// Bends with a radius of at least this (metres) are sweeping
+const SWEEPING_RADIUS_M: f64 = 200.0;
+
+/// Curvature (degrees per km) that gives a curve score of one half
+const CURVE_SCORE_HALF: f64 = 360.0;
+
+/// How twisty a route is, independent of how densely it was sampled
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Twistiness {
+    /// Heading change over all bends, in degrees per km
+    #[serde(rename = "curvaturePerKm")]
+    pub curvature_per_km: f64,
+    #[serde(rename = "tightBends")]
+    pub tight_bends: i64,
+    #[serde(rename = "mediumBends")]
+    pub medium_bends: i64,
+    #[serde(rename = "sweepingBends")]
+    pub sweeping_bends: i64,
+}
+
+impl Twistiness {
+    /// Twistiness of a lon/lat line
+    pub fn of_route(line: &LineString<f64>) -> Self {
+        LocalProjection::centred_on(&[line])
+            .map(|projection| Self::from_projected(&projection.project_line(line)))
+            .unwrap_or_default()
+    }
+
+    /// Twistiness of a line projected into a local metric frame; bends are
+    /// read from the line resampled at a fixed spacing (see [`TurnSequence`])
+    pub fn from_projected(line: &LineString<f64>) -> Self {
+        let sequence = TurnSequence::bends(line, MIN_BEND_DEG);
+        let mut twistiness = Self::default();
+        if sequence.length <= 0.0 {
+            return twistiness;
+        }
+
+        let total_angle: f64 = sequence.turns.iter().map(|turn| turn.angle.abs()).sum();
+        twistiness.curvature_per_km = total_angle / (sequence.length / 1000.0);
+
+        for turn in &sequence.turns {
+            let radius = turn.length / turn.angle.abs().to_radians();
+            if radius < TIGHT_RADIUS_M {
+                twistiness.tight_bends += 1;
+            } else if radius < SWEEPING_RADIUS_M {
+                twistiness.medium_bends += 1;
+            } else {
+                twistiness.sweeping_bends += 1;
+            }
+        }
+
+        twistiness
+    }
+
+    /// Values stored with a route, if it has been measured
+    pub fn from_db(route: &DbSavedRoute) -> Option<Self> {
+        Some(Self {
+            curvature_per_km: route.curvature_per_km?,
+            tight_bends: route.tight_bends?,
+            medium_bends: route.medium_bends?,
+            sweeping_bends: route.sweeping_bends?,
+        })
+    }
+
+    /// 0-1 score, rising with curvature: a straight route scores 0
+    pub fn curve_score(&self) -> f64 {
+        self.curvature_per_km / (self.curvature_per_km + CURVE_SCORE_HALF)
+    }
+
+    /// 0-1 similarity of the curvature of two routes
+    pub fn similarity(&self, other: &Twistiness) -> f64 {
+        let total = self.curvature_per_km + other.curvature_per_km;
+        if total <= 0.0 {
+            return 1.0;
+        }
+        1.0 - (self.curvature_per_km - other.curvature_per_km).abs() / total
+    }
+}
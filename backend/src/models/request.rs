@@ -10,8 +10,6 @@ pub struct SaveRouteRequest {
     pub elevation_gain: f64,
     #[serde(rename = "gainPerKm")]
     pub gain_per_km: f64,
-    #[serde(rename = "curveScore")]
-    pub curve_score: f64,
     #[serde(rename = "matchPercentage")]
     pub match_percentage: f64,
     pub geometry: serde_json::Value,
@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 use crate::db::models::DbSavedRoute;
+use crate::matching::twistiness::Twistiness;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SavedRoute {
@@ -15,6 +16,8 @@ pub struct SavedRoute {
     pub gain_per_km: f64,
     #[serde(rename = "curveScore")]
     pub curve_score: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub twistiness: Option<Twistiness>,
     #[serde(rename = "matchPercentage")]
     pub match_percentage: f64,
     pub geometry: serde_json::Value,
@@ -33,6 +36,8 @@ impl From<DbSavedRoute> for SavedRoute {
         let search_area: serde_json::Value = serde_json::from_str(&db_route.search_area_json)
             .unwrap_or(serde_json::json!({}));
         
+        let twistiness = Twistiness::from_db(&db_route);
+        
         Self {
             id: db_route.id,
             name: db_route.name,
@@ -42,6 +47,7 @@ impl From<DbSavedRoute> for SavedRoute {
             elevation_gain: db_route.elevation_gain_m,
             gain_per_km: db_route.gain_per_km,
             curve_score: db_route.curve_score,
+            twistiness,
             match_percentage: db_route.match_pct,
             geometry,
             elevation_profile,
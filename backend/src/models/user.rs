@@ -9,6 +9,10 @@ pub struct User {
     pub role: String,
     #[serde(rename = "createdAt")]
     pub created_at: String,
+    #[serde(rename = "emailVerified")]
+    pub email_verified: bool,
+    #[serde(rename = "mfaEnabled")]
+    pub mfa_enabled: bool,
 }
 
 impl From<DbUser> for User {
@@ -19,6 +23,8 @@ impl From<DbUser> for User {
             username: db_user.username,
             role: db_user.role,
             created_at: db_user.created_at,
+            email_verified: db_user.email_verified_at.is_some(),
+            mfa_enabled: db_user.totp_enabled_at.is_some(),
         }
     }
 }
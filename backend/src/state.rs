@@ -0,0 +1,41 @@
+use axum::extract::FromRef;
+use sqlx::SqlitePool;
+use std::sync::Arc;
+use crate::auth::oidc::OidcProviders;
+use crate::auth::password::PasswordPolicy;
+use crate::auth::session::RevocationList;
+use crate::auth::throttle::LoginThrottle;
+use crate::config::Config;
+use crate::mail::Mailer;
+use crate::matching::road_class::RoadNetwork;
+use crate::matching::spatial_index::SharedSpatialIndex;
+use crate::utils::dem::DemProvider;
+
+/// Shared application state handed to every router
+#[derive(Clone)]
+pub struct AppState {
+    pub config: Arc<Config>,
+    pub pool: SqlitePool,
+    /// Road classification from the configured OSM extract, if any
+    pub road_network: Option<Arc<RoadNetwork>>,
+    /// Local elevation model from the configured DEM directory, if any
+    pub dem: Option<Arc<DemProvider>>,
+    /// Route index loaded once at startup and kept in sync with the library
+    pub spatial_index: Arc<SharedSpatialIndex>,
+    /// Access tokens revoked by logout or session management
+    pub revoked_tokens: Arc<RevocationList>,
+    /// Outgoing email (SMTP, or an outbox directory in development)
+    pub mailer: Arc<dyn Mailer>,
+    /// Rules for new passwords, including the breached-password list
+    pub password_policy: Arc<PasswordPolicy>,
+    /// Failed login attempts per account and client address
+    pub login_throttle: Arc<LoginThrottle>,
+    /// External identity providers users can sign in with
+    pub oidc: Arc<OidcProviders>,
+}
+
+impl FromRef<AppState> for SqlitePool {
+    fn from_ref(state: &AppState) -> Self {
+        state.pool.clone()
+    }
+}
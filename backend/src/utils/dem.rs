@@ -0,0 +1,470 @@
+// backend/src/utils/dem.rs - offline elevation lookup from local DEM tiles
+
+use geo::LineString;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tiff::decoder::{Decoder, DecodingResult};
+use tiff::tags::Tag;
+use crate::error::AppError;
+
+/// SRTM void marker
+const HGT_VOID: i16 = -32768;
+
+/// Default number of decoded tiles kept in memory
+const DEFAULT_MAX_CACHED_TILES: usize = 16;
+
+/// How long a missing or unreadable tile is remembered before looking again,
+/// so tiles added to the directory are picked up without a restart
+const DEFAULT_MISSING_TILE_TTL: Duration = Duration::from_secs(60);
+
+/// A decoded elevation raster with pixel-centre georeferencing
+pub struct DemTile {
+    /// Longitude of the centre of the top-left pixel
+    origin_lon: f64,
+    /// Latitude of the centre of the top-left pixel
+    origin_lat: f64,
+    /// Pixel size in degrees (rows run southwards)
+    step_lon: f64,
+    step_lat: f64,
+    width: usize,
+    height: usize,
+    data: Vec<f32>,
+    nodata: Option<f32>,
+}
+
+impl DemTile {
+    /// Read an SRTM `.hgt` tile (1 or 3 arc-second, big-endian i16)
+    pub fn from_hgt(path: &Path, lat: i32, lon: i32) -> Result<Self, AppError> {
+        let bytes = std::fs::read(path)
+            .map_err(|e| AppError::FileError(format!("Failed to read {}: {}", path.display(), e)))?;
+
+        let samples = bytes.len() / 2;
+        let size = (samples as f64).sqrt() as usize;
+        if size < 2 || size * size != samples {
+            return Err(AppError::FileError(format!(
+                "{} is not a square SRTM tile ({} bytes)",
+                path.display(),
+                bytes.len()
+            )));
+        }
+
+        let data = bytes
+            .chunks_exact(2)
+            .map(|b| i16::from_be_bytes([b[0], b[1]]) as f32)
+            .collect();
+
+        let step = 1.0 / (size - 1) as f64;
+        Ok(Self {
+            origin_lon: lon as f64,
+            origin_lat: lat as f64 + 1.0,
+            step_lon: step,
+            step_lat: step,
+            width: size,
+            height: size,
+            data,
+            nodata: Some(HGT_VOID as f32),
+        })
+    }
+
+    /// Read a single-band GeoTIFF in geographic (lon/lat) coordinates.
+    ///
+    /// Georeferencing comes from ModelTiepoint + ModelPixelScale and the
+    /// raster is treated as PixelIsArea, as written by GDAL by default.
+    pub fn from_geotiff(path: &Path) -> Result<Self, AppError> {
+        let tiff_error = |e: tiff::TiffError| {
+            AppError::FileError(format!("Failed to read GeoTIFF {}: {}", path.display(), e))
+        };
+
+        let file = File::open(path)
+            .map_err(|e| AppError::FileError(format!("Failed to open {}: {}", path.display(), e)))?;
+        let mut decoder = Decoder::new(BufReader::new(file)).map_err(tiff_error)?;
+
+        let (width, height) = decoder.dimensions().map_err(tiff_error)?;
+        let extent = read_geotiff_extent(&mut decoder, width, height).map_err(|e| match e {
+            GeoTiffError::Tiff(e) => tiff_error(e),
+            GeoTiffError::NotGeoreferenced => AppError::FileError(format!(
+                "{} has no GeoTIFF georeferencing",
+                path.display()
+            )),
+        })?;
+
+        let nodata = decoder
+            .find_tag(Tag::GdalNodata)
+            .map_err(tiff_error)?
+            .and_then(|value| value.into_string().ok())
+            .and_then(|text| text.trim_end_matches('\0').trim().parse::<f32>().ok());
+
+        let data: Vec<f32> = match decoder.read_image().map_err(tiff_error)? {
+            DecodingResult::I16(v) => v.into_iter().map(|x| x as f32).collect(),
+            DecodingResult::U16(v) => v.into_iter().map(|x| x as f32).collect(),
+            DecodingResult::I32(v) => v.into_iter().map(|x| x as f32).collect(),
+            DecodingResult::F32(v) => v,
+            DecodingResult::F64(v) => v.into_iter().map(|x| x as f32).collect(),
+            _ => {
+                return Err(AppError::FileError(format!(
+                    "{} uses an unsupported sample format",
+                    path.display()
+                )))
+            }
+        };
+
+        let (width, height) = (width as usize, height as usize);
+        if data.len() != width * height || width < 2 || height < 2 {
+            return Err(AppError::FileError(format!(
+                "{} is not a single-band elevation raster",
+                path.display()
+            )));
+        }
+
+        Ok(Self {
+            origin_lon: extent.west + extent.step_lon / 2.0,
+            origin_lat: extent.north - extent.step_lat / 2.0,
+            step_lon: extent.step_lon,
+            step_lat: extent.step_lat,
+            width,
+            height,
+            data,
+            nodata,
+        })
+    }
+
+    /// Bilinearly interpolated elevation, or `None` outside the tile or
+    /// next to void cells
+    pub fn sample(&self, lon: f64, lat: f64) -> Option<f64> {
+        let col = (lon - self.origin_lon) / self.step_lon;
+        let row = (self.origin_lat - lat) / self.step_lat;
+
+        let max_col = (self.width - 1) as f64;
+        let max_row = (self.height - 1) as f64;
+        if !(0.0..=max_col).contains(&col) || !(0.0..=max_row).contains(&row) {
+            return None;
+        }
+
+        let c0 = (col.floor() as usize).min(self.width - 2);
+        let r0 = (row.floor() as usize).min(self.height - 2);
+        let fx = col - c0 as f64;
+        let fy = row - r0 as f64;
+
+        let value = |r: usize, c: usize| -> Option<f64> {
+            let v = self.data[r * self.width + c];
+            if v.is_nan() || Some(v) == self.nodata {
+                None
+            } else {
+                Some(v as f64)
+            }
+        };
+
+        let v00 = value(r0, c0)?;
+        let v01 = value(r0, c0 + 1)?;
+        let v10 = value(r0 + 1, c0)?;
+        let v11 = value(r0 + 1, c0 + 1)?;
+
+        let top = v00 * (1.0 - fx) + v01 * fx;
+        let bottom = v10 * (1.0 - fx) + v11 * fx;
+        Some(top * (1.0 - fy) + bottom * fy)
+    }
+}
+
+struct GeoTiffExtent {
+    west: f64,
+    north: f64,
+    step_lon: f64,
+    step_lat: f64,
+}
+
+enum GeoTiffError {
+    Tiff(tiff::TiffError),
+    NotGeoreferenced,
+}
+
+fn read_geotiff_extent<R: std::io::Read + std::io::Seek>(
+    decoder: &mut Decoder<R>,
+    width: u32,
+    height: u32,
+) -> Result<GeoTiffExtent, GeoTiffError> {
+    let scale = decoder
+        .find_tag(Tag::ModelPixelScaleTag)
+        .map_err(GeoTiffError::Tiff)?
+        .and_then(|v| v.into_f64_vec().ok())
+        .ok_or(GeoTiffError::NotGeoreferenced)?;
+    let tiepoint = decoder
+        .find_tag(Tag::ModelTiepointTag)
+        .map_err(GeoTiffError::Tiff)?
+        .and_then(|v| v.into_f64_vec().ok())
+        .ok_or(GeoTiffError::NotGeoreferenced)?;
+
+    if scale.len() < 2 || tiepoint.len() < 6 || width == 0 || height == 0 {
+        return Err(GeoTiffError::NotGeoreferenced);
+    }
+
+    // Tiepoint maps raster (i, j) to model (x, y)
+    let (i, j, x, y) = (tiepoint[0], tiepoint[1], tiepoint[3], tiepoint[4]);
+    Ok(GeoTiffExtent {
+        west: x - i * scale[0],
+        north: y + j * scale[1],
+        step_lon: scale[0],
+        step_lat: scale[1],
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum TileKey {
+    Hgt(i32, i32),
+    GeoTiff(usize),
+}
+
+/// Coverage of a GeoTIFF in the DEM directory, read from its header
+struct GeoTiffEntry {
+    path: PathBuf,
+    west: f64,
+    south: f64,
+    east: f64,
+    north: f64,
+}
+
+enum CachedTile {
+    Loaded(Arc<DemTile>),
+    /// Not found or unreadable when last looked for
+    Missing(Instant),
+}
+
+struct TileCache {
+    tiles: HashMap<TileKey, CachedTile>,
+    loaded_order: VecDeque<TileKey>,
+}
+
+/// Elevation provider backed by SRTM `.hgt` and GeoTIFF tiles in a local
+/// directory, with decoded tiles cached in memory
+pub struct DemProvider {
+    dir: PathBuf,
+    geotiffs: Vec<GeoTiffEntry>,
+    cache: Mutex<TileCache>,
+    max_cached_tiles: usize,
+    missing_tile_ttl: Duration,
+}
+
+impl DemProvider {
+    /// Open a DEM directory, cataloguing the extent of every GeoTIFF in it.
+    /// `.hgt` tiles are located by name (e.g. `N52E013.hgt`) on demand.
+    pub fn open(dir: &Path) -> Result<Self, AppError> {
+        let entries = std::fs::read_dir(dir)
+            .map_err(|e| AppError::FileError(format!("Failed to read DEM directory {}: {}", dir.display(), e)))?;
+
+        let mut geotiffs = Vec::new();
+        let mut hgt_count = 0;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let extension = path
+                .extension()
+                .and_then(|e| e.to_str())
+                .map(|e| e.to_ascii_lowercase());
+
+            match extension.as_deref() {
+                Some("tif") | Some("tiff") => match read_geotiff_entry(&path) {
+                    Ok(geotiff) => geotiffs.push(geotiff),
+                    Err(e) => tracing::warn!("Skipping DEM file {}: {}", path.display(), e),
+                },
+                Some("hgt") => hgt_count += 1,
+                _ => {}
+            }
+        }
+
+        tracing::info!(
+            "DEM directory {}: {} SRTM tiles, {} GeoTIFFs",
+            dir.display(),
+            hgt_count,
+            geotiffs.len()
+        );
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            geotiffs,
+            cache: Mutex::new(TileCache {
+                tiles: HashMap::new(),
+                loaded_order: VecDeque::new(),
+            }),
+            max_cached_tiles: DEFAULT_MAX_CACHED_TILES,
+            missing_tile_ttl: DEFAULT_MISSING_TILE_TTL,
+        })
+    }
+
+    pub fn with_max_cached_tiles(mut self, max_cached_tiles: usize) -> Self {
+        self.max_cached_tiles = max_cached_tiles.max(1);
+        self
+    }
+
+    pub fn with_missing_tile_ttl(mut self, missing_tile_ttl: Duration) -> Self {
+        self.missing_tile_ttl = missing_tile_ttl;
+        self
+    }
+
+    /// Elevation in metres at a point, or `None` without DEM coverage
+    pub fn elevation_at(&self, lon: f64, lat: f64) -> Option<f64> {
+        // Points on a whole degree sit on the edge shared by two tiles
+        let lat_cells = edge_cells(lat);
+        let lon_cells = edge_cells(lon);
+        for &lat_cell in lat_cells.iter().flatten() {
+            for &lon_cell in lon_cells.iter().flatten() {
+                let key = TileKey::Hgt(lat_cell, lon_cell);
+                if let Some(elevation) = self.tile(key).and_then(|tile| tile.sample(lon, lat)) {
+                    return Some(elevation);
+                }
+            }
+        }
+
+        self.geotiffs
+            .iter()
+            .enumerate()
+            .filter(|(_, g)| lon >= g.west && lon <= g.east && lat >= g.south && lat <= g.north)
+            .find_map(|(idx, _)| self.tile(TileKey::GeoTiff(idx))?.sample(lon, lat))
+    }
+
+    /// Elevation for every vertex of a line
+    pub fn sample_line(&self, line: &LineString<f64>) -> Vec<Option<f64>> {
+        line.points().map(|p| self.elevation_at(p.x(), p.y())).collect()
+    }
+
+    /// Load the tiles under a line on the blocking thread pool, so that
+    /// lookups along it from async code don't read files on the executor
+    pub async fn preload(self: &Arc<Self>, line: &LineString<f64>) {
+        let mut keys = HashSet::new();
+        for point in line.points() {
+            let (lon, lat) = (point.x(), point.y());
+            for &lat_cell in edge_cells(lat).iter().flatten() {
+                for &lon_cell in edge_cells(lon).iter().flatten() {
+                    keys.insert(TileKey::Hgt(lat_cell, lon_cell));
+                }
+            }
+            for (idx, g) in self.geotiffs.iter().enumerate() {
+                if lon >= g.west && lon <= g.east && lat >= g.south && lat <= g.north {
+                    keys.insert(TileKey::GeoTiff(idx));
+                }
+            }
+        }
+        keys.retain(|&key| self.cached(key).is_none());
+        if keys.is_empty() {
+            return;
+        }
+
+        let provider = Arc::clone(self);
+        let loaded = tokio::task::spawn_blocking(move || {
+            for key in keys {
+                provider.tile(key);
+            }
+        })
+        .await;
+        if let Err(e) = loaded {
+            tracing::warn!("Failed to preload DEM tiles: {}", e);
+        }
+    }
+
+    fn tile(&self, key: TileKey) -> Option<Arc<DemTile>> {
+        if let Some(tile) = self.cached(key) {
+            return tile;
+        }
+
+        // Read and decode without holding the lock, so lookups in tiles
+        // already loaded carry on meanwhile
+        let tile = self.load_tile(key).map(Arc::new);
+
+        let mut cache = self.cache.lock().unwrap();
+        // Another lookup may have loaded it first
+        if let Some(CachedTile::Loaded(existing)) = cache.tiles.get(&key) {
+            return Some(existing.clone());
+        }
+        match tile {
+            Some(ref loaded) => {
+                cache.tiles.insert(key, CachedTile::Loaded(loaded.clone()));
+                cache.loaded_order.push_back(key);
+                while cache.loaded_order.len() > self.max_cached_tiles {
+                    if let Some(evicted) = cache.loaded_order.pop_front() {
+                        cache.tiles.remove(&evicted);
+                    }
+                }
+            }
+            None => {
+                cache.tiles.insert(key, CachedTile::Missing(Instant::now()));
+            }
+        }
+        tile
+    }
+
+    /// The cached answer for a tile: `Some(None)` if it was recently found
+    /// missing, `None` if it has to be loaded
+    fn cached(&self, key: TileKey) -> Option<Option<Arc<DemTile>>> {
+        let cache = self.cache.lock().unwrap();
+        match cache.tiles.get(&key)? {
+            CachedTile::Loaded(tile) => Some(Some(tile.clone())),
+            CachedTile::Missing(since) if since.elapsed() < self.missing_tile_ttl => Some(None),
+            CachedTile::Missing(_) => None,
+        }
+    }
+
+    fn load_tile(&self, key: TileKey) -> Option<DemTile> {
+        let result = match key {
+            TileKey::Hgt(lat, lon) => {
+                let path = self.dir.join(hgt_file_name(lat, lon));
+                if !path.exists() {
+                    return None;
+                }
+                DemTile::from_hgt(&path, lat, lon)
+            }
+            TileKey::GeoTiff(idx) => DemTile::from_geotiff(&self.geotiffs[idx].path),
+        };
+
+        match result {
+            Ok(tile) => Some(tile),
+            Err(e) => {
+                tracing::warn!("Failed to load DEM tile {:?}: {}", key, e);
+                None
+            }
+        }
+    }
+}
+
+/// One-degree cells containing a coordinate: its own, plus the one below
+/// when the coordinate is exactly on a cell boundary
+fn edge_cells(value: f64) -> [Option<i32>; 2] {
+    let cell = value.floor() as i32;
+    if value == value.floor() {
+        [Some(cell), Some(cell - 1)]
+    } else {
+        [Some(cell), None]
+    }
+}
+
+/// SRTM tile name for the cell whose south-west corner is (lat, lon)
+pub fn hgt_file_name(lat: i32, lon: i32) -> String {
+    format!(
+        "{}{:02}{}{:03}.hgt",
+        if lat >= 0 { 'N' } else { 'S' },
+        lat.abs(),
+        if lon >= 0 { 'E' } else { 'W' },
+        lon.abs()
+    )
+}
+
+fn read_geotiff_entry(path: &Path) -> Result<GeoTiffEntry, AppError> {
+    let file = File::open(path)
+        .map_err(|e| AppError::FileError(format!("Failed to open {}: {}", path.display(), e)))?;
+    let mut decoder = Decoder::new(BufReader::new(file))
+        .map_err(|e| AppError::FileError(format!("Invalid TIFF: {}", e)))?;
+    let (width, height) = decoder
+        .dimensions()
+        .map_err(|e| AppError::FileError(format!("Invalid TIFF: {}", e)))?;
+
+    let extent = read_geotiff_extent(&mut decoder, width, height)
+        .map_err(|_| AppError::FileError("Missing GeoTIFF georeferencing".to_string()))?;
+
+    Ok(GeoTiffEntry {
+        path: path.to_path_buf(),
+        west: extent.west,
+        north: extent.north,
+        east: extent.west + extent.step_lon * width as f64,
+        south: extent.north - extent.step_lat * height as f64,
+    })
+}
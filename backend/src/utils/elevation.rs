@@ -1,33 +1,90 @@
-use geo::{LineString, Point};
+use geo::LineString;
+use serde::Serialize;
+use super::dem::DemProvider;
 
-pub fn interpolate_elevation_profile(
-    line: &LineString<f64>,
-    num_points: usize,
+/// Weight given to the DEM when blending with recorded elevation
+const BLEND_DEM_WEIGHT: f64 = 0.5;
+
+/// Where the elevation used for matching comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub enum ElevationCorrection {
+    /// Elevation as recorded in the uploaded file
+    #[default]
+    #[serde(rename = "raw")]
+    Raw,
+    /// DEM-sampled elevation wherever the DEM has coverage
+    #[serde(rename = "dem")]
+    Dem,
+    /// Bias-corrected recorded elevation averaged with the DEM
+    #[serde(rename = "blended")]
+    Blended,
+}
+
+impl ElevationCorrection {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "raw" => Some(ElevationCorrection::Raw),
+            "dem" => Some(ElevationCorrection::Dem),
+            "blended" => Some(ElevationCorrection::Blended),
+            _ => None,
+        }
+    }
+}
+
+/// Correct a recorded profile against DEM samples taken at the same points.
+///
+/// GPS elevation is usually offset from the terrain, so the median
+/// difference to the DEM is removed before blending; points without DEM
+/// coverage keep their bias-corrected recorded value.
+pub fn correct_elevation_profile(
+    recorded: &[f64],
+    dem_samples: &[Option<f64>],
+    mode: ElevationCorrection,
 ) -> Vec<f64> {
-    // Simple elevation interpolation
-    // In a real implementation, this would query a DEM service
-    let points: Vec<Point<f64>> = line.points().collect();
-    let total_points = points.len();
+    if mode == ElevationCorrection::Raw || recorded.len() != dem_samples.len() {
+        return recorded.to_vec();
+    }
     
-    if total_points == 0 || num_points == 0 {
-        return vec![];
+    let mut offsets: Vec<f64> = recorded.iter()
+        .zip(dem_samples)
+        .filter_map(|(r, d)| d.map(|d| r - d))
+        .collect();
+    if offsets.is_empty() {
+        return recorded.to_vec();
     }
+    offsets.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let bias = offsets[offsets.len() / 2];
     
-    let mut profile = Vec::with_capacity(num_points);
+    recorded.iter()
+        .zip(dem_samples)
+        .map(|(r, d)| {
+            let debiased = r - bias;
+            match (mode, d) {
+                (ElevationCorrection::Dem, Some(d)) => *d,
+                (ElevationCorrection::Blended, Some(d)) => {
+                    BLEND_DEM_WEIGHT * d + (1.0 - BLEND_DEM_WEIGHT) * debiased
+                }
+                _ => debiased,
+            }
+        })
+        .collect()
+}
+
+/// Sample an elevation profile for a line from the local DEM, one value per
+/// vertex. Returns `None` if any vertex lies outside DEM coverage.
+pub fn interpolate_elevation_profile(
+    line: &LineString<f64>,
+    dem: &DemProvider,
+) -> Option<Vec<f64>> {
+    let samples = dem.sample_line(line);
+    let covered = samples.iter().filter(|s| s.is_some()).count();
     
-    for i in 0..num_points {
-        let position = i as f64 / (num_points - 1) as f64;
-        let index = (position * (total_points - 1) as f64) as usize;
-        
-        // Simple elevation simulation based on location
-        let point = &points[index.min(total_points - 1)];
-        let base_elevation = 100.0;
-        let variation = (point.x() * 100.0).sin() * 10.0 + (point.y() * 100.0).cos() * 5.0;
-        
-        profile.push(base_elevation + variation);
+    if samples.is_empty() || covered < samples.len() {
+        tracing::debug!("DEM covers {}/{} points, not using DEM profile", covered, samples.len());
+        return None;
     }
     
-    profile
+    Some(samples.into_iter().flatten().collect())
 }
 
 pub fn calculate_elevation_stats(profile: &[f64]) -> ElevationStats {
@@ -60,7 +117,7 @@ pub fn calculate_elevation_stats(profile: &[f64]) -> ElevationStats {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct ElevationStats {
     pub total_gain: f64,
     pub total_loss: f64,
@@ -0,0 +1,403 @@
+// backend/src/utils/fit.rs - minimal reader and course writer for Garmin FIT files
+
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use crate::error::AppError;
+use crate::matching::engine::haversine_distance;
+use super::gpx_parser::{GpxSourceKind, SourcePoint, SourceTrack};
+
+const MESG_FILE_ID: u16 = 0;
+const MESG_LAP: u16 = 19;
+const MESG_RECORD: u16 = 20;
+const MESG_EVENT: u16 = 21;
+const MESG_COURSE: u16 = 31;
+
+const FILE_TYPE_COURSE: u64 = 6;
+const EVENT_TIMER: u64 = 0;
+const EVENT_TYPE_START: u64 = 0;
+const EVENT_TYPE_STOP: u64 = 1;
+const EVENT_TYPE_STOP_ALL: u64 = 4;
+
+const BASE_ENUM: u8 = 0x00;
+const BASE_UINT8: u8 = 0x02;
+const BASE_STRING: u8 = 0x07;
+const BASE_UINT16: u8 = 0x84;
+const BASE_SINT32: u8 = 0x85;
+const BASE_UINT32: u8 = 0x86;
+const BASE_UINT32Z: u8 = 0x8C;
+
+const MANUFACTURER_DEVELOPMENT: u16 = 255;
+const SPORT_GENERIC: u8 = 0;
+const PROFILE_VERSION: u16 = 2134;
+
+/// Seconds between the Unix epoch and the FIT epoch (1989-12-31T00:00:00Z)
+const FIT_EPOCH_OFFSET: i64 = 631_065_600;
+
+/// Speed used to give course records timestamps; head units need them for
+/// the virtual partner but the value itself is arbitrary
+pub const COURSE_NOMINAL_SPEED_MPS: f64 = 4.0;
+
+const COURSE_NAME_BYTES: usize = 16;
+
+const SEMICIRCLES_TO_DEGREES: f64 = 180.0 / 2_147_483_648.0;
+
+const CRC_TABLE: [u16; 16] = [
+    0x0000, 0xCC01, 0xD801, 0x1400, 0xF001, 0x3C00, 0x2800, 0xE401,
+    0xA001, 0x6C00, 0x7800, 0xB401, 0x5000, 0x9C01, 0x8801, 0x4400,
+];
+
+/// FIT CRC-16 over `data`, continuing from `crc`
+pub fn crc16(mut crc: u16, data: &[u8]) -> u16 {
+    for &byte in data {
+        let tmp = CRC_TABLE[(crc & 0xF) as usize];
+        crc = ((crc >> 4) & 0x0FFF) ^ tmp ^ CRC_TABLE[(byte & 0xF) as usize];
+        let tmp = CRC_TABLE[(crc & 0xF) as usize];
+        crc = ((crc >> 4) & 0x0FFF) ^ tmp ^ CRC_TABLE[((byte >> 4) & 0xF) as usize];
+    }
+    crc
+}
+
+/// Whether `data` starts with a FIT file header
+pub fn is_fit(data: &[u8]) -> bool {
+    data.len() >= 12 && &data[8..12] == b".FIT"
+}
+
+/// Read the position records of a FIT activity or course.
+///
+/// Timer stop events split the track into segments, so paused recordings
+/// are reported as gaps like multi-segment GPX tracks.
+pub fn read_fit(data: &[u8]) -> Result<SourceTrack, AppError> {
+    let mut reader = FitReader::default();
+    let mut pos = 0;
+
+    // Several FIT files may be chained back to back
+    while pos < data.len() {
+        pos += reader.read_file(&data[pos..])?;
+    }
+    reader.finish_segment();
+
+    let point_count: usize = reader.segments.iter().map(|s| s.len()).sum();
+    tracing::info!(
+        "Read FIT {}: {} points in {} segments",
+        if reader.is_course { "course" } else { "activity" },
+        point_count,
+        reader.segments.len()
+    );
+
+    Ok(SourceTrack {
+        kind: if reader.is_course { GpxSourceKind::Route } else { GpxSourceKind::Track },
+        name: reader.name,
+        segments: reader.segments,
+    })
+}
+
+#[derive(Clone)]
+struct FieldDef {
+    number: u8,
+    size: usize,
+}
+
+#[derive(Clone)]
+struct Definition {
+    global: u16,
+    big_endian: bool,
+    fields: Vec<FieldDef>,
+    developer_size: usize,
+}
+
+#[derive(Default)]
+struct FitReader {
+    definitions: HashMap<u8, Definition>,
+    is_course: bool,
+    name: Option<String>,
+    segments: Vec<Vec<SourcePoint>>,
+    current: Vec<SourcePoint>,
+}
+
+impl FitReader {
+    /// Read one FIT file from the start of `data`, returning its length
+    fn read_file(&mut self, data: &[u8]) -> Result<usize, AppError> {
+        if !is_fit(data) {
+            return Err(AppError::FileError("Invalid FIT file header".to_string()));
+        }
+        let header_size = data[0] as usize;
+        if header_size < 12 {
+            return Err(AppError::FileError("Invalid FIT file header".to_string()));
+        }
+        let data_size = u32::from_le_bytes([data[4], data[5], data[6], data[7]]) as usize;
+        let end = header_size + data_size;
+        let body = data.get(header_size..end).ok_or_else(truncated)?;
+        let crc = data.get(end..end + 2).ok_or_else(truncated)?;
+
+        if crc16(0, &data[..end]) != u16::from_le_bytes([crc[0], crc[1]]) {
+            return Err(AppError::FileError("FIT file CRC mismatch".to_string()));
+        }
+
+        // Local message definitions don't carry over between chained files
+        self.definitions.clear();
+
+        let mut rest = body;
+        while !rest.is_empty() {
+            let header = take(&mut rest, 1)?[0];
+            if header & 0x80 != 0 {
+                // Compressed timestamp header: always a data message
+                self.read_data((header >> 5) & 0x3, &mut rest)?;
+            } else if header & 0x40 != 0 {
+                self.read_definition(header & 0x0F, header & 0x20 != 0, &mut rest)?;
+            } else {
+                self.read_data(header & 0x0F, &mut rest)?;
+            }
+        }
+
+        Ok(end + 2)
+    }
+
+    fn read_definition(&mut self, local: u8, has_developer: bool, data: &mut &[u8]) -> Result<(), AppError> {
+        let fixed = take(data, 5)?;
+        let big_endian = fixed[1] == 1;
+        let global = if big_endian {
+            u16::from_be_bytes([fixed[2], fixed[3]])
+        } else {
+            u16::from_le_bytes([fixed[2], fixed[3]])
+        };
+
+        let field_count = fixed[4] as usize;
+        let fields = take(data, field_count * 3)?
+            .chunks(3)
+            .map(|f| FieldDef { number: f[0], size: f[1] as usize })
+            .collect();
+
+        let mut developer_size = 0;
+        if has_developer {
+            let count = take(data, 1)?[0] as usize;
+            developer_size = take(data, count * 3)?.chunks(3).map(|f| f[1] as usize).sum();
+        }
+
+        self.definitions.insert(local, Definition { global, big_endian, fields, developer_size });
+        Ok(())
+    }
+
+    fn read_data(&mut self, local: u8, data: &mut &[u8]) -> Result<(), AppError> {
+        let definition = self.definitions.get(&local).cloned().ok_or_else(|| {
+            AppError::FileError(format!("FIT data message uses undefined local type {}", local))
+        })?;
+
+        let mut values: HashMap<u8, &[u8]> = HashMap::new();
+        for field in &definition.fields {
+            values.insert(field.number, take(data, field.size)?);
+        }
+        take(data, definition.developer_size)?;
+
+        let read = |number: u8| -> Option<u64> {
+            read_uint(values.get(&number)?, definition.big_endian)
+        };
+
+        match definition.global {
+            MESG_FILE_ID => {
+                self.is_course = read(0) == Some(FILE_TYPE_COURSE);
+            }
+            MESG_COURSE => {
+                if let Some(name) = values.get(&5).and_then(|bytes| read_string(bytes)) {
+                    self.name = Some(name);
+                }
+            }
+            MESG_EVENT => {
+                let stopped = matches!(read(1), Some(EVENT_TYPE_STOP) | Some(EVENT_TYPE_STOP_ALL));
+                if read(0) == Some(EVENT_TIMER) && stopped {
+                    self.finish_segment();
+                }
+            }
+            MESG_RECORD => {
+                let lat = read(0).and_then(|v| semicircles(v, values[&0].len()));
+                let lon = read(1).and_then(|v| semicircles(v, values[&1].len()));
+                // enhanced_altitude (78) supersedes the 16-bit altitude (2)
+                let elevation = read(78)
+                    .or_else(|| read(2))
+                    .map(|v| v as f64 / 5.0 - 500.0);
+                if let (Some(lat), Some(lon)) = (lat, lon) {
+                    self.current.push(SourcePoint { lon, lat, elevation });
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn finish_segment(&mut self) {
+        if !self.current.is_empty() {
+            self.segments.push(std::mem::take(&mut self.current));
+        }
+    }
+}
+
+fn truncated() -> AppError {
+    AppError::FileError("Truncated FIT file".to_string())
+}
+
+fn take<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8], AppError> {
+    if data.len() < len {
+        return Err(truncated());
+    }
+    let (head, tail) = data.split_at(len);
+    *data = tail;
+    Ok(head)
+}
+
+/// Unsigned field value, or `None` for the all-ones "invalid" marker
+fn read_uint(bytes: &[u8], big_endian: bool) -> Option<u64> {
+    if bytes.is_empty() || bytes.len() > 8 || bytes.iter().all(|&b| b == 0xFF) {
+        return None;
+    }
+    let mut buf = [0u8; 8];
+    if big_endian {
+        buf[8 - bytes.len()..].copy_from_slice(bytes);
+        Some(u64::from_be_bytes(buf))
+    } else {
+        buf[..bytes.len()].copy_from_slice(bytes);
+        Some(u64::from_le_bytes(buf))
+    }
+}
+
+/// Convert a sint32 semicircle value to degrees
+fn semicircles(raw: u64, size: usize) -> Option<f64> {
+    if size != 4 || raw == 0x7FFF_FFFF {
+        return None;
+    }
+    Some(raw as u32 as i32 as f64 * SEMICIRCLES_TO_DEGREES)
+}
+
+fn read_string(bytes: &[u8]) -> Option<String> {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    let text = String::from_utf8_lossy(&bytes[..end]).trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+/// Encode a route as a FIT course file.
+///
+/// Records are timestamped from `start` at `COURSE_NOMINAL_SPEED_MPS`.
+pub fn write_course(name: &str, points: &[SourcePoint], start: DateTime<Utc>) -> Vec<u8> {
+    let mut distances = vec![0.0];
+    for pair in points.windows(2) {
+        let d = haversine_distance(pair[0].lat, pair[0].lon, pair[1].lat, pair[1].lon);
+        distances.push(distances.last().unwrap() + d);
+    }
+    let total_distance = *distances.last().unwrap();
+    let start_time = fit_timestamp(start);
+    let time_at = |distance: f64| start_time + (distance / COURSE_NOMINAL_SPEED_MPS).round() as u32;
+    let end_time = time_at(total_distance);
+
+    let mut writer = FitWriter::default();
+
+    writer.define(0, MESG_FILE_ID, &[(0, 1, BASE_ENUM), (1, 2, BASE_UINT16), (2, 2, BASE_UINT16), (3, 4, BASE_UINT32Z), (4, 4, BASE_UINT32)]);
+    writer.data(0, &[
+        &[FILE_TYPE_COURSE as u8],
+        &MANUFACTURER_DEVELOPMENT.to_le_bytes(),
+        &0u16.to_le_bytes(),
+        &1u32.to_le_bytes(),
+        &start_time.to_le_bytes(),
+    ]);
+
+    writer.define(1, MESG_COURSE, &[(5, COURSE_NAME_BYTES as u8, BASE_STRING), (4, 1, BASE_ENUM)]);
+    writer.data(1, &[&course_name(name), &[SPORT_GENERIC]]);
+
+    let (first, last) = match (points.first(), points.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return writer.finish(),
+    };
+
+    writer.define(2, MESG_LAP, &[
+        (253, 4, BASE_UINT32), (2, 4, BASE_UINT32),
+        (3, 4, BASE_SINT32), (4, 4, BASE_SINT32), (5, 4, BASE_SINT32), (6, 4, BASE_SINT32),
+        (7, 4, BASE_UINT32), (8, 4, BASE_UINT32), (9, 4, BASE_UINT32),
+    ]);
+    let elapsed_ms = (end_time - start_time) * 1000;
+    writer.data(2, &[
+        &end_time.to_le_bytes(),
+        &start_time.to_le_bytes(),
+        &to_semicircles(first.lat), &to_semicircles(first.lon),
+        &to_semicircles(last.lat), &to_semicircles(last.lon),
+        &elapsed_ms.to_le_bytes(),
+        &elapsed_ms.to_le_bytes(),
+        &((total_distance * 100.0).round() as u32).to_le_bytes(),
+    ]);
+
+    writer.define(3, MESG_EVENT, &[(253, 4, BASE_UINT32), (0, 1, BASE_ENUM), (1, 1, BASE_ENUM), (4, 1, BASE_UINT8)]);
+    writer.data(3, &[&start_time.to_le_bytes(), &[EVENT_TIMER as u8], &[EVENT_TYPE_START as u8], &[0]]);
+
+    writer.define(4, MESG_RECORD, &[(253, 4, BASE_UINT32), (0, 4, BASE_SINT32), (1, 4, BASE_SINT32), (2, 2, BASE_UINT16), (5, 4, BASE_UINT32)]);
+    for (point, distance) in points.iter().zip(&distances) {
+        let altitude = point.elevation
+            .map(|e| ((e + 500.0) * 5.0).round().clamp(0.0, 65534.0) as u16)
+            .unwrap_or(u16::MAX);
+        writer.data(4, &[
+            &time_at(*distance).to_le_bytes(),
+            &to_semicircles(point.lat),
+            &to_semicircles(point.lon),
+            &altitude.to_le_bytes(),
+            &((distance * 100.0).round() as u32).to_le_bytes(),
+        ]);
+    }
+
+    writer.data(3, &[&end_time.to_le_bytes(), &[EVENT_TIMER as u8], &[EVENT_TYPE_STOP_ALL as u8], &[0]]);
+
+    writer.finish()
+}
+
+#[derive(Default)]
+struct FitWriter {
+    body: Vec<u8>,
+}
+
+impl FitWriter {
+    /// Little-endian definition of `(field number, size, base type)` fields
+    fn define(&mut self, local: u8, global: u16, fields: &[(u8, u8, u8)]) {
+        self.body.extend_from_slice(&[0x40 | local, 0, 0]);
+        self.body.extend_from_slice(&global.to_le_bytes());
+        self.body.push(fields.len() as u8);
+        for &(number, size, base) in fields {
+            self.body.extend_from_slice(&[number, size, base]);
+        }
+    }
+
+    fn data(&mut self, local: u8, values: &[&[u8]]) {
+        self.body.push(local);
+        for value in values {
+            self.body.extend_from_slice(value);
+        }
+    }
+
+    /// Prepend the 14-byte header and append the file CRC
+    fn finish(self) -> Vec<u8> {
+        let mut file = Vec::with_capacity(self.body.len() + 16);
+        file.extend_from_slice(&[14, 0x20]);
+        file.extend_from_slice(&PROFILE_VERSION.to_le_bytes());
+        file.extend_from_slice(&(self.body.len() as u32).to_le_bytes());
+        file.extend_from_slice(b".FIT");
+        let header_crc = crc16(0, &file);
+        file.extend_from_slice(&header_crc.to_le_bytes());
+        file.extend_from_slice(&self.body);
+        let crc = crc16(0, &file);
+        file.extend_from_slice(&crc.to_le_bytes());
+        file
+    }
+}
+
+fn fit_timestamp(time: DateTime<Utc>) -> u32 {
+    (time.timestamp() - FIT_EPOCH_OFFSET).max(0) as u32
+}
+
+fn to_semicircles(degrees: f64) -> [u8; 4] {
+    ((degrees / SEMICIRCLES_TO_DEGREES).round() as i32).to_le_bytes()
+}
+
+/// Null-terminated name, truncated on a character boundary to fit the field
+fn course_name(name: &str) -> [u8; COURSE_NAME_BYTES] {
+    let mut bytes = [0u8; COURSE_NAME_BYTES];
+    let mut end = name.len().min(COURSE_NAME_BYTES - 1);
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    bytes[..end].copy_from_slice(&name.as_bytes()[..end]);
+    bytes
+}
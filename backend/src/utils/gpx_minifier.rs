@@ -1,6 +1,6 @@
 // backend/src/utils/gpx_minifier.rs - FIXED version that actually reduces size
 
-use gpx::{Gpx, Track, TrackSegment, Waypoint};
+use gpx::{Gpx, Metadata, Route, Track, TrackSegment, Waypoint};
 use geo::Point;
 use std::io::Cursor;
 use crate::error::AppError;
@@ -21,12 +21,20 @@ pub fn minify_gpx(gpx_content: &str) -> Result<String, AppError> {
     let mut minimal_gpx = Gpx {
         version: original_gpx.version,
         creator: Some("CurveMatch".to_string()),
-        metadata: None, // Remove all metadata
-        waypoints: vec![], // No waypoints
+        metadata: None,
+        waypoints: original_gpx.waypoints.iter().map(minimal_waypoint).collect(),
         tracks: vec![],
-        routes: vec![], // No routes
+        routes: vec![],
     };
     
+    // Keep only the file name, used to label waypoint-only files
+    if let Some(name) = original_gpx.metadata.as_ref().and_then(|m| m.name.clone()) {
+        minimal_gpx.metadata = Some(Metadata {
+            name: Some(name),
+            ..Default::default()
+        });
+    }
+    
     // Process each track
     for (track_idx, track) in original_gpx.tracks.iter().enumerate() {
         let mut minimal_track = Track::new();
@@ -43,16 +51,7 @@ pub fn minify_gpx(gpx_content: &str) -> Result<String, AppError> {
             let mut minimal_segment = TrackSegment::new();
             
             // Keep only essential waypoint data
-            for point in &segment.points {
-                let coord = point.point();
-                let geo_point = Point::new(coord.x(), coord.y());
-                let mut minimal_point = Waypoint::new(geo_point);
-                
-                // Only keep elevation if available
-                minimal_point.elevation = point.elevation;
-                
-                minimal_segment.points.push(minimal_point);
-            }
+            minimal_segment.points = segment.points.iter().map(minimal_waypoint).collect();
             
             if !minimal_segment.points.is_empty() {
                 minimal_track.segments.push(minimal_segment);
@@ -64,6 +63,14 @@ pub fn minify_gpx(gpx_content: &str) -> Result<String, AppError> {
         }
     }
     
+    // Planner exports use <rte>/<rtept> instead of tracks
+    for route in &original_gpx.routes {
+        let mut minimal_route = Route::new();
+        minimal_route.name = route.name.clone();
+        minimal_route.points = route.points.iter().map(minimal_waypoint).collect();
+        minimal_gpx.routes.push(minimal_route);
+    }
+    
     // Write minimal GPX
     let mut output = Vec::new();
     gpx::write(&minimal_gpx, &mut output)
@@ -80,7 +87,9 @@ pub fn minify_gpx(gpx_content: &str) -> Result<String, AppError> {
     let minimal_points: usize = minimal_gpx.tracks.iter()
         .flat_map(|t| &t.segments)
         .map(|s| s.points.len())
-        .sum();
+        .chain(minimal_gpx.routes.iter().map(|r| r.points.len()))
+        .sum::<usize>()
+        + minimal_gpx.waypoints.len();
     
     tracing::info!(
         "GPX minified: {} bytes -> {} bytes ({:.1}% reduction), {} points preserved",
@@ -93,6 +102,17 @@ pub fn minify_gpx(gpx_content: &str) -> Result<String, AppError> {
     Ok(minimal_content)
 }
 
+/// Copy a point keeping only position and elevation
+fn minimal_waypoint(point: &Waypoint) -> Waypoint {
+    let coord = point.point();
+    let mut minimal_point = Waypoint::new(Point::new(coord.x(), coord.y()));
+    
+    // Only keep elevation if available
+    minimal_point.elevation = point.elevation;
+    
+    minimal_point
+}
+
 /// Estimate the size reduction we can achieve
 pub fn estimate_reduction(gpx_content: &str) -> f64 {
     // Count various elements to estimate reduction
@@ -1,9 +1,24 @@
 ﻿use geo::LineString;
-use gpx::Gpx;
+use gpx::{Gpx, Waypoint};
+use serde::Serialize;
 use std::io::Cursor;
 use crate::error::AppError;
+use crate::matching::engine::haversine_distance;
+use super::dem::DemProvider;
+
+/// Jumps between consecutive track segments longer than this (metres) are
+/// reported as gaps
+pub const SEGMENT_GAP_THRESHOLD_M: f64 = 50.0;
 
 pub fn parse_gpx(gpx_content: &str) -> Result<ParsedGpx, AppError> {
+    parse_gpx_selection(gpx_content, GpxSelection::Auto)
+}
+
+/// Parse one track, route or the waypoint list of a GPX file.
+///
+/// Track segments are concatenated in order; jumps between them are
+/// recorded in `segment_gaps`.
+pub fn parse_gpx_selection(gpx_content: &str, selection: GpxSelection) -> Result<ParsedGpx, AppError> {
     tracing::debug!("Parsing GPX content: {} bytes", gpx_content.len());
     
     let cursor = Cursor::new(gpx_content);
@@ -14,56 +29,385 @@ pub fn parse_gpx(gpx_content: &str) -> Result<ParsedGpx, AppError> {
             AppError::FileError(format!("Failed to parse GPX: {}", e))
         })?;
     
-    // Extract the first track and segment
-    let track = gpx.tracks.first()
-        .ok_or_else(|| {
-            tracing::error!("No tracks found in GPX");
-            AppError::FileError("No tracks found in GPX".to_string())
-        })?;
+    build_parsed(gpx_sources(&gpx), selection, "GPX")
+}
+
+/// A point read from any supported input format
+#[derive(Debug, Clone, Copy)]
+pub struct SourcePoint {
+    pub lon: f64,
+    pub lat: f64,
+    pub elevation: Option<f64>,
+}
+
+impl From<&Waypoint> for SourcePoint {
+    fn from(waypoint: &Waypoint) -> Self {
+        SourcePoint {
+            lon: waypoint.point().x(),
+            lat: waypoint.point().y(),
+            elevation: waypoint.elevation,
+        }
+    }
+}
+
+/// A track, route or waypoint list read from any supported input format
+#[derive(Debug, Clone)]
+pub struct SourceTrack {
+    pub kind: GpxSourceKind,
+    pub name: Option<String>,
+    pub segments: Vec<Vec<SourcePoint>>,
+}
+
+impl SourceTrack {
+    fn point_count(&self) -> usize {
+        self.segments.iter().map(|s| s.len()).sum()
+    }
+}
+
+fn gpx_sources(gpx: &Gpx) -> Vec<SourceTrack> {
+    let tracks = gpx.tracks.iter().map(|track| SourceTrack {
+        kind: GpxSourceKind::Track,
+        name: track.name.clone(),
+        segments: track.segments.iter()
+            .map(|segment| segment.points.iter().map(SourcePoint::from).collect())
+            .collect(),
+    });
+    let routes = gpx.routes.iter().map(|route| SourceTrack {
+        kind: GpxSourceKind::Route,
+        name: route.name.clone(),
+        segments: vec![route.points.iter().map(SourcePoint::from).collect()],
+    });
+    let waypoints = (!gpx.waypoints.is_empty()).then(|| SourceTrack {
+        kind: GpxSourceKind::Waypoints,
+        name: gpx.metadata.as_ref().and_then(|m| m.name.clone()),
+        segments: vec![gpx.waypoints.iter().map(SourcePoint::from).collect()],
+    });
     
-    let segment = track.segments.first()
-        .ok_or_else(|| {
-            tracing::error!("No segments found in track");
-            AppError::FileError("No segments found in track".to_string())
-        })?;
+    tracks.chain(routes).chain(waypoints).collect()
+}
+
+/// Build the input route from the selected source of a parsed file.
+///
+/// `format` names the file type in error messages.
+pub fn build_parsed(
+    sources: Vec<SourceTrack>,
+    selection: GpxSelection,
+    format: &str,
+) -> Result<ParsedGpx, AppError> {
+    let selections = source_selections(&sources);
+    let selection = match selection {
+        GpxSelection::Auto => default_selection(&sources, &selections).ok_or_else(|| {
+            tracing::error!("No tracks, routes or waypoints found in {}", format);
+            AppError::FileError(format!("No tracks, routes or waypoints found in {}", format))
+        })?,
+        other => other,
+    };
     
-    // Convert to LineString
-    let points: Vec<(f64, f64)> = segment.points.iter()
-        .map(|p| (p.point().x(), p.point().y()))
-        .collect();
+    let selected = selections.iter()
+        .position(|s| *s == selection)
+        .map(|position| &sources[position])
+        .ok_or_else(|| AppError::FileError(match selection {
+            GpxSelection::Track(index) => format!("{} has no track {}", format, index),
+            GpxSelection::Route(index) => format!("{} has no route {}", format, index),
+            _ => format!("{} has no waypoints", format),
+        }))?;
+    
+    // Concatenate segments, noting where consecutive segments don't meet
+    let mut points: Vec<(f64, f64)> = Vec::new();
+    let mut elevations: Vec<Option<f64>> = Vec::new();
+    let mut segment_gaps = Vec::new();
+    for part in selected.segments.iter().filter(|s| !s.is_empty()) {
+        if let (Some(&(last_x, last_y)), Some(first)) = (points.last(), part.first()) {
+            let gap = haversine_distance(last_y, last_x, first.lat, first.lon);
+            if gap > SEGMENT_GAP_THRESHOLD_M {
+                segment_gaps.push(SegmentGap { after_point: points.len() - 1, distance_m: gap });
+            }
+        }
+        for point in part {
+            points.push((point.lon, point.lat));
+            // Keep one elevation slot per point so gaps don't shift the profile
+            elevations.push(point.elevation);
+        }
+    }
     
     if points.len() < 2 {
         tracing::error!("Track has only {} points, need at least 2", points.len());
         return Err(AppError::FileError("Track must have at least 2 points".to_string()));
     }
     
-    let geometry = LineString::from(points);
-    
-    // Extract elevation profile
-    let elevation_profile: Vec<f64> = segment.points.iter()
-        .filter_map(|p| p.elevation)
-        .collect();
+    if !segment_gaps.is_empty() {
+        tracing::warn!("{} has {} gaps between track segments", format, segment_gaps.len());
+    }
     
-    // Get track name
-    let name = track.name.clone();
+    let geometry = LineString::from(points);
+    let name = selected.name.clone();
     
     tracing::info!(
-        "Parsed GPX: name={:?}, points={}, elevations={}", 
+        "Parsed {}: name={:?}, source={:?}, points={}, elevations={}", 
+        format,
         name, 
-        segment.points.len(),
-        elevation_profile.len()
+        selection,
+        geometry.0.len(),
+        elevations.iter().filter(|e| e.is_some()).count()
     );
     
-    Ok(ParsedGpx {
+    let mut parsed = ParsedGpx {
         name,
         geometry,
-        elevation_profile,
-    })
+        elevations,
+        elevation_profile: Vec::new(),
+        filled_elevations: 0,
+        sources: list_sources(&sources, &selections, selection),
+        segment_gaps,
+    };
+    parsed.fill_elevation_gaps(ElevationGapFill::Interpolate, None)?;
+    
+    Ok(parsed)
+}
+
+/// Part of a file to use as the input route
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GpxSelection {
+    /// First track with points, else the first route, else the waypoints
+    #[default]
+    Auto,
+    Track(usize),
+    Route(usize),
+    Waypoints,
+}
+
+impl GpxSelection {
+    /// Parse `auto`, `waypoints`, `track:<index>` or `route:<index>`
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        match value {
+            "auto" => return Some(GpxSelection::Auto),
+            "waypoints" => return Some(GpxSelection::Waypoints),
+            _ => {}
+        }
+        let (kind, index) = value.split_once(':')?;
+        let index = index.trim().parse().ok()?;
+        match kind {
+            "track" => Some(GpxSelection::Track(index)),
+            "route" => Some(GpxSelection::Route(index)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum GpxSourceKind {
+    #[serde(rename = "track")]
+    Track,
+    #[serde(rename = "route")]
+    Route,
+    #[serde(rename = "waypoints")]
+    Waypoints,
+}
+
+/// A track, route or waypoint list available in the uploaded file
+#[derive(Debug, Clone, Serialize)]
+pub struct GpxSource {
+    pub kind: GpxSourceKind,
+    pub index: usize,
+    pub name: Option<String>,
+    pub points: usize,
+    pub selected: bool,
+}
+
+/// Jump between the end of one track segment and the start of the next
+#[derive(Debug, Clone, Serialize)]
+pub struct SegmentGap {
+    /// Index of the last point before the gap
+    #[serde(rename = "afterPoint")]
+    pub after_point: usize,
+    #[serde(rename = "distanceM")]
+    pub distance_m: f64,
+}
+
+/// Selection addressing each source; tracks and routes are numbered
+/// separately
+fn source_selections(sources: &[SourceTrack]) -> Vec<GpxSelection> {
+    let (mut tracks, mut routes) = (0, 0);
+    sources.iter()
+        .map(|source| match source.kind {
+            GpxSourceKind::Track => {
+                tracks += 1;
+                GpxSelection::Track(tracks - 1)
+            }
+            GpxSourceKind::Route => {
+                routes += 1;
+                GpxSelection::Route(routes - 1)
+            }
+            GpxSourceKind::Waypoints => GpxSelection::Waypoints,
+        })
+        .collect()
+}
+
+fn default_selection(sources: &[SourceTrack], selections: &[GpxSelection]) -> Option<GpxSelection> {
+    [GpxSourceKind::Track, GpxSourceKind::Route, GpxSourceKind::Waypoints]
+        .iter()
+        .find_map(|kind| {
+            sources.iter()
+                .position(|source| source.kind == *kind && source.point_count() > 0)
+        })
+        .map(|position| selections[position])
+}
+
+fn list_sources(
+    sources: &[SourceTrack],
+    selections: &[GpxSelection],
+    selection: GpxSelection,
+) -> Vec<GpxSource> {
+    sources.iter()
+        .zip(selections)
+        .map(|(source, &this)| GpxSource {
+            kind: source.kind,
+            index: match this {
+                GpxSelection::Track(index) | GpxSelection::Route(index) => index,
+                _ => 0,
+            },
+            name: source.name.clone(),
+            points: source.point_count(),
+            selected: this == selection,
+        })
+        .collect()
 }
 
 #[derive(Debug)]
 pub struct ParsedGpx {
     pub name: Option<String>,
     pub geometry: LineString<f64>,
+    /// Recorded elevation for each point of `geometry`
+    pub elevations: Vec<Option<f64>>,
+    /// Gap-filled elevation aligned with `geometry`; empty when the track
+    /// has no elevation at all
     pub elevation_profile: Vec<f64>,
+    /// Number of points whose elevation was filled in
+    pub filled_elevations: usize,
+    /// Everything in the file that could be matched
+    pub sources: Vec<GpxSource>,
+    pub segment_gaps: Vec<SegmentGap>,
+}
+
+/// How to handle points recorded without `<ele>`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ElevationGapFill {
+    /// Linear interpolation by distance between the neighbouring points
+    #[default]
+    Interpolate,
+    /// Sample the local DEM, interpolating where it has no coverage
+    Dem,
+    /// Refuse tracks with missing elevations
+    Reject,
+}
+
+impl ElevationGapFill {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "interpolate" => Some(ElevationGapFill::Interpolate),
+            "dem" => Some(ElevationGapFill::Dem),
+            "reject" => Some(ElevationGapFill::Reject),
+            _ => None,
+        }
+    }
+}
+
+impl ParsedGpx {
+    /// Rebuild `elevation_profile` from `elevations`, filling gaps with the
+    /// given strategy. Returns the number of filled points.
+    pub fn fill_elevation_gaps(
+        &mut self,
+        strategy: ElevationGapFill,
+        dem: Option<&DemProvider>,
+    ) -> Result<usize, AppError> {
+        let missing = self.elevations.iter().filter(|e| e.is_none()).count();
+        let mut filled: Vec<Option<f64>> = self.elevations.clone();
+        
+        if missing > 0 {
+            match strategy {
+                ElevationGapFill::Reject => {
+                    return Err(AppError::FileError(format!(
+                        "Track has {} of {} points without elevation",
+                        missing,
+                        self.elevations.len()
+                    )));
+                }
+                ElevationGapFill::Dem => {
+                    let dem = dem.ok_or_else(|| {
+                        AppError::BadRequest("DEM gap filling requested but no DEM is configured".to_string())
+                    })?;
+                    for (elevation, point) in filled.iter_mut().zip(self.geometry.points()) {
+                        if elevation.is_none() {
+                            *elevation = dem.elevation_at(point.x(), point.y());
+                        }
+                    }
+                }
+                ElevationGapFill::Interpolate => {}
+            }
+        }
+        
+        self.elevation_profile = interpolate_gaps(&self.geometry, &filled);
+        self.filled_elevations = if self.elevation_profile.is_empty() { 0 } else { missing };
+        
+        if self.filled_elevations > 0 {
+            tracing::info!(
+                "Filled {} missing elevations ({:?})",
+                self.filled_elevations, strategy
+            );
+        }
+        
+        Ok(self.filled_elevations)
+    }
+}
+
+/// Fill `None` elevations linearly by distance along the line; leading and
+/// trailing gaps take the nearest known value. Returns an empty profile if
+/// no point has an elevation.
+fn interpolate_gaps(line: &LineString<f64>, elevations: &[Option<f64>]) -> Vec<f64> {
+    let known: Vec<usize> = (0..elevations.len())
+        .filter(|&i| elevations[i].is_some())
+        .collect();
+    if known.is_empty() {
+        return Vec::new();
+    }
+    
+    let mut distances = vec![0.0];
+    for segment in line.lines() {
+        let d = haversine_distance(segment.start.y, segment.start.x, segment.end.y, segment.end.x);
+        distances.push(distances.last().unwrap() + d);
+    }
+    
+    let mut profile = Vec::with_capacity(elevations.len());
+    let mut next_known = 0;
+    for i in 0..elevations.len() {
+        if let Some(elevation) = elevations[i] {
+            profile.push(elevation);
+            continue;
+        }
+        
+        while next_known < known.len() && known[next_known] < i {
+            next_known += 1;
+        }
+        let before = next_known.checked_sub(1).map(|k| known[k]);
+        let after = known.get(next_known).copied();
+        
+        let value = match (before, after) {
+            (Some(b), Some(a)) => {
+                let (eb, ea) = (elevations[b].unwrap(), elevations[a].unwrap());
+                let span = distances[a] - distances[b];
+                if span > 0.0 {
+                    eb + (ea - eb) * (distances[i] - distances[b]) / span
+                } else {
+                    eb
+                }
+            }
+            (Some(b), None) => elevations[b].unwrap(),
+            (None, Some(a)) => elevations[a].unwrap(),
+            (None, None) => unreachable!("at least one known elevation"),
+        };
+        profile.push(value);
+    }
+    
+    profile
 }
\ No newline at end of file
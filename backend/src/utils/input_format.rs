@@ -0,0 +1,356 @@
+// backend/src/utils/input_format.rs - detect and parse uploaded route files
+
+use roxmltree::{Document, Node};
+use serde::Serialize;
+use serde_json::Value;
+use crate::error::AppError;
+use super::fit;
+use super::gpx_parser::{
+    build_parsed, parse_gpx_selection, GpxSelection, GpxSourceKind, ParsedGpx, SourcePoint,
+    SourceTrack,
+};
+
+/// File formats accepted as an input route
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum InputFormat {
+    #[serde(rename = "gpx")]
+    Gpx,
+    #[serde(rename = "tcx")]
+    Tcx,
+    #[serde(rename = "kml")]
+    Kml,
+    #[serde(rename = "geojson")]
+    GeoJson,
+    #[serde(rename = "fit")]
+    Fit,
+}
+
+impl InputFormat {
+    /// Identify a file from its magic bytes or XML root element
+    pub fn detect(data: &[u8]) -> Result<Self, AppError> {
+        if fit::is_fit(data) {
+            return Ok(InputFormat::Fit);
+        }
+        if data.starts_with(b"PK\x03\x04") {
+            return Err(AppError::FileError(
+                "Zip archives (including KMZ) are not supported; upload the extracted file".to_string(),
+            ));
+        }
+        if data.starts_with(&[0x1f, 0x8b]) {
+            return Err(AppError::FileError("Compressed uploads are not supported".to_string()));
+        }
+
+        let text = std::str::from_utf8(data).map_err(|_| unrecognised())?;
+        let text = text.trim_start_matches('\u{feff}').trim_start();
+
+        if text.starts_with('{') {
+            return Ok(InputFormat::GeoJson);
+        }
+        if text.starts_with('<') {
+            return match xml_root_name(text) {
+                Some("gpx") => Ok(InputFormat::Gpx),
+                Some("TrainingCenterDatabase") => Ok(InputFormat::Tcx),
+                Some("kml") => Ok(InputFormat::Kml),
+                Some(other) => Err(AppError::FileError(format!(
+                    "Unsupported XML document <{}>; expected GPX, TCX or KML",
+                    other
+                ))),
+                None => Err(unrecognised()),
+            };
+        }
+
+        Err(unrecognised())
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            InputFormat::Gpx => "GPX",
+            InputFormat::Tcx => "TCX",
+            InputFormat::Kml => "KML",
+            InputFormat::GeoJson => "GeoJSON",
+            InputFormat::Fit => "FIT",
+        }
+    }
+}
+
+/// Parse an uploaded route file of the given format
+pub fn parse_route_file(
+    data: &[u8],
+    format: InputFormat,
+    selection: GpxSelection,
+) -> Result<ParsedGpx, AppError> {
+    let label = format.label();
+
+    if format == InputFormat::Fit {
+        return build_parsed(vec![fit::read_fit(data)?], selection, label);
+    }
+
+    let text = std::str::from_utf8(data)
+        .map_err(|_| AppError::FileError(format!("Invalid {} file encoding", label)))?
+        .trim_start_matches('\u{feff}');
+
+    let sources = match format {
+        InputFormat::Gpx => return parse_gpx_selection(text, selection),
+        InputFormat::Tcx => tcx_sources(&parse_xml(text, label)?),
+        InputFormat::Kml => kml_sources(&parse_xml(text, label)?),
+        InputFormat::GeoJson => {
+            let json: Value = serde_json::from_str(text)
+                .map_err(|e| AppError::FileError(format!("Failed to parse GeoJSON: {}", e)))?;
+            geojson_sources(&json)?
+        }
+        InputFormat::Fit => unreachable!("FIT is handled above"),
+    };
+
+    build_parsed(sources, selection, label)
+}
+
+fn unrecognised() -> AppError {
+    AppError::FileError("Unrecognised file format; expected GPX, TCX, KML, GeoJSON or FIT".to_string())
+}
+
+/// Local name of the first element, skipping the prolog and comments
+fn xml_root_name(text: &str) -> Option<&str> {
+    let mut rest = text;
+    loop {
+        rest = rest.trim_start();
+        if let Some(after) = rest.strip_prefix("<?") {
+            rest = &after[after.find("?>")? + 2..];
+        } else if let Some(after) = rest.strip_prefix("<!--") {
+            rest = &after[after.find("-->")? + 3..];
+        } else if let Some(after) = rest.strip_prefix("<!") {
+            rest = &after[after.find('>')? + 1..];
+        } else {
+            let tag = rest.strip_prefix('<')?;
+            let end = tag.find(|c: char| c.is_whitespace() || c == '>' || c == '/')?;
+            let name = &tag[..end];
+            return Some(name.rsplit(':').next().unwrap_or(name));
+        }
+    }
+}
+
+fn parse_xml<'a>(text: &'a str, label: &str) -> Result<Document<'a>, AppError> {
+    Document::parse(text).map_err(|e| AppError::FileError(format!("Failed to parse {}: {}", label, e)))
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|c| c.is_element() && c.tag_name().name() == name)
+}
+
+fn child_text<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    child(node, name).and_then(|c| c.text()).map(str::trim)
+}
+
+fn descendants_named<'a, 'input: 'a>(
+    node: Node<'a, 'input>,
+    name: &'a str,
+) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    node.descendants().filter(move |n| n.is_element() && n.tag_name().name() == name)
+}
+
+/// Activities become tracks (one segment per lap track), courses become routes
+fn tcx_sources(doc: &Document) -> Vec<SourceTrack> {
+    let root = doc.root_element();
+
+    let track_points = |track: Node| -> Vec<SourcePoint> {
+        descendants_named(track, "Trackpoint")
+            .filter_map(|point| {
+                let position = child(point, "Position")?;
+                Some(SourcePoint {
+                    lat: child_text(position, "LatitudeDegrees")?.parse().ok()?,
+                    lon: child_text(position, "LongitudeDegrees")?.parse().ok()?,
+                    elevation: child_text(point, "AltitudeMeters").and_then(|t| t.parse().ok()),
+                })
+            })
+            .collect()
+    };
+
+    let activities = descendants_named(root, "Activity").map(|activity| SourceTrack {
+        kind: GpxSourceKind::Track,
+        name: child_text(activity, "Notes").map(str::to_string),
+        segments: descendants_named(activity, "Track").map(track_points).collect(),
+    });
+    let courses = descendants_named(root, "Course").map(|course| SourceTrack {
+        kind: GpxSourceKind::Route,
+        name: child_text(course, "Name").map(str::to_string),
+        segments: descendants_named(course, "Track").map(track_points).collect(),
+    });
+
+    activities.chain(courses).collect()
+}
+
+/// Line placemarks become tracks; point placemarks are gathered as waypoints
+fn kml_sources(doc: &Document) -> Vec<SourceTrack> {
+    let root = doc.root_element();
+    let mut sources = Vec::new();
+    let mut waypoints = Vec::new();
+
+    for placemark in descendants_named(root, "Placemark") {
+        let mut segments = Vec::new();
+        for geometry in placemark.descendants().filter(|n| n.is_element()) {
+            match geometry.tag_name().name() {
+                "LineString" => {
+                    if let Some(coords) = child_text(geometry, "coordinates") {
+                        segments.push(kml_coordinates(coords));
+                    }
+                }
+                // gx:Track, with space-separated "lon lat alt" coordinates
+                "Track" => {
+                    let points = descendants_named(geometry, "coord")
+                        .filter_map(|coord| {
+                            let values: Vec<f64> = coord.text()?
+                                .split_whitespace()
+                                .filter_map(|v| v.parse().ok())
+                                .collect();
+                            Some(SourcePoint {
+                                lon: *values.first()?,
+                                lat: *values.get(1)?,
+                                elevation: values.get(2).copied(),
+                            })
+                        })
+                        .collect();
+                    segments.push(points);
+                }
+                "Point" => {
+                    if let Some(coords) = child_text(geometry, "coordinates") {
+                        waypoints.extend(kml_coordinates(coords));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if !segments.is_empty() {
+            drop_clamped_altitudes(&mut segments);
+            sources.push(SourceTrack {
+                kind: GpxSourceKind::Track,
+                name: child_text(placemark, "name").map(str::to_string),
+                segments,
+            });
+        }
+    }
+
+    if !waypoints.is_empty() {
+        let mut segments = vec![waypoints];
+        drop_clamped_altitudes(&mut segments);
+        let name = child(root, "Document")
+            .and_then(|document| child_text(document, "name"))
+            .map(str::to_string);
+        sources.push(SourceTrack { kind: GpxSourceKind::Waypoints, name, segments });
+    }
+
+    sources
+}
+
+/// Parse whitespace-separated "lon,lat[,alt]" tuples
+fn kml_coordinates(text: &str) -> Vec<SourcePoint> {
+    text.split_whitespace()
+        .filter_map(|tuple| {
+            let mut values = tuple.split(',').map(|v| v.trim().parse::<f64>());
+            Some(SourcePoint {
+                lon: values.next()?.ok()?,
+                lat: values.next()?.ok()?,
+                elevation: values.next().and_then(|v| v.ok()),
+            })
+        })
+        .collect()
+}
+
+/// Planners write altitude 0 for ground-clamped geometry; treat an all-zero
+/// altitude list as missing rather than sea level
+fn drop_clamped_altitudes(segments: &mut [Vec<SourcePoint>]) {
+    let all_zero = segments.iter().flatten().all(|p| p.elevation.unwrap_or(0.0) == 0.0);
+    if all_zero {
+        segments.iter_mut().flatten().for_each(|p| p.elevation = None);
+    }
+}
+
+/// Line features become tracks; point features are gathered as waypoints
+fn geojson_sources(json: &Value) -> Result<Vec<SourceTrack>, AppError> {
+    let features: Vec<&Value> = match json.get("type").and_then(Value::as_str) {
+        Some("FeatureCollection") => json.get("features")
+            .and_then(Value::as_array)
+            .map(|features| features.iter().collect())
+            .unwrap_or_default(),
+        Some(_) => vec![json],
+        None => return Err(AppError::FileError("GeoJSON object has no type".to_string())),
+    };
+
+    let mut sources = Vec::new();
+    let mut waypoints = Vec::new();
+
+    for feature in features {
+        // Bare geometries are accepted as well as features
+        let geometry = match feature.get("type").and_then(Value::as_str) {
+            Some("Feature") => match feature.get("geometry") {
+                Some(geometry) if !geometry.is_null() => geometry,
+                _ => continue,
+            },
+            _ => feature,
+        };
+        let name = feature.pointer("/properties/name")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+
+        let mut segments = Vec::new();
+        collect_geojson_geometry(geometry, &mut segments, &mut waypoints)?;
+        if !segments.is_empty() {
+            sources.push(SourceTrack { kind: GpxSourceKind::Track, name, segments });
+        }
+    }
+
+    if !waypoints.is_empty() {
+        sources.push(SourceTrack {
+            kind: GpxSourceKind::Waypoints,
+            name: None,
+            segments: vec![waypoints],
+        });
+    }
+
+    Ok(sources)
+}
+
+fn collect_geojson_geometry(
+    geometry: &Value,
+    segments: &mut Vec<Vec<SourcePoint>>,
+    waypoints: &mut Vec<SourcePoint>,
+) -> Result<(), AppError> {
+    let coordinates = geometry.get("coordinates");
+    match geometry.get("type").and_then(Value::as_str) {
+        Some("LineString") => segments.push(geojson_positions(coordinates)),
+        Some("MultiLineString") => {
+            for line in coordinates.and_then(Value::as_array).into_iter().flatten() {
+                segments.push(geojson_positions(Some(line)));
+            }
+        }
+        Some("Point") => waypoints.extend(coordinates.and_then(geojson_position)),
+        Some("MultiPoint") => waypoints.extend(geojson_positions(coordinates)),
+        Some("GeometryCollection") => {
+            for inner in geometry.get("geometries").and_then(Value::as_array).into_iter().flatten() {
+                collect_geojson_geometry(inner, segments, waypoints)?;
+            }
+        }
+        Some("Polygon") | Some("MultiPolygon") => {}
+        Some(other) => {
+            return Err(AppError::FileError(format!("Unsupported GeoJSON geometry type {}", other)));
+        }
+        None => return Err(AppError::FileError("GeoJSON geometry has no type".to_string())),
+    }
+    Ok(())
+}
+
+fn geojson_positions(coordinates: Option<&Value>) -> Vec<SourcePoint> {
+    coordinates
+        .and_then(Value::as_array)
+        .map(|positions| positions.iter().filter_map(geojson_position).collect())
+        .unwrap_or_default()
+}
+
+/// `[lon, lat, ele?]`
+fn geojson_position(position: &Value) -> Option<SourcePoint> {
+    let values = position.as_array()?;
+    Some(SourcePoint {
+        lon: values.first()?.as_f64()?,
+        lat: values.get(1)?.as_f64()?,
+        elevation: values.get(2).and_then(Value::as_f64),
+    })
+}
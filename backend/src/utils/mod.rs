@@ -1,4 +1,9 @@
 ﻿// backend/src/utils/mod.rs
 pub mod gpx_parser;
 pub mod gpx_minifier;
-pub mod elevation;
\ No newline at end of file
+pub mod elevation;
+pub mod osm_pbf;
+pub mod dem;
+pub mod fit;
+pub mod input_format;
+pub mod route_export;
\ No newline at end of file
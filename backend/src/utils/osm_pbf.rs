@@ -0,0 +1,372 @@
+// backend/src/utils/osm_pbf.rs - minimal OSM PBF reader for highway ways
+
+use flate2::read::ZlibDecoder;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use crate::error::AppError;
+
+/// A way tagged with `highway=*`, resolved to coordinates
+#[derive(Debug, Clone)]
+pub struct HighwayWay {
+    pub highway: String,
+    pub coords: Vec<(f64, f64)>, // (lon, lat)
+}
+
+/// Read every `highway=*` way from an .osm.pbf extract.
+///
+/// The file is scanned twice: the first pass collects highway ways and the
+/// node ids they reference, the second resolves only those nodes. Blocks are
+/// streamed one at a time, so memory use doesn't grow with the extract.
+pub fn read_highway_ways(path: &Path) -> Result<Vec<HighwayWay>, AppError> {
+    // First pass: ways with a highway tag
+    let mut ways = Vec::new();
+    let mut wanted_nodes = HashSet::new();
+    for_each_data_block(path, |block| {
+        let block = PrimitiveBlock::parse(block)?;
+        for group in &block.groups {
+            for way in iter_fields(group, 3) {
+                let way = parse_way(way?, &block.strings)?;
+                if let Some(highway) = way.highway {
+                    wanted_nodes.extend(way.refs.iter().copied());
+                    ways.push((highway, way.refs));
+                }
+            }
+        }
+        Ok(())
+    })?;
+
+    // Second pass: coordinates for referenced nodes
+    let mut nodes: HashMap<i64, (f64, f64)> = HashMap::with_capacity(wanted_nodes.len());
+    for_each_data_block(path, |block| {
+        let block = PrimitiveBlock::parse(block)?;
+        for group in &block.groups {
+            for node in iter_fields(group, 1) {
+                let (id, lat, lon) = parse_node(node?)?;
+                if wanted_nodes.contains(&id) {
+                    nodes.insert(id, block.to_lon_lat(lat, lon));
+                }
+            }
+            for dense in iter_fields(group, 2) {
+                for (id, lat, lon) in parse_dense_nodes(dense?)? {
+                    if wanted_nodes.contains(&id) {
+                        nodes.insert(id, block.to_lon_lat(lat, lon));
+                    }
+                }
+            }
+        }
+        Ok(())
+    })?;
+
+    let result: Vec<HighwayWay> = ways
+        .into_iter()
+        .filter_map(|(highway, refs)| {
+            let coords: Vec<(f64, f64)> = refs.iter().filter_map(|r| nodes.get(r).copied()).collect();
+            if coords.len() >= 2 {
+                Some(HighwayWay { highway, coords })
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    tracing::info!(
+        "Read {} highway ways ({} nodes) from {}",
+        result.len(),
+        nodes.len(),
+        path.display()
+    );
+
+    Ok(result)
+}
+
+/// Hand each decompressed `OSMData` blob of the file to `f` in turn
+fn for_each_data_block(
+    path: &Path,
+    mut f: impl FnMut(&[u8]) -> Result<(), AppError>,
+) -> Result<(), AppError> {
+    let read_error = |e: std::io::Error| AppError::FileError(format!("Failed to read {}: {}", path.display(), e));
+    let file = File::open(path).map_err(read_error)?;
+    let mut reader = BufReader::new(file);
+    let mut header = Vec::new();
+    let mut blob = Vec::new();
+
+    while !reader.fill_buf().map_err(read_error)?.is_empty() {
+        let mut len = [0u8; 4];
+        read_exact(&mut reader, &mut len)?;
+        header.resize(u32::from_be_bytes(len) as usize, 0);
+        read_exact(&mut reader, &mut header)?;
+
+        let mut is_data = false;
+        let mut data_size = 0usize;
+        for field in ProtoIter::new(&header) {
+            match field? {
+                (1, Value::Bytes(b)) => is_data = b == b"OSMData",
+                (3, Value::Varint(v)) => data_size = v as usize,
+                _ => {}
+            }
+        }
+
+        blob.resize(data_size, 0);
+        read_exact(&mut reader, &mut blob)?;
+
+        if is_data {
+            f(&decode_blob(&blob)?)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn read_exact(reader: &mut impl Read, buf: &mut [u8]) -> Result<(), AppError> {
+    reader.read_exact(buf).map_err(|e| match e.kind() {
+        std::io::ErrorKind::UnexpectedEof => truncated(),
+        _ => AppError::FileError(format!("Failed to read PBF: {}", e)),
+    })
+}
+
+fn decode_blob(blob: &[u8]) -> Result<Vec<u8>, AppError> {
+    let mut raw_size = 0usize;
+    for field in ProtoIter::new(blob) {
+        match field? {
+            (1, Value::Bytes(raw)) => return Ok(raw.to_vec()),
+            (2, Value::Varint(v)) => raw_size = v as usize,
+            (3, Value::Bytes(zlib)) => {
+                let mut out = Vec::with_capacity(raw_size);
+                ZlibDecoder::new(zlib)
+                    .read_to_end(&mut out)
+                    .map_err(|e| AppError::FileError(format!("Failed to inflate PBF blob: {}", e)))?;
+                return Ok(out);
+            }
+            _ => {}
+        }
+    }
+
+    Err(AppError::FileError("Unsupported PBF blob compression".to_string()))
+}
+
+struct PrimitiveBlock<'a> {
+    strings: Vec<&'a [u8]>,
+    groups: Vec<&'a [u8]>,
+    granularity: i64,
+    lat_offset: i64,
+    lon_offset: i64,
+}
+
+impl<'a> PrimitiveBlock<'a> {
+    fn parse(data: &'a [u8]) -> Result<Self, AppError> {
+        let mut block = PrimitiveBlock {
+            strings: Vec::new(),
+            groups: Vec::new(),
+            granularity: 100,
+            lat_offset: 0,
+            lon_offset: 0,
+        };
+
+        for field in ProtoIter::new(data) {
+            match field? {
+                (1, Value::Bytes(table)) => {
+                    for s in iter_fields(table, 1) {
+                        block.strings.push(s?);
+                    }
+                }
+                (2, Value::Bytes(group)) => block.groups.push(group),
+                (17, Value::Varint(v)) => block.granularity = v as i64,
+                (19, Value::Varint(v)) => block.lat_offset = v as i64,
+                (20, Value::Varint(v)) => block.lon_offset = v as i64,
+                _ => {}
+            }
+        }
+
+        Ok(block)
+    }
+
+    fn to_lon_lat(&self, lat: i64, lon: i64) -> (f64, f64) {
+        (
+            1e-9 * (self.lon_offset + self.granularity * lon) as f64,
+            1e-9 * (self.lat_offset + self.granularity * lat) as f64,
+        )
+    }
+}
+
+struct ParsedWay {
+    highway: Option<String>,
+    refs: Vec<i64>,
+}
+
+fn parse_way(data: &[u8], strings: &[&[u8]]) -> Result<ParsedWay, AppError> {
+    let mut keys = Vec::new();
+    let mut vals = Vec::new();
+    let mut refs = Vec::new();
+
+    for field in ProtoIter::new(data) {
+        match field? {
+            (2, Value::Bytes(b)) => keys = packed_varints(b)?,
+            (3, Value::Bytes(b)) => vals = packed_varints(b)?,
+            (8, Value::Bytes(b)) => refs = delta_decode(packed_varints(b)?),
+            _ => {}
+        }
+    }
+
+    let highway = keys.iter().zip(vals.iter()).find_map(|(k, v)| {
+        let key = strings.get(*k as usize)?;
+        if *key == b"highway" {
+            strings.get(*v as usize).map(|s| String::from_utf8_lossy(s).into_owned())
+        } else {
+            None
+        }
+    });
+
+    Ok(ParsedWay { highway, refs })
+}
+
+fn parse_node(data: &[u8]) -> Result<(i64, i64, i64), AppError> {
+    let (mut id, mut lat, mut lon) = (0, 0, 0);
+    for field in ProtoIter::new(data) {
+        match field? {
+            (1, Value::Varint(v)) => id = zigzag(v),
+            (8, Value::Varint(v)) => lat = zigzag(v),
+            (9, Value::Varint(v)) => lon = zigzag(v),
+            _ => {}
+        }
+    }
+    Ok((id, lat, lon))
+}
+
+fn parse_dense_nodes(data: &[u8]) -> Result<Vec<(i64, i64, i64)>, AppError> {
+    let mut ids = Vec::new();
+    let mut lats = Vec::new();
+    let mut lons = Vec::new();
+
+    for field in ProtoIter::new(data) {
+        match field? {
+            (1, Value::Bytes(b)) => ids = delta_decode(packed_varints(b)?),
+            (8, Value::Bytes(b)) => lats = delta_decode(packed_varints(b)?),
+            (9, Value::Bytes(b)) => lons = delta_decode(packed_varints(b)?),
+            _ => {}
+        }
+    }
+
+    Ok(ids
+        .into_iter()
+        .zip(lats)
+        .zip(lons)
+        .map(|((id, lat), lon)| (id, lat, lon))
+        .collect())
+}
+
+fn truncated() -> AppError {
+    AppError::FileError("Truncated OSM PBF file".to_string())
+}
+
+fn zigzag(v: u64) -> i64 {
+    ((v >> 1) as i64) ^ -((v & 1) as i64)
+}
+
+fn delta_decode(values: Vec<u64>) -> Vec<i64> {
+    let mut acc = 0i64;
+    values
+        .into_iter()
+        .map(|v| {
+            acc += zigzag(v);
+            acc
+        })
+        .collect()
+}
+
+fn packed_varints(mut data: &[u8]) -> Result<Vec<u64>, AppError> {
+    let mut values = Vec::new();
+    while !data.is_empty() {
+        values.push(read_varint(&mut data)?);
+    }
+    Ok(values)
+}
+
+fn read_varint(data: &mut &[u8]) -> Result<u64, AppError> {
+    let mut result = 0u64;
+    let mut shift = 0;
+    loop {
+        let (&byte, rest) = data.split_first().ok_or_else(truncated)?;
+        *data = rest;
+        result |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(result);
+        }
+        shift += 7;
+        if shift > 63 {
+            return Err(AppError::FileError("Invalid varint in OSM PBF file".to_string()));
+        }
+    }
+}
+
+/// Iterate over the length-delimited occurrences of one field number
+fn iter_fields(data: &[u8], number: u32) -> impl Iterator<Item = Result<&[u8], AppError>> {
+    ProtoIter::new(data).filter_map(move |field| match field {
+        Ok((n, Value::Bytes(b))) if n == number => Some(Ok(b)),
+        Ok(_) => None,
+        Err(e) => Some(Err(e)),
+    })
+}
+
+enum Value<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+/// Minimal protobuf wire-format field iterator
+struct ProtoIter<'a> {
+    data: &'a [u8],
+    failed: bool,
+}
+
+impl<'a> ProtoIter<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, failed: false }
+    }
+
+    fn next_field(&mut self) -> Result<(u32, Value<'a>), AppError> {
+        let key = read_varint(&mut self.data)?;
+        let number = (key >> 3) as u32;
+        let value = match key & 0x7 {
+            0 => Value::Varint(read_varint(&mut self.data)?),
+            1 => {
+                self.data = self.data.get(8..).ok_or_else(truncated)?;
+                Value::Fixed
+            }
+            2 => {
+                let len = read_varint(&mut self.data)? as usize;
+                let bytes = self.data.get(..len).ok_or_else(truncated)?;
+                self.data = &self.data[len..];
+                Value::Bytes(bytes)
+            }
+            5 => {
+                self.data = self.data.get(4..).ok_or_else(truncated)?;
+                Value::Fixed
+            }
+            wire => {
+                return Err(AppError::FileError(format!(
+                    "Unsupported protobuf wire type {} in OSM PBF file",
+                    wire
+                )))
+            }
+        };
+        Ok((number, value))
+    }
+}
+
+impl<'a> Iterator for ProtoIter<'a> {
+    type Item = Result<(u32, Value<'a>), AppError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.data.is_empty() {
+            return None;
+        }
+        let field = self.next_field();
+        if field.is_err() {
+            self.failed = true;
+        }
+        Some(field)
+    }
+}
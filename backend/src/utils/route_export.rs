@@ -0,0 +1,262 @@
+// backend/src/utils/route_export.rs - serialise saved routes for download
+
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use std::fmt::Write;
+use crate::db::models::DbSavedRoute;
+use crate::error::AppError;
+use crate::matching::engine::haversine_distance;
+use super::fit::{self, COURSE_NOMINAL_SPEED_MPS};
+use super::gpx_parser::SourcePoint;
+
+/// Download formats for saved routes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Gpx,
+    GeoJson,
+    Kml,
+    Tcx,
+    Fit,
+    Csv,
+}
+
+impl ExportFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "gpx" => Some(ExportFormat::Gpx),
+            "geojson" | "json" => Some(ExportFormat::GeoJson),
+            "kml" => Some(ExportFormat::Kml),
+            "tcx" => Some(ExportFormat::Tcx),
+            "fit" => Some(ExportFormat::Fit),
+            "csv" => Some(ExportFormat::Csv),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Gpx => "application/gpx+xml",
+            ExportFormat::GeoJson => "application/geo+json",
+            ExportFormat::Kml => "application/vnd.google-earth.kml+xml",
+            ExportFormat::Tcx => "application/vnd.garmin.tcx+xml",
+            ExportFormat::Fit => "application/vnd.ant.fit",
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Gpx => "gpx",
+            ExportFormat::GeoJson => "geojson",
+            ExportFormat::Kml => "kml",
+            ExportFormat::Tcx => "tcx",
+            ExportFormat::Fit => "fit",
+            ExportFormat::Csv => "csv",
+        }
+    }
+}
+
+/// Saved route geometry with elevation and cumulative distance per point
+pub struct ExportRoute {
+    pub name: String,
+    pub points: Vec<SourcePoint>,
+    pub distances: Vec<f64>,
+}
+
+impl ExportRoute {
+    pub fn from_db(route: &DbSavedRoute) -> Result<Self, AppError> {
+        let geometry: serde_json::Value = serde_json::from_str(&route.geom_wkt)
+            .map_err(|_| AppError::InternalServerError(anyhow::anyhow!("Stored route geometry is invalid")))?;
+        let profile: Vec<f64> = serde_json::from_str(&route.elevation_profile_json).unwrap_or_default();
+
+        let coords = geometry.get("coordinates").and_then(|c| c.as_array()).cloned().unwrap_or_default();
+        let positions: Vec<(f64, f64)> = coords.iter()
+            .filter_map(|coord| {
+                let arr = coord.as_array()?;
+                Some((arr.first()?.as_f64()?, arr.get(1)?.as_f64()?))
+            })
+            .collect();
+
+        // A profile that doesn't line up with the geometry can't be attributed to points
+        let aligned = profile.len() == positions.len();
+        let points: Vec<SourcePoint> = positions.iter()
+            .enumerate()
+            .map(|(i, &(lon, lat))| SourcePoint {
+                lon,
+                lat,
+                elevation: if aligned { Some(profile[i]) } else { None },
+            })
+            .collect();
+
+        let mut distances = Vec::with_capacity(points.len());
+        let mut total = 0.0;
+        for (i, point) in points.iter().enumerate() {
+            if i > 0 {
+                total += haversine_distance(points[i - 1].lat, points[i - 1].lon, point.lat, point.lon);
+            }
+            distances.push(total);
+        }
+
+        Ok(Self {
+            name: route.name.clone(),
+            points,
+            distances,
+        })
+    }
+
+    pub fn total_distance(&self) -> f64 {
+        self.distances.last().copied().unwrap_or(0.0)
+    }
+
+    /// Time at a point when riding at the nominal course speed
+    fn time_at(&self, start: DateTime<Utc>, distance: f64) -> DateTime<Utc> {
+        start + Duration::seconds((distance / COURSE_NOMINAL_SPEED_MPS).round() as i64)
+    }
+}
+
+/// Render a saved route in the requested format
+pub fn export_route(route: &DbSavedRoute, format: ExportFormat) -> Result<Vec<u8>, AppError> {
+    if format == ExportFormat::Gpx {
+        return Ok(route.gpx_data.clone());
+    }
+
+    let export = ExportRoute::from_db(route)?;
+    let start = Utc::now();
+
+    Ok(match format {
+        ExportFormat::Gpx => unreachable!("GPX is served from the stored file"),
+        ExportFormat::GeoJson => to_geojson(&export).into_bytes(),
+        ExportFormat::Kml => to_kml(&export).into_bytes(),
+        ExportFormat::Tcx => to_tcx(&export, start).into_bytes(),
+        ExportFormat::Fit => fit::write_course(&export.name, &export.points, start),
+        ExportFormat::Csv => to_csv(&export).into_bytes(),
+    })
+}
+
+/// `attachment` disposition with an ASCII-safe file name
+pub fn content_disposition(name: &str, id: i64, format: ExportFormat) -> String {
+    let stem: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    let stem = stem.trim_matches('_');
+    let stem = if stem.is_empty() { format!("route-{}", id) } else { stem.to_string() };
+    format!("attachment; filename=\"{}.{}\"", stem, format.extension())
+}
+
+fn to_geojson(route: &ExportRoute) -> String {
+    let coordinates: Vec<serde_json::Value> = route.points.iter()
+        .map(|p| match p.elevation {
+            Some(elevation) => serde_json::json!([p.lon, p.lat, elevation]),
+            None => serde_json::json!([p.lon, p.lat]),
+        })
+        .collect();
+
+    serde_json::json!({
+        "type": "Feature",
+        "properties": {
+            "name": route.name,
+            "distance": route.total_distance(),
+        },
+        "geometry": {
+            "type": "LineString",
+            "coordinates": coordinates,
+        }
+    })
+    .to_string()
+}
+
+fn to_kml(route: &ExportRoute) -> String {
+    let has_elevation = route.points.iter().all(|p| p.elevation.is_some());
+    let coordinates: Vec<String> = route.points.iter()
+        .map(|p| match p.elevation {
+            Some(elevation) => format!("{},{},{}", p.lon, p.lat, elevation),
+            None => format!("{},{}", p.lon, p.lat),
+        })
+        .collect();
+
+    let mut kml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<kml xmlns=\"http://www.opengis.net/kml/2.2\">\n  <Document>\n");
+    let _ = writeln!(kml, "    <name>{}</name>", xml_escape(&route.name));
+    kml.push_str("    <Placemark>\n");
+    let _ = writeln!(kml, "      <name>{}</name>", xml_escape(&route.name));
+    kml.push_str("      <LineString>\n        <tessellate>1</tessellate>\n");
+    if has_elevation {
+        kml.push_str("        <altitudeMode>absolute</altitudeMode>\n");
+    }
+    let _ = writeln!(kml, "        <coordinates>{}</coordinates>", coordinates.join(" "));
+    kml.push_str("      </LineString>\n    </Placemark>\n  </Document>\n</kml>\n");
+    kml
+}
+
+fn to_tcx(route: &ExportRoute, start: DateTime<Utc>) -> String {
+    let timestamp = |distance: f64| {
+        route.time_at(start, distance).to_rfc3339_opts(SecondsFormat::Secs, true)
+    };
+    // Course names are limited to 15 characters by the schema
+    let name: String = route.name.chars().take(15).collect();
+    let total = route.total_distance();
+
+    let mut tcx = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<TrainingCenterDatabase xmlns=\"http://www.garmin.com/xmlschemas/TrainingCenterDatabase/v2\">\n  <Courses>\n    <Course>\n");
+    let _ = writeln!(tcx, "      <Name>{}</Name>", xml_escape(&name));
+
+    if let (Some(first), Some(last)) = (route.points.first(), route.points.last()) {
+        tcx.push_str("      <Lap>\n");
+        let _ = writeln!(tcx, "        <TotalTimeSeconds>{}</TotalTimeSeconds>", (total / COURSE_NOMINAL_SPEED_MPS).round());
+        let _ = writeln!(tcx, "        <DistanceMeters>{:.1}</DistanceMeters>", total);
+        let _ = writeln!(tcx, "        <BeginPosition><LatitudeDegrees>{}</LatitudeDegrees><LongitudeDegrees>{}</LongitudeDegrees></BeginPosition>", first.lat, first.lon);
+        let _ = writeln!(tcx, "        <EndPosition><LatitudeDegrees>{}</LatitudeDegrees><LongitudeDegrees>{}</LongitudeDegrees></EndPosition>", last.lat, last.lon);
+        tcx.push_str("        <Intensity>Active</Intensity>\n      </Lap>\n");
+    }
+
+    tcx.push_str("      <Track>\n");
+    for (point, distance) in route.points.iter().zip(&route.distances) {
+        tcx.push_str("        <Trackpoint>\n");
+        let _ = writeln!(tcx, "          <Time>{}</Time>", timestamp(*distance));
+        let _ = writeln!(tcx, "          <Position><LatitudeDegrees>{}</LatitudeDegrees><LongitudeDegrees>{}</LongitudeDegrees></Position>", point.lat, point.lon);
+        if let Some(elevation) = point.elevation {
+            let _ = writeln!(tcx, "          <AltitudeMeters>{}</AltitudeMeters>", elevation);
+        }
+        let _ = writeln!(tcx, "          <DistanceMeters>{:.1}</DistanceMeters>", distance);
+        tcx.push_str("        </Trackpoint>\n");
+    }
+    tcx.push_str("      </Track>\n    </Course>\n  </Courses>\n</TrainingCenterDatabase>\n");
+    tcx
+}
+
+/// One row per point: cumulative distance, position, elevation and the
+/// gradient of the segment leading to the point
+fn to_csv(route: &ExportRoute) -> String {
+    let mut csv = String::from("distance_m,latitude,longitude,elevation_m,gradient_pct\n");
+
+    for (i, (point, distance)) in route.points.iter().zip(&route.distances).enumerate() {
+        let gradient = match (i.checked_sub(1), point.elevation) {
+            (Some(prev), Some(elevation)) => {
+                let run = distance - route.distances[prev];
+                match route.points[prev].elevation {
+                    Some(prev_elevation) if run > 0.0 => {
+                        format!("{:.1}", (elevation - prev_elevation) / run * 100.0)
+                    }
+                    _ => String::new(),
+                }
+            }
+            (None, Some(_)) => "0.0".to_string(),
+            _ => String::new(),
+        };
+        let elevation = point.elevation.map(|e| format!("{:.1}", e)).unwrap_or_default();
+
+        let _ = writeln!(
+            csv,
+            "{:.1},{},{},{},{}",
+            distance, point.lat, point.lon, elevation, gradient
+        );
+    }
+
+    csv
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -57,6 +57,96 @@ async fn test_login_flow() {
     assert!(response.is_err() || response.unwrap().status().is_success());
 }
 
+/// Builders shared by the test modules below
+#[cfg(test)]
+mod fixtures {
+    use axum::Router;
+    use curvematch_backend::api;
+    use curvematch_backend::auth::oidc::OidcProviders;
+    use curvematch_backend::auth::password::PasswordPolicy;
+    use curvematch_backend::auth::session::RevocationList;
+    use curvematch_backend::auth::throttle::LoginThrottle;
+    use curvematch_backend::config::Config;
+    use curvematch_backend::db::models::DbSavedRoute;
+    use curvematch_backend::mail::OutboxMailer;
+    use curvematch_backend::matching::engine::calculate_distance;
+    use curvematch_backend::matching::spatial_index::{IndexSources, SharedSpatialIndex};
+    use curvematch_backend::state::AppState;
+    use geo::LineString;
+    use sqlx::sqlite::{SqlitePool, SqlitePoolOptions};
+    use std::sync::Arc;
+    
+    /// A saved route along `line` (lon, lat), its distance measured from it
+    pub fn db_route(id: i64, line: &LineString<f64>) -> DbSavedRoute {
+        let coordinates: Vec<[f64; 2]> = line.0.iter().map(|c| [c.x, c.y]).collect();
+        DbSavedRoute {
+            id,
+            user_id: 1,
+            name: format!("Route {}", id),
+            tag: "Cycling".to_string(),
+            saved_at: "2024-01-01 10:00:00".to_string(),
+            distance_m: calculate_distance(line),
+            elevation_gain_m: 0.0,
+            gain_per_km: 0.0,
+            curve_score: 0.5,
+            match_pct: 50.0,
+            geom_wkt: serde_json::json!({ "type": "LineString", "coordinates": coordinates }).to_string(),
+            elevation_profile_json: "[]".to_string(),
+            search_area_json: "{}".to_string(),
+            gpx_data: vec![],
+            curvature_per_km: None,
+            tight_bends: None,
+            medium_bends: None,
+            sweeping_bends: None,
+        }
+    }
+    
+    pub async fn test_pool() -> SqlitePool {
+        // One connection so every query sees the same in-memory database
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+        pool
+    }
+    
+    pub async fn test_app(name: &str) -> (Router, SqlitePool, Arc<OutboxMailer>) {
+        test_app_with_config(name, Config::default()).await
+    }
+    
+    pub async fn test_app_with_config(name: &str, config: Config) -> (Router, SqlitePool, Arc<OutboxMailer>) {
+        let (state, mailer) = test_state(name, config).await;
+        let pool = state.pool.clone();
+        (api::routes(state.clone()).with_state(state), pool, mailer)
+    }
+    
+    pub async fn test_state(name: &str, config: Config) -> (AppState, Arc<OutboxMailer>) {
+        let pool = test_pool().await;
+        
+        let outbox_dir = std::env::temp_dir().join(format!("curvematch-{}-{}", name, std::process::id()));
+        std::fs::remove_dir_all(&outbox_dir).ok();
+        let mailer = Arc::new(OutboxMailer::new(&outbox_dir).unwrap());
+        
+        let spatial_index = SharedSpatialIndex::load(&pool, IndexSources::default()).await.unwrap();
+        let config = Arc::new(config);
+        let state = AppState {
+            login_throttle: Arc::new(LoginThrottle::new(config.login.clone())),
+            oidc: Arc::new(OidcProviders::new(&config.oidc).unwrap()),
+            password_policy: Arc::new(PasswordPolicy::default()),
+            config,
+            pool: pool.clone(),
+            road_network: None,
+            dem: None,
+            spatial_index: Arc::new(spatial_index),
+            revoked_tokens: Arc::new(RevocationList::default()),
+            mailer: mailer.clone(),
+        };
+        (state, mailer)
+    }
+}
+
 #[cfg(test)]
 mod auth_tests {
     use curvematch_backend::auth::password::{hash_password, verify_password};
@@ -107,3 +197,2369 @@ mod gpx_tests {
         assert_eq!(parsed.geometry.0.len(), 2);
     }
 }
+
+#[cfg(test)]
+mod safety_tests {
+    use super::fixtures::{db_route, test_app_with_config};
+    use axum::body::Body;
+    use axum::http::{header, Request, StatusCode};
+    use curvematch_backend::auth::jwt::create_token;
+    use curvematch_backend::config::Config;
+    use curvematch_backend::matching::engine::{MatchingConfig, MatchingEngine};
+    use curvematch_backend::matching::road_class::{RoadClassProfile, RoadSafety, SafetyMode};
+    use curvematch_backend::matching::spatial_index::{IndexSources, RouteEntry, SpatialIndex};
+    use geo::LineString;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+    
+    #[test]
+    fn test_highway_classification() {
+        assert_eq!(RoadSafety::from_highway("footway"), RoadSafety::Trail);
+        assert_eq!(RoadSafety::from_highway("residential"), RoadSafety::Residential);
+        assert_eq!(RoadSafety::from_highway("primary"), RoadSafety::Arterial);
+        assert_eq!(SafetyMode::parse("Strict"), Some(SafetyMode::Strict));
+        assert_eq!(SafetyMode::parse("Reckless"), None);
+    }
+    
+    #[test]
+    fn test_unsafe_share_by_mode() {
+        let mut profile = RoadClassProfile::default();
+        profile.shares.insert("path".to_string(), 0.5);
+        profile.shares.insert("residential".to_string(), 0.3);
+        profile.shares.insert("secondary".to_string(), 0.2);
+        
+        assert!((profile.unsafe_share(SafetyMode::Strict) - 0.5).abs() < 1e-9);
+        assert!((profile.unsafe_share(SafetyMode::Moderate) - 0.2).abs() < 1e-9);
+        assert_eq!(profile.unsafe_share(SafetyMode::None), 0.0);
+    }
+    
+    #[test]
+    fn test_strict_mode_drops_unclassified_routes() {
+        let input: LineString<f64> = vec![(13.40, 52.52), (13.41, 52.53)].into();
+        let mut on_trails = RouteEntry::from_db(db_route(1, &input), &IndexSources::default()).unwrap();
+        let mut profile = RoadClassProfile::default();
+        profile.shares.insert("path".to_string(), 1.0);
+        on_trails.road_classes = Some(profile);
+        let unclassified = RouteEntry::from_db(db_route(2, &input), &IndexSources::default()).unwrap();
+        
+        let mut index = SpatialIndex::new();
+        index.insert(on_trails);
+        index.insert(unclassified);
+        let engine = MatchingEngine::from_index(Arc::new(index));
+        
+        let matched_ids = |safety_mode| {
+            let config = MatchingConfig {
+                distance_flexibility: 100.0,
+                elevation_importance: 0.0,
+                curvature_importance: 100.0,
+                min_match_percentage: 0.0,
+                safety_mode,
+                ..Default::default()
+            };
+            let mut ids: Vec<String> = engine
+                .find_matches_with_config(&input, &[], (13.3, 52.4, 13.5, 52.6), config)
+                .unwrap()
+                .into_iter()
+                .map(|m| m.id)
+                .collect();
+            ids.sort();
+            ids
+        };
+        
+        assert_eq!(matched_ids(SafetyMode::Strict), vec!["1"]);
+        assert_eq!(matched_ids(SafetyMode::Moderate), vec!["1", "2"]);
+        assert_eq!(matched_ids(SafetyMode::None), vec!["1", "2"]);
+    }
+    
+    #[tokio::test]
+    async fn test_strict_mode_needs_road_network() {
+        let config = Config::default();
+        let (token, _) = create_token(&config.auth, 1, "test-session").unwrap();
+        let (app, _, _) = test_app_with_config("strict-without-osm", config).await;
+        
+        let track = r#"{"type":"LineString","coordinates":[[13.40,52.52,34.0],[13.41,52.53,44.0]]}"#;
+        let matching = |safety_mode: &str| {
+            let body = format!(
+                "--x\r\nContent-Disposition: form-data; name=\"gpxFile\"; filename=\"ride.geojson\"\r\n\r\n{}\r\n\
+                 --x\r\nContent-Disposition: form-data; name=\"searchArea\"\r\n\r\n{}\r\n\
+                 --x\r\nContent-Disposition: form-data; name=\"safetyMode\"\r\n\r\n{}\r\n--x--\r\n",
+                track, r#"{"west":13.3,"south":52.4,"east":13.5,"north":52.6}"#, safety_mode
+            );
+            Request::builder()
+                .method("POST")
+                .uri("/match")
+                .header(header::CONTENT_TYPE, "multipart/form-data; boundary=x")
+                .header(header::COOKIE, format!("token={}", token))
+                .body(Body::from(body))
+                .unwrap()
+        };
+        
+        // Every route would be unclassified and dropped
+        let response = app.clone().oneshot(matching("Strict")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        
+        let response = app.oneshot(matching("Moderate")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
+
+#[cfg(test)]
+mod matching_config_tests {
+    use curvematch_backend::matching::engine::{MatchingConfig, ShapeMetric};
+    
+    #[test]
+    fn test_default_config_is_valid() {
+        assert!(MatchingConfig::default().validate().is_ok());
+        // Fréchet is opt-in, as it rejects routes ridden the other way round
+        assert_eq!(MatchingConfig::default().shape_metric, ShapeMetric::Hausdorff);
+    }
+    
+    #[test]
+    fn test_out_of_range_config_is_rejected() {
+        let config = MatchingConfig {
+            granularity_meters: 1.0,
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+        
+        let config = MatchingConfig {
+            max_results: 0,
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+        
+        let config = MatchingConfig {
+            shape_importance: f64::NAN,
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+    }
+}
+
+#[cfg(test)]
+mod elevation_window_tests {
+    use curvematch_backend::matching::algorithms::{elevation_similarity, within_elevation_window};
+    
+    #[test]
+    fn test_gain_window_rejects_distant_gains() {
+        assert!(within_elevation_window(300.0, 320.0, 10.0));
+        assert!(!within_elevation_window(300.0, 1200.0, 50.0));
+        // Flat routes get the absolute floor rather than a sub-metre window
+        assert!(within_elevation_window(5.0, 20.0, 10.0));
+    }
+    
+    #[test]
+    fn test_elevation_similarity_honours_flexibility() {
+        let low: Vec<f64> = (0..100).map(|i| i as f64 * 3.0).collect();
+        let high: Vec<f64> = (0..100).map(|i| i as f64 * 12.0).collect();
+        assert_eq!(elevation_similarity(&low, &high, 20.0), 0.0);
+        assert!(elevation_similarity(&low, &low, 20.0) > 0.9);
+    }
+}
+
+#[cfg(test)]
+mod spatial_index_tests {
+    use super::fixtures::db_route;
+    use curvematch_backend::db::models::DbSavedRoute;
+    use curvematch_backend::matching::spatial_index::{IndexSources, RouteEntry, SpatialIndex};
+    use geo::LineString;
+    use std::sync::Arc;
+    
+    /// A short route in Berlin, moved `offset` degrees east
+    fn named_route(id: i64, name: &str, offset: f64) -> DbSavedRoute {
+        let line = LineString::from(vec![(13.40 + offset, 52.52), (13.41 + offset, 52.53)]);
+        DbSavedRoute { name: name.to_string(), ..db_route(id, &line) }
+    }
+    
+    #[test]
+    fn test_incremental_updates() {
+        let mut index = SpatialIndex::new();
+        index.insert(RouteEntry::from_db(named_route(1, "Park Loop", 0.0), &IndexSources::default()).unwrap());
+        index.insert(RouteEntry::from_db(named_route(2, "Far Away", 5.0), &IndexSources::default()).unwrap());
+        assert_eq!(index.len(), 2);
+        
+        let berlin = (13.3, 52.4, 13.5, 52.6);
+        assert_eq!(index.query_bounds(berlin).len(), 1);
+        
+        assert!(index.rename("1", "Renamed Loop"));
+        assert_eq!(index.query_bounds(berlin)[0].name, "Renamed Loop");
+        
+        assert!(index.remove("1").is_some());
+        assert!(index.query_bounds(berlin).is_empty());
+        assert_eq!(index.len(), 1);
+    }
+    
+    #[test]
+    fn test_copies_share_routes() {
+        let mut index = SpatialIndex::new();
+        index.insert(RouteEntry::from_db(named_route(1, "Park Loop", 0.0), &IndexSources::default()).unwrap());
+        index.insert(RouteEntry::from_db(named_route(2, "Far Away", 5.0), &IndexSources::default()).unwrap());
+        let snapshot = index.clone();
+        
+        let berlin = (13.3, 52.4, 13.5, 52.6);
+        assert!(index.rename("1", "Renamed Loop"));
+        assert_eq!(snapshot.query_bounds(berlin)[0].name, "Park Loop");
+        assert_eq!(index.query_bounds(berlin)[0].name, "Renamed Loop");
+        
+        // Untouched routes are not copied
+        let far = (18.3, 52.4, 18.5, 52.6);
+        assert!(Arc::ptr_eq(&snapshot.query_bounds(far)[0], &index.query_bounds(far)[0]));
+    }
+}
+
+#[cfg(test)]
+mod frechet_tests {
+    use super::fixtures::db_route;
+    use curvematch_backend::matching::algorithms::{
+        discrete_frechet_distance, discrete_frechet_distance_bounded, frechet_distance, hausdorff_distance,
+    };
+    use curvematch_backend::matching::engine::{MatchingConfig, MatchingEngine, ShapeMetric};
+    use curvematch_backend::matching::projection::project_together;
+    use curvematch_backend::matching::spatial_index::{IndexSources, RouteEntry, SpatialIndex};
+    use geo::LineString;
+    use std::sync::Arc;
+    
+    /// An east-west line then a dog-leg north, roughly 2.5 km long
+    fn dog_leg() -> Vec<(f64, f64)> {
+        vec![(13.40, 52.52), (13.41, 52.52), (13.42, 52.52), (13.43, 52.52), (13.43, 52.53)]
+    }
+    
+    fn reversed(points: &[(f64, f64)]) -> Vec<(f64, f64)> {
+        points.iter().rev().copied().collect()
+    }
+    
+    #[test]
+    fn test_direction_of_travel_matters() {
+        let forward = LineString::from(dog_leg());
+        let backward = LineString::from(reversed(&dog_leg()));
+        let [projected_forward, projected_backward] = project_together([&forward, &backward]).unwrap();
+        
+        assert_eq!(discrete_frechet_distance(&projected_forward, &projected_forward), 0.0);
+        // Hausdorff can't tell the two apart; Fréchet has to pair the ends
+        assert_eq!(hausdorff_distance(&forward, &backward), hausdorff_distance(&forward, &forward));
+        let distance = discrete_frechet_distance(&projected_forward, &projected_backward);
+        assert!(distance > 2000.0, "distance {}", distance);
+        assert!(frechet_distance(&forward, &backward) < 0.5);
+        assert_eq!(frechet_distance(&forward, &forward), 1.0);
+    }
+    
+    #[test]
+    fn test_bounded_variant_agrees_and_abandons() {
+        let zigzag: Vec<(f64, f64)> = (0..40)
+            .map(|i| (13.40 + i as f64 * 0.001, 52.52 + if i % 2 == 0 { 0.0 } else { 0.002 }))
+            .collect();
+        let [line1, line2] = project_together([&LineString::from(zigzag), &LineString::from(dog_leg())]).unwrap();
+        
+        let full = discrete_frechet_distance(&line1, &line2);
+        let bounded = discrete_frechet_distance_bounded(&line1, &line2, None).unwrap();
+        assert!((full - bounded).abs() < 1e-9);
+        assert_eq!(discrete_frechet_distance_bounded(&line2, &line1, None), Some(bounded));
+        
+        assert_eq!(discrete_frechet_distance_bounded(&line1, &line2, Some(full + 1.0)), Some(bounded));
+        assert_eq!(discrete_frechet_distance_bounded(&line1, &line2, Some(full - 1.0)), None);
+        assert_eq!(discrete_frechet_distance_bounded(&line1, &LineString::new(vec![]), None), None);
+    }
+    
+    #[test]
+    fn test_shape_metric_ranks_reversed_route_lower() {
+        let mut index = SpatialIndex::new();
+        for route in [db_route(1, &LineString::from(dog_leg())), db_route(2, &LineString::from(reversed(&dog_leg())))] {
+            index.insert(RouteEntry::from_db(route, &IndexSources::default()).unwrap());
+        }
+        let engine = MatchingEngine::from_index(Arc::new(index));
+        let input = LineString::from(dog_leg());
+        let bounds = (13.3, 52.4, 13.5, 52.6);
+        let config = |shape_metric, min_match_percentage| MatchingConfig {
+            shape_importance: 100.0,
+            elevation_importance: 0.0,
+            shape_metric,
+            min_match_percentage,
+            ..Default::default()
+        };
+        
+        let matches = engine.find_matches_with_config(&input, &[], bounds, config(ShapeMetric::Frechet, 0.0)).unwrap();
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].id, "1");
+        assert!(matches[0].match_percentage > matches[1].match_percentage);
+        
+        // The reversed route is abandoned early once it can't reach the threshold
+        let matches = engine.find_matches_with_config(&input, &[], bounds, config(ShapeMetric::Frechet, 90.0)).unwrap();
+        assert_eq!(matches.iter().map(|m| m.id.as_str()).collect::<Vec<_>>(), vec!["1"]);
+        
+        let matches = engine.find_matches_with_config(&input, &[], bounds, config(ShapeMetric::Hausdorff, 0.0)).unwrap();
+        assert_eq!(matches[0].match_percentage, matches[1].match_percentage);
+    }
+}
+
+#[cfg(test)]
+mod projection_tests {
+    use curvematch_backend::matching::algorithms::{discrete_hausdorff_distance, hausdorff_distance};
+    use curvematch_backend::matching::engine::haversine_distance;
+    use curvematch_backend::matching::projection::{planar_distance, project_together, LocalProjection};
+    use geo::{LineString, Point};
+    
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!((actual - expected).abs() <= tolerance, "expected {} ± {}, got {}", expected, tolerance, actual);
+    }
+    
+    #[test]
+    fn test_projected_distances_are_metres() {
+        let projection = LocalProjection::new(13.4, 52.5);
+        assert_eq!(projection.project(Point::new(13.4, 52.5)), Point::new(0.0, 0.0));
+        
+        // A degree of latitude is 111.195 km anywhere; a degree of longitude
+        // shrinks with the cosine of the latitude
+        let north = projection.project(Point::new(13.4, 53.5));
+        assert_close(north.x(), 0.0, 1e-6);
+        assert_close(north.y(), 111_194.9, 0.1);
+        let east = projection.project(Point::new(13.41, 52.5));
+        assert_close(east.x(), 676.9, 0.1);
+        assert!(east.y() > 0.0 && east.y() < 0.1, "y {}", east.y());
+        
+        // Away from the centre, projected distances stay close to great-circle ones
+        let (a, b) = ((13.45, 52.55), (13.52, 52.47));
+        let planar = planar_distance(&projection.project(a.into()), &projection.project(b.into()));
+        assert_close(planar, haversine_distance(a.1, a.0, b.1, b.0), 0.05);
+    }
+    
+    #[test]
+    fn test_shape_distances_do_not_depend_on_latitude() {
+        // Two parallel lines 0.001 degrees of latitude (111.2 m) apart
+        for lat in [0.0, 45.0, 60.0] {
+            let line1 = LineString::from(vec![(10.0, lat), (10.01, lat), (10.02, lat)]);
+            let line2 = LineString::from(vec![(10.0, lat + 0.001), (10.01, lat + 0.001), (10.02, lat + 0.001)]);
+            let [projected1, projected2] = project_together([&line1, &line2]).unwrap();
+            
+            assert_close(discrete_hausdorff_distance(&projected1, &projected2), 111.2, 0.1);
+            assert_close(hausdorff_distance(&line1, &line2), 1.0 / 1.1112, 1e-4);
+        }
+        
+        assert!(project_together([&LineString::new(vec![]), &LineString::new(vec![])]).is_none());
+    }
+}
+
+#[cfg(test)]
+mod procrustes_tests {
+    use super::fixtures::db_route;
+    use curvematch_backend::matching::alignment::{procrustes_align, resample_by_arc_length};
+    use curvematch_backend::matching::engine::{
+        haversine_distance, MatchingConfig, MatchingEngine, ShapeMetric,
+    };
+    use curvematch_backend::matching::projection::LocalProjection;
+    use curvematch_backend::matching::spatial_index::{IndexSources, RouteEntry, SpatialIndex};
+    use geo::{LineString, Point};
+    use std::sync::Arc;
+    
+    const BERLIN: (f64, f64) = (13.40, 52.50);
+    const MUNICH: (f64, f64) = (11.58, 48.14);
+    
+    /// A route shape in metres, rotated and scaled, then placed at `centre`
+    fn place(centre: (f64, f64), rotation_deg: f64, scale: f64) -> LineString<f64> {
+        let shape = [(0.0, 0.0), (1000.0, 0.0), (1000.0, 800.0), (1600.0, 1400.0), (2500.0, 1400.0)];
+        let (sin, cos) = rotation_deg.to_radians().sin_cos();
+        let projection = LocalProjection::new(centre.0, centre.1);
+        shape.iter()
+            .map(|&(x, y)| projection.unproject(Point::new(scale * (cos * x - sin * y), scale * (sin * x + cos * y))))
+            .collect()
+    }
+    
+    fn own_frame(line: &LineString<f64>) -> LineString<f64> {
+        LocalProjection::centred_on(&[line]).unwrap().project_line(line)
+    }
+    
+    #[test]
+    fn test_resampling_is_even_along_the_line() {
+        let line = LineString::from(vec![(0.0, 0.0), (100.0, 0.0), (100.0, 100.0)]);
+        let samples = resample_by_arc_length(&line, 5).unwrap();
+        let expected = [(0.0, 0.0), (50.0, 0.0), (100.0, 0.0), (100.0, 50.0), (100.0, 100.0)];
+        for (sample, (x, y)) in samples.iter().zip(expected) {
+            assert!((sample.x() - x).abs() < 1e-9 && (sample.y() - y).abs() < 1e-9, "{:?}", sample);
+        }
+        
+        assert!(resample_by_arc_length(&LineString::from(vec![(5.0, 5.0), (5.0, 5.0)]), 5).is_none());
+    }
+    
+    #[test]
+    fn test_alignment_ignores_position_and_rotation() {
+        let input = place(BERLIN, 0.0, 1.0);
+        let candidate = place(MUNICH, 40.0, 1.0);
+        
+        let alignment = procrustes_align(&own_frame(&input), &own_frame(&candidate), false).unwrap();
+        assert!(alignment.rms_distance < 1.0, "rms {}", alignment.rms_distance);
+        assert!((alignment.transform.rotation.to_degrees() - 40.0).abs() < 0.1);
+        assert_eq!(alignment.transform.scale, 1.0);
+        
+        // The fitted transform carries the input onto the candidate
+        let frame = LocalProjection::centred_on(&[&candidate]).unwrap();
+        let aligned = frame.unproject_line(&alignment.transform.apply_line(&own_frame(&input)));
+        for (a, c) in aligned.0.iter().zip(&candidate.0) {
+            assert!(haversine_distance(a.y, a.x, c.y, c.x) < 1.0);
+        }
+        
+        // Point pairs follow the direction of travel
+        let reversed: LineString<f64> = candidate.0.iter().rev().copied().collect();
+        let alignment = procrustes_align(&own_frame(&input), &own_frame(&reversed), false).unwrap();
+        assert!(alignment.rms_distance > 100.0, "rms {}", alignment.rms_distance);
+    }
+    
+    #[test]
+    fn test_scale_is_fitted_only_when_allowed() {
+        let input = own_frame(&place(BERLIN, 0.0, 1.0));
+        let larger = own_frame(&place(MUNICH, -75.0, 1.5));
+        
+        let fixed = procrustes_align(&input, &larger, false).unwrap();
+        assert_eq!(fixed.transform.scale, 1.0);
+        assert!(fixed.rms_distance > 100.0, "rms {}", fixed.rms_distance);
+        
+        let scaled = procrustes_align(&input, &larger, true).unwrap();
+        assert!((scaled.transform.scale - 1.5).abs() < 1e-4, "scale {}", scaled.transform.scale);
+        assert!(scaled.rms_distance < 1.0, "rms {}", scaled.rms_distance);
+    }
+    
+    #[test]
+    fn test_procrustes_metric_finds_the_shape_in_another_city() {
+        let same_shape = place(MUNICH, 120.0, 1.0);
+        let straight = LineString::from(vec![(11.60, 48.15), (11.6515, 48.15)]);
+        let mut index = SpatialIndex::new();
+        for (id, line) in [(1, &same_shape), (2, &straight)] {
+            index.insert(RouteEntry::from_db(db_route(id, line), &IndexSources::default()).unwrap());
+        }
+        let engine = MatchingEngine::from_index(Arc::new(index));
+        let input = place(BERLIN, 0.0, 1.0);
+        let bounds = (11.4, 48.0, 11.8, 48.3);
+        let config = |shape_metric| MatchingConfig {
+            shape_importance: 100.0,
+            elevation_importance: 0.0,
+            shape_metric,
+            min_match_percentage: 0.0,
+            ..Default::default()
+        };
+        
+        let matches = engine.find_matches_with_config(&input, &[], bounds, config(ShapeMetric::Procrustes)).unwrap();
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].id, "1");
+        assert!(matches[0].match_percentage > 99.0, "match {}", matches[0].match_percentage);
+        assert!(matches[1].match_percentage < 80.0, "match {}", matches[1].match_percentage);
+        let alignment = matches[0].shape_alignment.unwrap();
+        assert!((alignment.transform.rotation.to_degrees() - 120.0).abs() < 0.1);
+        assert_eq!(matches[0].aligned_input.as_ref().unwrap().0.len(), input.0.len());
+        
+        // Absolute metrics see only the 600 km between the cities
+        let matches = engine.find_matches_with_config(&input, &[], bounds, config(ShapeMetric::Hausdorff)).unwrap();
+        assert!(matches.iter().all(|m| m.match_percentage < 1.0 && m.shape_alignment.is_none()));
+    }
+}
+
+#[cfg(test)]
+mod turn_tests {
+    use curvematch_backend::matching::algorithms::turn_sequence_similarity;
+    use curvematch_backend::matching::projection::LocalProjection;
+    use curvematch_backend::matching::turns::{TurnDirection, TurnSequence};
+    use geo::LineString;
+    
+    /// 400 m straights joined by hairpins of radius 25 m; +1 turns left, -1 right
+    fn switchbacks(hairpins: &[f64]) -> LineString<f64> {
+        let mut heading = std::f64::consts::FRAC_PI_2;
+        let mut points = vec![(0.0, 0.0)];
+        let advance = |points: &mut Vec<(f64, f64)>, length: f64, heading: f64| {
+            let &(x, y) = points.last().unwrap();
+            points.push((x + length * heading.cos(), y + length * heading.sin()));
+        };
+        for &side in hairpins {
+            for _ in 0..40 {
+                advance(&mut points, 10.0, heading);
+            }
+            let step = side * 10f64.to_radians();
+            for _ in 0..18 {
+                advance(&mut points, 25.0 * step.abs(), heading + step / 2.0);
+                heading += step;
+            }
+        }
+        for _ in 0..40 {
+            advance(&mut points, 10.0, heading);
+        }
+        LineString::from(points)
+    }
+    
+    fn in_berlin(line: &LineString<f64>) -> LineString<f64> {
+        let projection = LocalProjection::new(13.40, 52.50);
+        line.points().map(|point| projection.unproject(point)).collect()
+    }
+    
+    #[test]
+    fn test_turns_are_signed_and_stamped() {
+        let sequence = TurnSequence::from_projected(&switchbacks(&[1.0, -1.0, 1.0]));
+        let directions: Vec<TurnDirection> = sequence.turns.iter().map(|turn| turn.direction()).collect();
+        assert_eq!(directions, vec![TurnDirection::Left, TurnDirection::Right, TurnDirection::Left]);
+        
+        // Each hairpin is 78.5 m of arc after 400 m of straight
+        for (i, turn) in sequence.turns.iter().enumerate() {
+            assert!((turn.angle.abs() - 180.0).abs() < 10.0, "angle {}", turn.angle);
+            let middle = 400.0 + 39.3 + i as f64 * 478.5;
+            assert!((turn.position - middle).abs() < 25.0, "turn {} at {}", i, turn.position);
+        }
+        assert!((sequence.length - 1835.6).abs() < 1.0, "length {}", sequence.length);
+    }
+    
+    #[test]
+    fn test_gps_jitter_is_not_a_turn() {
+        // 3 m of side-to-side noise every 5 m, along a straight and round a corner
+        let jitter = |i: usize| [3.0, -3.0][i % 2];
+        let straight: LineString<f64> = (0..400).map(|i| (jitter(i), i as f64 * 5.0)).collect();
+        assert_eq!(TurnSequence::from_projected(&straight).turns, vec![]);
+        
+        let corner: LineString<f64> = (0..200).map(|i| (jitter(i), i as f64 * 5.0))
+            .chain((1..200).map(|i| (i as f64 * 5.0, 1000.0 + jitter(i))))
+            .collect();
+        let turns = TurnSequence::from_projected(&corner).turns;
+        assert_eq!(turns.len(), 1, "{:?}", turns);
+        assert_eq!(turns[0].direction(), TurnDirection::Right);
+        assert!((turns[0].angle + 90.0).abs() < 10.0, "angle {}", turns[0].angle);
+    }
+    
+    #[test]
+    fn test_turn_order_outranks_turn_count() {
+        let input = in_berlin(&switchbacks(&[1.0, 1.0, -1.0, 1.0]));
+        let same_order = in_berlin(&switchbacks(&[1.0, 1.0, -1.0, 1.0]));
+        let other_order = in_berlin(&switchbacks(&[-1.0, 1.0, 1.0, -1.0]));
+        let fewer = in_berlin(&switchbacks(&[1.0, 1.0, -1.0]));
+        
+        assert!(turn_sequence_similarity(&input, &same_order) > 0.95);
+        let reordered = turn_sequence_similarity(&input, &other_order);
+        let missing_one = turn_sequence_similarity(&input, &fewer);
+        // Same number of bends but in another order scores below a route
+        // that shares the pattern and misses one hairpin
+        assert!(reordered < missing_one, "reordered {} vs missing one {}", reordered, missing_one);
+        assert!(reordered < 0.7, "reordered {}", reordered);
+        
+        assert_eq!(turn_sequence_similarity(&in_berlin(&switchbacks(&[])), &in_berlin(&switchbacks(&[]))), 1.0);
+        assert_eq!(TurnSequence::default().similarity(&TurnSequence::from_projected(&switchbacks(&[1.0]))), 0.0);
+    }
+}
+
+#[cfg(test)]
+mod twistiness_tests {
+    use super::fixtures::{db_route, test_pool};
+    use curvematch_backend::db::queries::routes::{
+        get_route_by_id, get_routes_without_twistiness, save_route, set_route_twistiness, NewRoute,
+    };
+    use curvematch_backend::db::queries::users::create_user;
+    use curvematch_backend::matching::engine::{MatchingConfig, MatchingEngine};
+    use curvematch_backend::matching::projection::LocalProjection;
+    use curvematch_backend::matching::spatial_index::{IndexSources, RouteEntry, SpatialIndex};
+    use curvematch_backend::matching::twistiness::Twistiness;
+    use curvematch_backend::models::route::SavedRoute;
+    use geo::{LineString, Point};
+    use std::sync::Arc;
+    
+    /// 200 m straights each followed by a bend of `(radius m, angle deg)`,
+    /// with vertices roughly `spacing` metres apart
+    fn road(bends: &[(f64, f64)], spacing: f64) -> LineString<f64> {
+        let mut heading = 0.0_f64;
+        let mut points = vec![Point::new(0.0, 0.0)];
+        let advance = |points: &mut Vec<Point<f64>>, length: f64, heading: f64| {
+            let last = points[points.len() - 1];
+            points.push(Point::new(last.x() + length * heading.cos(), last.y() + length * heading.sin()));
+        };
+        for &(radius, angle) in bends {
+            let steps = (200.0 / spacing).ceil();
+            for _ in 0..steps as usize {
+                advance(&mut points, 200.0 / steps, heading);
+            }
+            let sweep = angle.to_radians();
+            let steps = (radius * sweep.abs() / spacing).ceil().max(1.0);
+            for _ in 0..steps as usize {
+                let step = sweep / steps;
+                advance(&mut points, 2.0 * radius * (step.abs() / 2.0).sin(), heading + step / 2.0);
+                heading += step;
+            }
+        }
+        advance(&mut points, 200.0, heading);
+        points.into_iter().collect()
+    }
+    
+    fn in_alps(line: &LineString<f64>) -> LineString<f64> {
+        let projection = LocalProjection::new(10.45, 46.53);
+        line.points().map(|point| projection.unproject(point)).collect()
+    }
+    
+    #[test]
+    fn test_twistiness_does_not_depend_on_sampling() {
+        let bends = [(100.0, 90.0), (100.0, -90.0), (100.0, 90.0), (100.0, -90.0)];
+        let dense = Twistiness::from_projected(&road(&bends, 5.0));
+        let sparse = Twistiness::from_projected(&road(&bends, 40.0));
+        
+        // 360 degrees of bends over 1.63 km of road
+        assert!((dense.curvature_per_km - 221.0).abs() < 15.0, "{:?}", dense);
+        assert!((dense.curvature_per_km - sparse.curvature_per_km).abs() < 0.1 * dense.curvature_per_km);
+        assert_eq!((dense.medium_bends, sparse.medium_bends), (4, 4));
+        
+        let straight = Twistiness::from_projected(&road(&[], 5.0));
+        assert_eq!(straight, Twistiness::default());
+        assert_eq!(straight.curve_score(), 0.0);
+        assert!(dense.curve_score() > 0.3 && dense.curve_score() < 0.5);
+    }
+    
+    #[test]
+    fn test_bends_are_classed_by_radius() {
+        let twistiness = Twistiness::from_projected(&road(&[(20.0, 160.0), (120.0, -90.0), (500.0, 60.0)], 10.0));
+        assert_eq!(
+            (twistiness.tight_bends, twistiness.medium_bends, twistiness.sweeping_bends),
+            (1, 1, 1),
+            "{:?}",
+            twistiness
+        );
+    }
+    
+    #[test]
+    fn test_wide_bends_are_counted() {
+        // 90 degrees over 1.26 km of road: at most 2.9 degrees per 25 m sample
+        for spacing in [5.0, 25.0, 40.0] {
+            let twistiness = Twistiness::from_projected(&road(&[(800.0, 90.0)], spacing));
+            assert_eq!(
+                (twistiness.tight_bends, twistiness.medium_bends, twistiness.sweeping_bends),
+                (0, 0, 1),
+                "{:?}",
+                twistiness
+            );
+            // Over 1.66 km in all, with the straights
+            assert!((twistiness.curvature_per_km - 54.3).abs() < 5.0, "{:?}", twistiness);
+        }
+    }
+    
+    #[test]
+    fn test_twistiness_filters_and_ranks_matches() {
+        let twisty = in_alps(&road(&[(30.0, 150.0), (30.0, -150.0), (30.0, 150.0), (30.0, -150.0)], 10.0));
+        let gentle = in_alps(&road(&[(400.0, 30.0), (400.0, -30.0)], 10.0));
+        let mut index = SpatialIndex::new();
+        for (id, line) in [(1, &twisty), (2, &gentle)] {
+            index.insert(RouteEntry::from_db(db_route(id, line), &IndexSources::default()).unwrap());
+        }
+        let engine = MatchingEngine::from_index(Arc::new(index));
+        let bounds = (10.3, 46.4, 10.6, 46.7);
+        let config = MatchingConfig {
+            distance_flexibility: 100.0,
+            elevation_importance: 0.0,
+            curvature_importance: 100.0,
+            min_match_percentage: 0.0,
+            ..Default::default()
+        };
+        
+        let matches = engine.find_matches_with_config(&twisty, &[], bounds, config.clone()).unwrap();
+        let ids: Vec<&str> = matches.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, vec!["1", "2"]);
+        assert!(matches[0].match_percentage > 99.0);
+        assert!(matches[0].curve_score > matches[1].curve_score);
+        assert!(matches[0].twistiness.tight_bends >= 4, "{:?}", matches[0].twistiness);
+        
+        let twisty_curvature = matches[0].twistiness.curvature_per_km;
+        let filtered = MatchingConfig { max_curvature_per_km: Some(twisty_curvature / 2.0), ..config.clone() };
+        let matches = engine.find_matches_with_config(&twisty, &[], bounds, filtered).unwrap();
+        assert_eq!(matches.iter().map(|m| m.id.as_str()).collect::<Vec<_>>(), vec!["2"]);
+        
+        let inverted = MatchingConfig { min_curvature_per_km: Some(10.0), max_curvature_per_km: Some(5.0), ..config };
+        assert!(inverted.validate().is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_twistiness_is_stored_with_routes() {
+        let pool = test_pool().await;
+        let user = create_user(&pool, "rider@example.com", "rider", "salt", "hash").await.unwrap();
+        let line = in_alps(&road(&[(30.0, 150.0), (30.0, -150.0)], 10.0));
+        let geometry = db_route(0, &line).geom_wkt;
+        let twistiness = Twistiness::of_route(&line);
+        let new_route = |twistiness| NewRoute {
+            user_id: user.id,
+            name: "Pass",
+            tag: "Cycling",
+            distance_m: 1000.0,
+            elevation_gain_m: 10.0,
+            gain_per_km: 10.0,
+            curve_score: 0.5,
+            match_pct: 50.0,
+            geom_wkt: &geometry,
+            elevation_profile_json: "[]",
+            search_area_json: "{}",
+            gpx_data: b"<gpx/>",
+            twistiness,
+        };
+        let measured = save_route(&pool, new_route(Some(&twistiness))).await.unwrap();
+        assert_eq!(Twistiness::from_db(&measured), Some(twistiness));
+        let route = save_route(&pool, new_route(None)).await.unwrap();
+        
+        // Routes saved without it are picked up for backfilling
+        assert_eq!(Twistiness::from_db(&route), None);
+        let unmeasured = get_routes_without_twistiness(&pool).await.unwrap();
+        assert_eq!(unmeasured.iter().map(|r| r.id).collect::<Vec<_>>(), vec![route.id]);
+        
+        set_route_twistiness(&pool, route.id, &twistiness).await.unwrap();
+        assert!(get_routes_without_twistiness(&pool).await.unwrap().is_empty());
+        
+        let stored = get_route_by_id(&pool, route.id, user.id).await.unwrap().unwrap();
+        assert_eq!(Twistiness::from_db(&stored), Some(twistiness));
+        assert_eq!(stored.curve_score, twistiness.curve_score());
+        
+        let json = serde_json::to_value(SavedRoute::from(stored)).unwrap();
+        assert_eq!(json["twistiness"]["tightBends"], 2);
+        assert!(json["twistiness"]["curvaturePerKm"].as_f64().unwrap() > 0.0);
+    }
+}
+
+#[cfg(test)]
+mod dem_tests {
+    use curvematch_backend::utils::dem::{hgt_file_name, DemProvider};
+    use curvematch_backend::utils::elevation::interpolate_elevation_profile;
+    use geo::LineString;
+    use std::sync::Arc;
+    use std::time::Duration;
+    
+    #[test]
+    fn test_hgt_tile_names() {
+        assert_eq!(hgt_file_name(52, 13), "N52E013.hgt");
+        assert_eq!(hgt_file_name(-1, -1), "S01W001.hgt");
+    }
+    
+    #[test]
+    fn test_bilinear_lookup_from_hgt_tile() {
+        let dir = std::env::temp_dir().join(format!("curvematch-dem-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        
+        // 3x3 tile, rows north to south, 0.5 degree spacing
+        let values: [i16; 9] = [0, 10, 20, 30, 40, 50, 60, 70, 80];
+        let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_be_bytes()).collect();
+        std::fs::write(dir.join("N10E020.hgt"), bytes).unwrap();
+        
+        let dem = DemProvider::open(&dir).unwrap();
+        assert_eq!(dem.elevation_at(20.0, 11.0), Some(0.0));
+        assert_eq!(dem.elevation_at(21.0, 10.0), Some(80.0));
+        assert!((dem.elevation_at(20.25, 10.75).unwrap() - 20.0).abs() < 1e-9);
+        assert_eq!(dem.elevation_at(30.0, 10.5), None);
+        
+        let line = LineString::from(vec![(20.0, 11.0), (20.5, 10.5)]);
+        assert_eq!(interpolate_elevation_profile(&line, &dem), Some(vec![0.0, 40.0]));
+        
+        std::fs::remove_dir_all(&dir).ok();
+    }
+    
+    #[tokio::test]
+    async fn test_missing_tiles_are_looked_for_again() {
+        let dir = std::env::temp_dir().join(format!("curvematch-dem-missing-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        
+        let remembered = Arc::new(DemProvider::open(&dir).unwrap());
+        let expiring = Arc::new(DemProvider::open(&dir).unwrap().with_missing_tile_ttl(Duration::ZERO));
+        let line = LineString::from(vec![(20.25, 10.75), (20.5, 10.5)]);
+        remembered.preload(&line).await;
+        expiring.preload(&line).await;
+        assert_eq!(remembered.elevation_at(20.5, 10.5), None);
+        assert_eq!(expiring.elevation_at(20.5, 10.5), None);
+        
+        // The tile is added while the server is running
+        let values: [i16; 9] = [0, 10, 20, 30, 40, 50, 60, 70, 80];
+        let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_be_bytes()).collect();
+        std::fs::write(dir.join("N10E020.hgt"), bytes).unwrap();
+        
+        assert_eq!(remembered.elevation_at(20.5, 10.5), None);
+        expiring.preload(&line).await;
+        assert_eq!(expiring.elevation_at(20.5, 10.5), Some(40.0));
+        
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
+
+#[cfg(test)]
+mod elevation_correction_tests {
+    use curvematch_backend::utils::elevation::{correct_elevation_profile, ElevationCorrection};
+    
+    #[test]
+    fn test_dem_and_blended_correction() {
+        // Recorded track reads 20m high and is missing DEM coverage at the end
+        let recorded = vec![120.0, 131.0, 140.0, 150.0];
+        let dem = vec![Some(100.0), Some(110.0), Some(120.0), None];
+        
+        let raw = correct_elevation_profile(&recorded, &dem, ElevationCorrection::Raw);
+        assert_eq!(raw, recorded);
+        
+        let corrected = correct_elevation_profile(&recorded, &dem, ElevationCorrection::Dem);
+        assert_eq!(corrected, vec![100.0, 110.0, 120.0, 130.0]);
+        
+        let blended = correct_elevation_profile(&recorded, &dem, ElevationCorrection::Blended);
+        assert!((blended[1] - 110.5).abs() < 1e-9);
+        assert!((blended[3] - 130.0).abs() < 1e-9);
+    }
+}
+
+#[cfg(test)]
+mod elevation_gap_tests {
+    use curvematch_backend::utils::gpx_parser::{parse_gpx, ElevationGapFill};
+    
+    const GPX_WITH_GAPS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1">
+  <trk><name>Gaps</name><trkseg>
+    <trkpt lat="52.0000" lon="13.0000"><ele>100</ele></trkpt>
+    <trkpt lat="52.0010" lon="13.0000"></trkpt>
+    <trkpt lat="52.0030" lon="13.0000"><ele>130</ele></trkpt>
+    <trkpt lat="52.0040" lon="13.0000"></trkpt>
+  </trkseg></trk>
+</gpx>"#;
+    
+    #[test]
+    fn test_missing_elevations_stay_aligned() {
+        let parsed = parse_gpx(GPX_WITH_GAPS).unwrap();
+        
+        assert_eq!(parsed.elevations, vec![Some(100.0), None, Some(130.0), None]);
+        assert_eq!(parsed.elevation_profile.len(), parsed.geometry.0.len());
+        assert_eq!(parsed.filled_elevations, 2);
+        // One third of the way between the known points, by distance
+        assert!((parsed.elevation_profile[1] - 110.0).abs() < 0.1);
+        // Trailing gap holds the last known value
+        assert_eq!(parsed.elevation_profile[3], 130.0);
+    }
+    
+    #[test]
+    fn test_reject_strategy() {
+        let mut parsed = parse_gpx(GPX_WITH_GAPS).unwrap();
+        assert!(parsed.fill_elevation_gaps(ElevationGapFill::Reject, None).is_err());
+        assert!(parsed.fill_elevation_gaps(ElevationGapFill::Dem, None).is_err());
+        assert_eq!(ElevationGapFill::parse("DEM"), Some(ElevationGapFill::Dem));
+    }
+}
+
+#[cfg(test)]
+mod gpx_source_tests {
+    use curvematch_backend::utils::gpx_parser::{parse_gpx, parse_gpx_selection, GpxSelection};
+    
+    const MULTI_SOURCE_GPX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1">
+  <wpt lat="48.0000" lon="11.0000"/>
+  <wpt lat="48.0010" lon="11.0010"/>
+  <rte><name>Planned</name>
+    <rtept lat="47.0000" lon="10.0000"/>
+    <rtept lat="47.0010" lon="10.0000"/>
+  </rte>
+  <trk><name>Paused</name>
+    <trkseg>
+      <trkpt lat="52.0000" lon="13.0000"><ele>100</ele></trkpt>
+      <trkpt lat="52.0010" lon="13.0000"><ele>101</ele></trkpt>
+    </trkseg>
+    <trkseg>
+      <trkpt lat="52.0100" lon="13.0000"><ele>102</ele></trkpt>
+      <trkpt lat="52.0110" lon="13.0000"><ele>103</ele></trkpt>
+    </trkseg>
+  </trk>
+</gpx>"#;
+    
+    #[test]
+    fn test_segments_are_concatenated_with_gaps() {
+        let parsed = parse_gpx(MULTI_SOURCE_GPX).unwrap();
+        
+        assert_eq!(parsed.name.as_deref(), Some("Paused"));
+        assert_eq!(parsed.geometry.0.len(), 4);
+        assert_eq!(parsed.segment_gaps.len(), 1);
+        assert_eq!(parsed.segment_gaps[0].after_point, 1);
+        assert!(parsed.segment_gaps[0].distance_m > 900.0);
+        assert_eq!(parsed.sources.len(), 3);
+        assert!(parsed.sources[0].selected);
+    }
+    
+    #[test]
+    fn test_routes_and_waypoints_can_be_selected() {
+        let route = parse_gpx_selection(MULTI_SOURCE_GPX, GpxSelection::parse("route:0").unwrap()).unwrap();
+        assert_eq!(route.name.as_deref(), Some("Planned"));
+        assert_eq!(route.geometry.0.len(), 2);
+        
+        let waypoints = parse_gpx_selection(MULTI_SOURCE_GPX, GpxSelection::Waypoints).unwrap();
+        assert_eq!(waypoints.geometry.0.len(), 2);
+        
+        assert!(parse_gpx_selection(MULTI_SOURCE_GPX, GpxSelection::Track(3)).is_err());
+        assert_eq!(GpxSelection::parse("lap:1"), None);
+    }
+    
+    #[test]
+    fn test_route_only_file() {
+        let gpx = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="planner" xmlns="http://www.topografix.com/GPX/1/1">
+  <rte><rtept lat="47.0000" lon="10.0000"/><rtept lat="47.0010" lon="10.0010"/></rte>
+</gpx>"#;
+        let parsed = parse_gpx(gpx).unwrap();
+        assert_eq!(parsed.geometry.0.len(), 2);
+    }
+}
+
+#[cfg(test)]
+mod input_format_tests {
+    use curvematch_backend::utils::fit::crc16;
+    use curvematch_backend::utils::gpx_parser::GpxSelection;
+    use curvematch_backend::utils::input_format::{parse_route_file, InputFormat};
+    
+    fn parse(data: &[u8]) -> curvematch_backend::utils::gpx_parser::ParsedGpx {
+        let format = InputFormat::detect(data).unwrap();
+        parse_route_file(data, format, GpxSelection::Auto).unwrap()
+    }
+    
+    fn semicircles(degrees: f64) -> [u8; 4] {
+        ((degrees / 180.0 * 2_147_483_648.0) as i32).to_le_bytes()
+    }
+    
+    /// Activity with two records, a timer stop, then a third record
+    fn fit_activity() -> Vec<u8> {
+        let mut body = vec![
+            0x40, 0, 0, 0, 0, 1, 0, 1, 0x00, // file_id definition
+            0x00, 4,                         // type = activity
+            0x41, 0, 0, 20, 0, 3, 0, 4, 0x85, 1, 4, 0x85, 2, 2, 0x84, // record definition
+            0x42, 0, 0, 21, 0, 2, 0, 1, 0x00, 1, 1, 0x00,             // event definition
+        ];
+        let record = |body: &mut Vec<u8>, lat: f64, lon: f64, altitude: f64| {
+            body.push(0x01);
+            body.extend_from_slice(&semicircles(lat));
+            body.extend_from_slice(&semicircles(lon));
+            body.extend_from_slice(&(((altitude + 500.0) * 5.0) as u16).to_le_bytes());
+        };
+        record(&mut body, 52.0, 13.0, 100.0);
+        record(&mut body, 52.001, 13.0, 110.0);
+        body.extend_from_slice(&[0x02, 0, 4]); // timer stop_all
+        record(&mut body, 52.01, 13.0, 120.0);
+        
+        let mut file = vec![14, 0x20, 0x56, 0x08];
+        file.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        file.extend_from_slice(b".FIT");
+        file.extend_from_slice(&[0, 0]);
+        file.extend_from_slice(&body);
+        let crc = crc16(0, &file);
+        file.extend_from_slice(&crc.to_le_bytes());
+        file
+    }
+    
+    #[test]
+    fn test_fit_activity() {
+        let data = fit_activity();
+        assert_eq!(InputFormat::detect(&data).unwrap(), InputFormat::Fit);
+        
+        let parsed = parse(&data);
+        assert_eq!(parsed.geometry.0.len(), 3);
+        assert!((parsed.geometry.0[1].y - 52.001).abs() < 1e-6);
+        assert!((parsed.elevation_profile[2] - 120.0).abs() < 1e-6);
+        assert_eq!(parsed.segment_gaps.len(), 1);
+        
+        // A corrupted byte fails the file CRC
+        let mut corrupt = data.clone();
+        corrupt[20] ^= 0xFF;
+        assert!(parse_route_file(&corrupt, InputFormat::Fit, GpxSelection::Auto).is_err());
+    }
+    
+    #[test]
+    fn test_tcx_course() {
+        let tcx = r#"<?xml version="1.0" encoding="UTF-8"?>
+<TrainingCenterDatabase xmlns="http://www.garmin.com/xmlschemas/TrainingCenterDatabase/v2">
+  <Courses><Course><Name>Ridge</Name><Track>
+    <Trackpoint><Position><LatitudeDegrees>47.0</LatitudeDegrees><LongitudeDegrees>10.0</LongitudeDegrees></Position><AltitudeMeters>500</AltitudeMeters></Trackpoint>
+    <Trackpoint><Time>2024-01-01T00:00:00Z</Time></Trackpoint>
+    <Trackpoint><Position><LatitudeDegrees>47.001</LatitudeDegrees><LongitudeDegrees>10.0</LongitudeDegrees></Position><AltitudeMeters>510</AltitudeMeters></Trackpoint>
+  </Track></Course></Courses>
+</TrainingCenterDatabase>"#;
+        assert_eq!(InputFormat::detect(tcx.as_bytes()).unwrap(), InputFormat::Tcx);
+        
+        let parsed = parse(tcx.as_bytes());
+        assert_eq!(parsed.name.as_deref(), Some("Ridge"));
+        assert_eq!(parsed.geometry.0.len(), 2);
+        assert_eq!(parsed.elevation_profile, vec![500.0, 510.0]);
+    }
+    
+    #[test]
+    fn test_kml_and_geojson_lines() {
+        let kml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<kml xmlns="http://www.opengis.net/kml/2.2"><Document><Placemark><name>Loop</name>
+  <LineString><coordinates>10.0,47.0,0 10.001,47.001,0 10.002,47.0,0</coordinates></LineString>
+</Placemark></Document></kml>"#;
+        let parsed = parse(kml.as_bytes());
+        assert_eq!(parsed.name.as_deref(), Some("Loop"));
+        assert_eq!(parsed.geometry.0.len(), 3);
+        // Ground-clamped altitudes are not sea level
+        assert!(parsed.elevation_profile.is_empty());
+        
+        let geojson = r#"{"type": "FeatureCollection", "features": [
+            {"type": "Feature", "properties": {"name": "Planned"},
+             "geometry": {"type": "LineString", "coordinates": [[10.0, 47.0, 500], [10.001, 47.001, 505]]}}
+        ]}"#;
+        assert_eq!(InputFormat::detect(geojson.as_bytes()).unwrap(), InputFormat::GeoJson);
+        let parsed = parse(geojson.as_bytes());
+        assert_eq!(parsed.name.as_deref(), Some("Planned"));
+        assert_eq!(parsed.elevation_profile, vec![500.0, 505.0]);
+    }
+    
+    #[test]
+    fn test_unsupported_uploads() {
+        assert!(InputFormat::detect(b"PK\x03\x04rest").is_err());
+        assert!(InputFormat::detect(b"<?xml version=\"1.0\"?><svg></svg>").is_err());
+        assert!(InputFormat::detect(&[0u8, 1, 2, 3]).is_err());
+    }
+}
+
+#[cfg(test)]
+mod route_export_tests {
+    use super::fixtures::db_route;
+    use curvematch_backend::db::models::DbSavedRoute;
+    use curvematch_backend::utils::fit::read_fit;
+    use curvematch_backend::utils::gpx_parser::{GpxSelection, GpxSourceKind};
+    use curvematch_backend::utils::input_format::{parse_route_file, InputFormat};
+    use curvematch_backend::utils::route_export::{content_disposition, export_route, ExportFormat};
+    use geo::LineString;
+    
+    fn saved_route() -> DbSavedRoute {
+        let line = LineString::from(vec![(13.0, 52.0), (13.0, 52.001), (13.0, 52.002)]);
+        DbSavedRoute {
+            name: "Hill & Dale loop".to_string(),
+            distance_m: 222.0,
+            elevation_gain_m: 10.0,
+            gain_per_km: 45.0,
+            elevation_profile_json: "[100.0, 110.0, 105.0]".to_string(),
+            gpx_data: b"<gpx/>".to_vec(),
+            ..db_route(7, &line)
+        }
+    }
+    
+    #[test]
+    fn test_fit_course_round_trip() {
+        let data = export_route(&saved_route(), ExportFormat::Fit).unwrap();
+        let course = read_fit(&data).unwrap();
+        
+        assert_eq!(course.kind, GpxSourceKind::Route);
+        assert_eq!(course.name.as_deref(), Some("Hill & Dale loo"));
+        assert_eq!(course.segments.len(), 1);
+        let points = &course.segments[0];
+        assert_eq!(points.len(), 3);
+        assert!((points[1].lat - 52.001).abs() < 1e-6);
+        assert!((points[1].elevation.unwrap() - 110.0).abs() < 0.2);
+    }
+    
+    #[test]
+    fn test_text_exports_parse_back() {
+        let route = saved_route();
+        for (format, input) in [
+            (ExportFormat::GeoJson, InputFormat::GeoJson),
+            (ExportFormat::Kml, InputFormat::Kml),
+            (ExportFormat::Tcx, InputFormat::Tcx),
+        ] {
+            let data = export_route(&route, format).unwrap();
+            assert_eq!(InputFormat::detect(&data).unwrap(), input);
+            let parsed = parse_route_file(&data, input, GpxSelection::Auto).unwrap();
+            assert_eq!(parsed.geometry.0.len(), 3);
+            assert_eq!(parsed.elevation_profile, vec![100.0, 110.0, 105.0]);
+        }
+        
+        assert_eq!(export_route(&route, ExportFormat::Gpx).unwrap(), b"<gpx/>".to_vec());
+    }
+    
+    #[test]
+    fn test_csv_gradients_and_headers() {
+        let csv = String::from_utf8(export_route(&saved_route(), ExportFormat::Csv).unwrap()).unwrap();
+        let rows: Vec<&str> = csv.lines().collect();
+        
+        assert_eq!(rows[0], "distance_m,latitude,longitude,elevation_m,gradient_pct");
+        assert_eq!(rows.len(), 4);
+        // 10m climb over ~111m
+        assert!(rows[2].ends_with(",110.0,9.0"));
+        
+        assert_eq!(ExportFormat::parse("FIT"), Some(ExportFormat::Fit));
+        assert_eq!(ExportFormat::Fit.content_type(), "application/vnd.ant.fit");
+        assert_eq!(
+            content_disposition("Hill & Dale loop", 7, ExportFormat::Fit),
+            "attachment; filename=\"Hill___Dale_loop.fit\""
+        );
+        assert_eq!(
+            content_disposition("Ö", 7, ExportFormat::Csv),
+            "attachment; filename=\"route-7.csv\""
+        );
+    }
+}
+
+#[cfg(test)]
+mod ownership_tests {
+    use super::fixtures::{test_pool, test_state};
+    use axum::body::Body;
+    use axum::http::{header, Request, StatusCode};
+    use curvematch_backend::api;
+    use curvematch_backend::auth::jwt::create_token;
+    use curvematch_backend::config::Config;
+    use curvematch_backend::db::queries::public_routes::publish_route;
+    use curvematch_backend::db::queries::routes::{
+        delete_route_by_id, get_route_by_id, save_route, update_route_name, NewRoute,
+    };
+    use curvematch_backend::db::queries::users::create_user;
+    use curvematch_backend::matching::engine::{MatchingConfig, MatchingEngine};
+    use curvematch_backend::matching::spatial_index::{IndexSources, SharedSpatialIndex};
+    use geo::LineString;
+    use sqlx::sqlite::SqlitePool;
+    use tower::ServiceExt;
+    
+    async fn user_with_route(pool: &SqlitePool, name: &str) -> (i64, i64) {
+        let user = create_user(pool, &format!("{}@example.com", name), name, "salt", "hash")
+            .await
+            .unwrap();
+        let route = save_route(pool, NewRoute {
+            user_id: user.id,
+            name,
+            tag: "Running",
+            distance_m: 1000.0,
+            elevation_gain_m: 10.0,
+            gain_per_km: 10.0,
+            curve_score: 0.5,
+            match_pct: 50.0,
+            geom_wkt: r#"{"type":"LineString","coordinates":[[13.40,52.52],[13.41,52.53]]}"#,
+            elevation_profile_json: "[34.0, 44.0]",
+            search_area_json: "{}",
+            gpx_data: b"<gpx/>",
+            twistiness: None,
+        })
+        .await
+        .unwrap();
+        (user.id, route.id)
+    }
+    
+    #[tokio::test]
+    async fn test_queries_are_scoped_by_owner() {
+        let pool = test_pool().await;
+        let (alice, alice_route) = user_with_route(&pool, "alice").await;
+        let (bob, _) = user_with_route(&pool, "bob").await;
+        
+        assert!(get_route_by_id(&pool, alice_route, alice).await.unwrap().is_some());
+        assert!(get_route_by_id(&pool, alice_route, bob).await.unwrap().is_none());
+        assert!(!update_route_name(&pool, alice_route, bob, "mine now").await.unwrap());
+        assert!(!delete_route_by_id(&pool, alice_route, bob).await.unwrap());
+        assert!(delete_route_by_id(&pool, alice_route, alice).await.unwrap());
+    }
+    
+    #[tokio::test]
+    async fn test_library_requires_owner_session() {
+        let (state, _) = test_state("ownership", Config::default()).await;
+        let (alice, alice_route) = user_with_route(&state.pool, "alice").await;
+        let (bob, _) = user_with_route(&state.pool, "bob").await;
+        let config = state.config.clone();
+        let app = api::routes(state.clone()).with_state(state);
+        
+        let get = |user: Option<i64>| {
+            let mut request = Request::builder().uri(format!("/route/{}/gpx", alice_route));
+            if let Some(user) = user {
+                let (token, _) = create_token(&config.auth, user, "test-session").unwrap();
+                request = request.header(header::COOKIE, format!("token={}", token));
+            }
+            request.body(Body::empty()).unwrap()
+        };
+        
+        let response = app.clone().oneshot(get(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        
+        let response = app.clone().oneshot(get(Some(bob))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        
+        let response = app.oneshot(get(Some(alice))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    
+    #[tokio::test]
+    async fn test_matches_only_own_and_public_routes() {
+        let pool = test_pool().await;
+        let (alice, alice_route) = user_with_route(&pool, "alice").await;
+        let (bob, bob_route) = user_with_route(&pool, "bob").await;
+        
+        let input: LineString<f64> = vec![(13.40, 52.52), (13.41, 52.53)].into();
+        let config = MatchingConfig {
+            distance_flexibility: 100.0,
+            elevation_importance: 0.0,
+            curvature_importance: 100.0,
+            min_match_percentage: 0.0,
+            ..Default::default()
+        };
+        let matched_ids = |index: &SharedSpatialIndex, user: i64| {
+            let engine = MatchingEngine::from_index(index.snapshot()).for_user(user);
+            let mut ids: Vec<i64> = engine
+                .find_matches_with_config(&input, &[], (13.3, 52.4, 13.5, 52.6), config.clone())
+                .unwrap()
+                .iter()
+                .map(|m| m.id.parse().unwrap())
+                .collect();
+            ids.sort();
+            ids
+        };
+        
+        let index = SharedSpatialIndex::load(&pool, IndexSources::default()).await.unwrap();
+        assert_eq!(matched_ids(&index, bob), vec![bob_route]);
+        assert_eq!(matched_ids(&index, alice), vec![alice_route]);
+        
+        // Published routes are visible to everyone, also after a reload
+        publish_route(&pool, alice_route, alice).await.unwrap();
+        index.set_public(alice_route, true);
+        assert_eq!(matched_ids(&index, bob), vec![alice_route, bob_route]);
+        let reloaded = SharedSpatialIndex::load(&pool, IndexSources::default()).await.unwrap();
+        assert_eq!(matched_ids(&reloaded, bob), vec![alice_route, bob_route]);
+        
+        index.set_public(alice_route, false);
+        assert_eq!(matched_ids(&index, bob), vec![bob_route]);
+    }
+}
+
+mod session_tests {
+    use super::fixtures::test_pool;
+    use curvematch_backend::auth::jwt::verify_token;
+    use curvematch_backend::auth::session::{
+        end_all_sessions, refresh_session, start_session, RevocationList,
+    };
+    use curvematch_backend::config::AuthConfig;
+    use curvematch_backend::db::queries::sessions::{
+        find_session, get_active_sessions, rotate_session, touch_session,
+    };
+    use curvematch_backend::db::queries::users::create_user;
+    
+    #[tokio::test]
+    async fn test_refresh_rotates_tokens() {
+        let pool = test_pool().await;
+        let auth = AuthConfig::default();
+        let revoked = RevocationList::default();
+        let user = create_user(&pool, "rider@example.com", "rider", "salt", "hash").await.unwrap();
+        
+        let first = start_session(&pool, &auth, user.id, Some("Firefox")).await.unwrap();
+        assert!(verify_token(&auth, &first.access_token, &revoked).is_ok());
+        
+        let second = refresh_session(&pool, &auth, &revoked, &first.refresh_token).await.unwrap();
+        assert_eq!(second.session_id, first.session_id);
+        assert_ne!(second.refresh_token, first.refresh_token);
+        assert!(verify_token(&auth, &second.access_token, &revoked).is_ok());
+        
+        // The access token issued before the refresh is no longer accepted
+        assert!(verify_token(&auth, &first.access_token, &revoked).is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_refresh_token_reuse_revokes_session() {
+        let pool = test_pool().await;
+        let auth = AuthConfig::default();
+        let revoked = RevocationList::default();
+        let user = create_user(&pool, "rider@example.com", "rider", "salt", "hash").await.unwrap();
+        
+        let first = start_session(&pool, &auth, user.id, None).await.unwrap();
+        let second = refresh_session(&pool, &auth, &revoked, &first.refresh_token).await.unwrap();
+        
+        assert!(refresh_session(&pool, &auth, &revoked, &first.refresh_token).await.is_err());
+        assert!(refresh_session(&pool, &auth, &revoked, &second.refresh_token).await.is_err());
+        assert!(verify_token(&auth, &second.access_token, &revoked).is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_concurrent_rotation_applies_once() {
+        let pool = test_pool().await;
+        let auth = AuthConfig::default();
+        let user = create_user(&pool, "rider@example.com", "rider", "salt", "hash").await.unwrap();
+        let issued = start_session(&pool, &auth, user.id, None).await.unwrap();
+        
+        // Both refreshes read the session before either rotates it
+        let session = find_session(&pool, &issued.session_id).await.unwrap().unwrap();
+        let exp = session.access_expires_at;
+        assert!(rotate_session(&pool, &session.id, &session.refresh_token_hash, "first", "jti-1", exp).await.unwrap());
+        assert!(!rotate_session(&pool, &session.id, &session.refresh_token_hash, "second", "jti-2", exp).await.unwrap());
+        
+        let session = find_session(&pool, &issued.session_id).await.unwrap().unwrap();
+        assert_eq!((session.refresh_token_hash.as_str(), session.access_jti.as_str()), ("first", "jti-1"));
+    }
+    
+    #[tokio::test]
+    async fn test_touch_updates_last_seen_once_a_minute() {
+        let pool = test_pool().await;
+        let auth = AuthConfig::default();
+        let user = create_user(&pool, "rider@example.com", "rider", "salt", "hash").await.unwrap();
+        let issued = start_session(&pool, &auth, user.id, None).await.unwrap();
+        let backdate = |offset: &'static str| {
+            sqlx::query("UPDATE sessions SET last_seen_at = datetime('now', ?1) WHERE id = ?2")
+                .bind(offset)
+                .bind(issued.session_id.clone())
+                .execute(&pool)
+        };
+        
+        backdate("-2 hours").await.unwrap();
+        let stale = find_session(&pool, &issued.session_id).await.unwrap().unwrap().last_seen_at;
+        touch_session(&pool, &issued.session_id).await.unwrap();
+        let seen = find_session(&pool, &issued.session_id).await.unwrap().unwrap().last_seen_at;
+        assert!(seen > stale);
+        
+        // A use within the minute leaves it alone
+        backdate("-30 seconds").await.unwrap();
+        let recent = find_session(&pool, &issued.session_id).await.unwrap().unwrap().last_seen_at;
+        touch_session(&pool, &issued.session_id).await.unwrap();
+        let seen = find_session(&pool, &issued.session_id).await.unwrap().unwrap().last_seen_at;
+        assert_eq!(seen, recent);
+    }
+    
+    #[tokio::test]
+    async fn test_log_out_everywhere() {
+        let pool = test_pool().await;
+        let auth = AuthConfig::default();
+        let revoked = RevocationList::default();
+        let user = create_user(&pool, "rider@example.com", "rider", "salt", "hash").await.unwrap();
+        
+        let laptop = start_session(&pool, &auth, user.id, Some("laptop")).await.unwrap();
+        let phone = start_session(&pool, &auth, user.id, Some("phone")).await.unwrap();
+        let now = chrono::Utc::now().timestamp();
+        assert_eq!(get_active_sessions(&pool, user.id, now).await.unwrap().len(), 2);
+        
+        assert_eq!(end_all_sessions(&pool, &revoked, user.id).await.unwrap(), 2);
+        assert!(get_active_sessions(&pool, user.id, now).await.unwrap().is_empty());
+        assert!(verify_token(&auth, &laptop.access_token, &revoked).is_err());
+        assert!(verify_token(&auth, &phone.access_token, &revoked).is_err());
+        
+        // Revocations survive a restart
+        let reloaded = RevocationList::load(&pool).await.unwrap();
+        assert!(reloaded.is_revoked(&verify_token(&auth, &laptop.access_token, &RevocationList::default()).unwrap().jti));
+    }
+}
+
+mod config_tests {
+    use curvematch_backend::config::{CliArgs, Config, Profile, DEFAULT_JWT_SECRET};
+    use curvematch_backend::matching::engine::MatchingConfig;
+    use std::collections::HashMap;
+    
+    fn env(vars: &[(&str, &str)]) -> HashMap<String, String> {
+        vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+    
+    fn cli(args: &[&str]) -> CliArgs {
+        CliArgs::parse(args.iter().map(|a| a.to_string())).unwrap()
+    }
+    
+    #[test]
+    fn test_layers_override_in_order() {
+        let path = std::env::temp_dir().join(format!("curvematch-{}.toml", std::process::id()));
+        std::fs::write(&path, r#"
+            [server]
+            port = 4000
+            host = "0.0.0.0"
+            cors_origins = ["https://curvematch.example"]
+            
+            [database]
+            max_connections = 8
+            
+            [matching]
+            max_results = 50
+        "#).unwrap();
+        
+        let config = Config::from_layers(
+            &cli(&["--config", path.to_str().unwrap(), "--port=5000"]),
+            env(&[
+                ("SERVER_PORT", "4500"),
+                ("CURVEMATCH_DATABASE__MAX_CONNECTIONS", "12"),
+                ("CURVEMATCH_SERVER__CORS_ORIGINS", "https://a.example, https://b.example"),
+                ("CURVEMATCH_AUTH__ADMIN_EMAILS", "ops@example.com, lead@example.com"),
+            ]),
+        )
+        .unwrap();
+        std::fs::remove_file(&path).ok();
+        
+        assert_eq!(config.server.host, "0.0.0.0");
+        assert_eq!(config.server.port, 5000);
+        assert_eq!(config.database.max_connections, 12);
+        assert_eq!(config.server.cors_origins, vec!["https://a.example", "https://b.example"]);
+        assert_eq!(config.matching.max_results, 50);
+        assert_eq!(config.auth.access_token_ttl_minutes, 15);
+        assert_eq!(config.auth.admin_emails, vec!["ops@example.com", "lead@example.com"]);
+    }
+    
+    #[test]
+    fn test_legacy_env_names() {
+        let config = Config::from_layers(
+            &CliArgs::default(),
+            env(&[
+                ("JWT_SECRET", "s3cret"),
+                ("DATABASE_URL", "sqlite::memory:"),
+                ("FRONTEND_URL", "http://localhost:5173,http://localhost:4173"),
+                ("DEM_DIR", "/data/dem"),
+            ]),
+        )
+        .unwrap();
+        
+        assert_eq!(config.auth.jwt_secret, "s3cret");
+        assert_eq!(config.database.url, "sqlite::memory:");
+        assert_eq!(config.server.cors_origins.len(), 2);
+        assert_eq!(config.data.dem_dir.as_deref(), Some("/data/dem"));
+        assert!(config.data.osm_pbf_path.is_none());
+    }
+    
+    #[test]
+    fn test_default_secret_rejected_outside_dev() {
+        let production = Config::from_layers(&cli(&["--profile", "production"]), HashMap::new()).unwrap();
+        assert_eq!(production.profile, Profile::Production);
+        assert_eq!(production.auth.jwt_secret, DEFAULT_JWT_SECRET);
+        assert!(production.validate().is_err());
+        
+        let dev = Config::from_layers(&cli(&["--profile", "dev"]), HashMap::new()).unwrap();
+        assert!(dev.validate().is_ok());
+        
+        let configured = Config::from_layers(
+            &cli(&["--profile", "prod", "--set", "auth.jwt_secret=something-long"]),
+            HashMap::new(),
+        )
+        .unwrap();
+        assert!(configured.validate().is_ok());
+    }
+    
+    #[test]
+    fn test_matching_defaults_follow_engine() {
+        let config = Config::default().matching.matching_config();
+        let engine = MatchingConfig::default();
+    
+        assert_eq!(config.distance_flexibility, engine.distance_flexibility);
+        assert_eq!(config.elevation_flexibility, engine.elevation_flexibility);
+        assert_eq!(config.shape_metric, engine.shape_metric);
+        assert_eq!(config.elevation_importance, engine.elevation_importance);
+        assert_eq!(config.max_results, engine.max_results);
+    }
+    
+    #[test]
+    fn test_oidc_providers_from_file_and_env() {
+        let path = std::env::temp_dir().join(format!("curvematch-oidc-{}.toml", std::process::id()));
+        std::fs::write(&path, r#"
+            [oidc.providers.corp]
+            display_name = "Corp SSO"
+            issuer = "http://localhost:8080/realms/corp"
+            client_id = "curvematch"
+            redirect_uri = "http://localhost:3000/api/oidc/corp/callback"
+        "#).unwrap();
+        
+        let load = |profile: &str| Config::from_layers(
+            &cli(&["--config", path.to_str().unwrap(), "--profile", profile, "--set", "auth.jwt_secret=something-long"]),
+            env(&[("CURVEMATCH_OIDC__PROVIDERS__CORP__CLIENT_SECRET", "from-env")]),
+        )
+        .unwrap();
+        let dev = load("dev");
+        let production = load("production");
+        std::fs::remove_file(&path).ok();
+        
+        let corp = &dev.oidc.providers["corp"];
+        assert_eq!(corp.client_secret.as_deref(), Some("from-env"));
+        assert_eq!(corp.scopes, vec!["openid", "email", "profile"]);
+        assert!(dev.validate().is_ok());
+        // A plain http issuer is only accepted for local development
+        assert!(production.validate().is_err());
+    }
+    
+    #[test]
+    fn test_unknown_flag_rejected() {
+        assert!(CliArgs::parse(vec!["--prot".to_string(), "80".to_string()]).is_err());
+    }
+}
+
+mod account_tests {
+    use super::fixtures::{test_app, test_app_with_config};
+    use axum::body::Body;
+    use axum::http::{header, Request, StatusCode};
+    use axum::Router;
+    use curvematch_backend::auth::api_keys::{issue_api_key, Scope};
+    use curvematch_backend::auth::session::{start_session, ACCESS_COOKIE};
+    use curvematch_backend::auth::totp::{base32_decode, code_at, TOTP_PERIOD_SECS};
+    use curvematch_backend::config::Config;
+    use curvematch_backend::db::queries::audit::get_audit_entries;
+    use curvematch_backend::db::queries::recovery_codes::count_unused_recovery_codes;
+    use curvematch_backend::db::queries::sessions::get_active_sessions;
+    use curvematch_backend::db::queries::routes::{save_route, NewRoute};
+    use curvematch_backend::db::queries::users::{create_user, find_user_by_email, set_user_role};
+    use tower::ServiceExt;
+    
+    async fn post(app: &Router, uri: &str, body: serde_json::Value) -> StatusCode {
+        let request = Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        app.clone().oneshot(request).await.unwrap().status()
+    }
+    
+    fn token_from(body: &str) -> String {
+        let start = body.find("token=").expect("link with token") + "token=".len();
+        body[start..].split_whitespace().next().unwrap().to_string()
+    }
+    
+    pub async fn signup(app: &Router, email: &str) -> StatusCode {
+        post(app, "/signup", serde_json::json!({
+            "email": email,
+            "username": "rider",
+            "password": "old-password",
+            "confirmPassword": "old-password",
+        }))
+        .await
+    }
+    
+    #[tokio::test]
+    async fn test_signup_sends_verification_email() {
+        let (app, pool, mailer) = test_app("verify").await;
+        
+        assert_eq!(signup(&app, "not-an-email").await, StatusCode::BAD_REQUEST);
+        assert_eq!(signup(&app, "rider@example.com").await, StatusCode::OK);
+        
+        let messages = mailer.messages().unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].to, "rider@example.com");
+        let token = token_from(&messages[0].body);
+        
+        let user = find_user_by_email(&pool, "rider@example.com").await.unwrap().unwrap();
+        assert!(user.email_verified_at.is_none());
+        
+        assert_eq!(post(&app, "/verify-email", serde_json::json!({ "token": token })).await, StatusCode::OK);
+        let user = find_user_by_email(&pool, "rider@example.com").await.unwrap().unwrap();
+        assert!(user.email_verified_at.is_some());
+        
+        // Tokens are single-use
+        assert_eq!(post(&app, "/verify-email", serde_json::json!({ "token": token })).await, StatusCode::BAD_REQUEST);
+    }
+    
+    #[tokio::test]
+    async fn test_password_reset_flow() {
+        let (app, pool, mailer) = test_app("reset").await;
+        assert_eq!(signup(&app, "rider@example.com").await, StatusCode::OK);
+        let user = find_user_by_email(&pool, "rider@example.com").await.unwrap().unwrap();
+        let config = Config::default();
+        start_session(&pool, &config.auth, user.id, Some("laptop")).await.unwrap();
+        
+        // Unknown addresses get the same response but no email
+        let forgot = |email: &str| serde_json::json!({ "email": email });
+        assert_eq!(post(&app, "/password/forgot", forgot("nobody@example.com")).await, StatusCode::OK);
+        assert_eq!(mailer.messages().unwrap().len(), 1);
+        
+        assert_eq!(post(&app, "/password/forgot", forgot("rider@example.com")).await, StatusCode::OK);
+        let messages = mailer.messages().unwrap();
+        assert_eq!(messages.len(), 2);
+        let token = token_from(&messages[1].body);
+        
+        let reset = |token: &str| serde_json::json!({
+            "token": token,
+            "password": "new-password",
+            "confirmPassword": "new-password",
+        });
+        assert_eq!(post(&app, "/password/reset", reset("bogus")).await, StatusCode::BAD_REQUEST);
+        assert_eq!(post(&app, "/password/reset", reset(&token)).await, StatusCode::OK);
+        assert_eq!(post(&app, "/password/reset", reset(&token)).await, StatusCode::BAD_REQUEST);
+        
+        // Existing sessions are signed out
+        let now = chrono::Utc::now().timestamp();
+        assert!(get_active_sessions(&pool, user.id, now).await.unwrap().is_empty());
+        
+        let login = |password: &str| serde_json::json!({ "email": "rider@example.com", "password": password });
+        assert_eq!(post(&app, "/login", login("old-password")).await, StatusCode::BAD_REQUEST);
+        assert_eq!(post(&app, "/login", login("new-password")).await, StatusCode::OK);
+    }
+    
+    #[tokio::test]
+    async fn test_repeated_failed_logins_lock_the_account() {
+        let (app, pool, _) = test_app("lockout").await;
+        assert_eq!(signup(&app, "rider@example.com").await, StatusCode::OK);
+        
+        let login = |password: &str| serde_json::json!({ "email": "rider@example.com", "password": password });
+        for _ in 0..5 {
+            assert_eq!(post(&app, "/login", login("wrong-password")).await, StatusCode::BAD_REQUEST);
+        }
+        
+        // Even the right password is refused while locked
+        let request = Request::builder()
+            .method("POST")
+            .uri("/login")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(login("old-password").to_string()))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(response.headers().contains_key(header::RETRY_AFTER));
+        
+        let events: Vec<String> = get_audit_entries(&pool, 10).await.unwrap()
+            .into_iter()
+            .map(|entry| entry.event)
+            .collect();
+        assert_eq!(events[0], "login.throttled");
+        assert_eq!(events[1], "login.locked");
+        assert_eq!(events.iter().filter(|e| *e == "login.failed").count(), 4);
+    }
+    
+    #[tokio::test]
+    async fn test_forwarded_for_uses_the_proxy_address() {
+        let mut config = Config::default();
+        config.login.trust_forwarded_for = true;
+        config.login.max_attempts_per_ip = 3;
+        let (app, pool, _) = test_app_with_config("forwarded-for", config).await;
+        
+        // Each attempt claims a new address, but the proxy saw the same one
+        for (i, email) in ["a@example.com", "b@example.com", "c@example.com"].iter().enumerate() {
+            let request = Request::builder()
+                .method("POST")
+                .uri("/login")
+                .header(header::CONTENT_TYPE, "application/json")
+                .header("x-forwarded-for", format!("10.0.0.{}, 198.51.100.9", i))
+                .body(Body::from(serde_json::json!({ "email": email, "password": "wrong" }).to_string()))
+                .unwrap();
+            app.clone().oneshot(request).await.unwrap();
+        }
+        
+        let request = Request::builder()
+            .method("POST")
+            .uri("/login")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header("x-forwarded-for", "10.0.0.99, 198.51.100.9")
+            .body(Body::from(serde_json::json!({ "email": "d@example.com", "password": "wrong" }).to_string()))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        
+        let entries = get_audit_entries(&pool, 10).await.unwrap();
+        assert!(entries.iter().all(|entry| entry.ip_address.as_deref() == Some("198.51.100.9")));
+    }
+    
+    #[tokio::test]
+    async fn test_signup_enforces_password_policy() {
+        let (app, _, _) = test_app("policy").await;
+        let signup_with = |password: &str| serde_json::json!({
+            "email": "rider@example.com",
+            "username": "rider-one",
+            "password": password,
+            "confirmPassword": password,
+        });
+        
+        assert_eq!(post(&app, "/signup", signup_with("")).await, StatusCode::BAD_REQUEST);
+        assert_eq!(post(&app, "/signup", signup_with("short")).await, StatusCode::BAD_REQUEST);
+        assert_eq!(post(&app, "/signup", signup_with("Rider-One")).await, StatusCode::BAD_REQUEST);
+        assert_eq!(post(&app, "/signup", signup_with("long enough")).await, StatusCode::OK);
+    }
+    
+    async fn send_json(
+        app: &Router,
+        uri: &str,
+        cookie: Option<&str>,
+        body: serde_json::Value,
+    ) -> (StatusCode, serde_json::Value) {
+        let mut request = Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json");
+        if let Some(token) = cookie {
+            request = request.header(header::COOKIE, format!("{}={}", ACCESS_COOKIE, token));
+        }
+        let response = app.clone()
+            .oneshot(request.body(Body::from(body.to_string())).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null))
+    }
+    
+    #[tokio::test]
+    async fn test_totp_enrolment_and_two_step_login() {
+        let (app, pool, _) = test_app("totp").await;
+        assert_eq!(signup(&app, "rider@example.com").await, StatusCode::OK);
+        let user = find_user_by_email(&pool, "rider@example.com").await.unwrap().unwrap();
+        let session = start_session(&pool, &Config::default().auth, user.id, None).await.unwrap();
+        let cookie = Some(session.access_token.as_str());
+        
+        let (status, setup) = send_json(&app, "/mfa/totp/setup", cookie, serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::OK);
+        let secret = base32_decode(setup["secret"].as_str().unwrap()).unwrap();
+        assert!(setup["otpauthUri"].as_str().unwrap().starts_with("otpauth://totp/CurveMatch:rider%40example%2Ecom?"));
+        
+        let step = chrono::Utc::now().timestamp() / TOTP_PERIOD_SECS;
+        let code = |step: i64| format!("{:06}", code_at(&secret, step));
+        let (status, _) = send_json(&app, "/mfa/totp/activate", cookie, serde_json::json!({ "code": "000000x" })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, activated) = send_json(&app, "/mfa/totp/activate", cookie, serde_json::json!({ "code": code(step) })).await;
+        assert_eq!(status, StatusCode::OK);
+        let recovery_codes = activated["recoveryCodes"].as_array().unwrap();
+        assert_eq!(recovery_codes.len(), 10);
+        
+        // The password alone only yields a pending token, which isn't a session
+        let login = serde_json::json!({ "email": "rider@example.com", "password": "old-password" });
+        let (status, challenge) = send_json(&app, "/login", None, login.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(challenge["mfaRequired"], true);
+        assert!(challenge.get("user").is_none());
+        let mfa_token = challenge["mfaToken"].as_str().unwrap().to_string();
+        let (status, _) = send_json(&app, "/mfa/totp/setup", Some(&mfa_token), serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        
+        // The code used to activate can't be replayed; the next one works
+        let second_step = |code: &str| serde_json::json!({ "mfaToken": mfa_token, "code": code });
+        let (status, _) = send_json(&app, "/login/mfa", None, second_step(&code(step))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, body) = send_json(&app, "/login/mfa", None, second_step(&code(step + 1))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["user"]["mfaEnabled"], true);
+        
+        // Recovery codes work once each, with or without the dash
+        let recovery = recovery_codes[0].as_str().unwrap().replace('-', "").to_uppercase();
+        let (status, _) = send_json(&app, "/login/mfa", None, second_step(&recovery)).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send_json(&app, "/login/mfa", None, second_step(&recovery)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(count_unused_recovery_codes(&pool, user.id).await.unwrap(), 9);
+        
+        let (status, _) = send_json(&app, "/mfa/totp/disable", cookie, serde_json::json!({
+            "password": "old-password",
+            "code": recovery_codes[1],
+        }))
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let (_, body) = send_json(&app, "/login", None, login).await;
+        assert_eq!(body["user"]["mfaEnabled"], false);
+    }
+    
+    async fn call(
+        app: &Router,
+        method: &str,
+        uri: &str,
+        credential: (header::HeaderName, String),
+        body: Option<serde_json::Value>,
+    ) -> (StatusCode, serde_json::Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(credential.0, credential.1)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body.map(|b| Body::from(b.to_string())).unwrap_or_default())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null))
+    }
+    
+    #[tokio::test]
+    async fn test_scoped_api_keys() {
+        let (app, pool, _) = test_app("api-keys").await;
+        assert_eq!(signup(&app, "rider@example.com").await, StatusCode::OK);
+        let user = find_user_by_email(&pool, "rider@example.com").await.unwrap().unwrap();
+        let session = start_session(&pool, &Config::default().auth, user.id, None).await.unwrap();
+        let cookie = || (header::COOKIE, format!("{}={}", ACCESS_COOKIE, session.access_token));
+        
+        let create = |scopes: serde_json::Value| Some(serde_json::json!({ "name": "CI", "scopes": scopes }));
+        let (status, _) = call(&app, "POST", "/api-keys", cookie(), create(serde_json::json!(["library:admin"]))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, created) = call(&app, "POST", "/api-keys", cookie(), create(serde_json::json!(["match:run", "library:read"]))).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let key = created["key"].as_str().unwrap().to_string();
+        assert!(key.starts_with("cmk_"));
+        assert_eq!(created["prefix"], key[..12]);
+        assert_eq!(created["scopes"], serde_json::json!(["match:run", "library:read"]));
+        
+        // Only the digest is stored
+        let stored: (String,) = sqlx::query_as("SELECT key_hash FROM api_keys").fetch_one(&pool).await.unwrap();
+        assert_ne!(stored.0, key);
+        
+        let bearer = |key: &str| (header::AUTHORIZATION, format!("Bearer {}", key));
+        assert_eq!(call(&app, "GET", "/library", bearer(&key), None).await.0, StatusCode::OK);
+        assert_eq!(call(&app, "DELETE", "/library/1", bearer(&key), None).await.0, StatusCode::FORBIDDEN);
+        assert_eq!(call(&app, "GET", "/library", bearer("cmk_not-a-key"), None).await.0, StatusCode::UNAUTHORIZED);
+        // Keys can't manage accounts, including other keys
+        assert_eq!(call(&app, "GET", "/api-keys", bearer(&key), None).await.0, StatusCode::UNAUTHORIZED);
+        assert_eq!(call(&app, "GET", "/sessions", bearer(&key), None).await.0, StatusCode::UNAUTHORIZED);
+        
+        let (status, keys) = call(&app, "GET", "/api-keys", cookie(), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(keys[0]["lastUsedAt"].is_string());
+        assert!(keys[0].get("key").is_none());
+        
+        let id = created["id"].as_i64().unwrap();
+        let (status, _) = call(&app, "DELETE", &format!("/api-keys/{}", id), cookie(), None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert_eq!(call(&app, "GET", "/library", bearer(&key), None).await.0, StatusCode::UNAUTHORIZED);
+        let (status, _) = call(&app, "DELETE", &format!("/api-keys/{}", id), cookie(), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+    
+    #[tokio::test]
+    async fn test_roles_gate_admin_and_curation() {
+        let (app, pool, _) = test_app("roles").await;
+        let auth = Config::default().auth;
+        let admin = create_user(&pool, "admin@example.com", "admin", "salt", "hash").await.unwrap();
+        set_user_role(&pool, admin.id, "admin").await.unwrap();
+        assert_eq!(signup(&app, "rider@example.com").await, StatusCode::OK);
+        let rider = find_user_by_email(&pool, "rider@example.com").await.unwrap().unwrap();
+        let route = save_route(&pool, NewRoute {
+            user_id: rider.id,
+            name: "Loop",
+            tag: "Cycling",
+            distance_m: 1000.0,
+            elevation_gain_m: 10.0,
+            gain_per_km: 10.0,
+            curve_score: 0.5,
+            match_pct: 50.0,
+            geom_wkt: r#"{"type":"LineString","coordinates":[[13.40,52.52],[13.41,52.53]]}"#,
+            elevation_profile_json: "[34.0, 44.0]",
+            search_area_json: "{}",
+            gpx_data: b"<gpx/>",
+            twistiness: None,
+        })
+        .await
+        .unwrap();
+        
+        let admin_session = start_session(&pool, &auth, admin.id, None).await.unwrap();
+        let rider_session = start_session(&pool, &auth, rider.id, None).await.unwrap();
+        let as_admin = || (header::COOKIE, format!("{}={}", ACCESS_COOKIE, admin_session.access_token));
+        let as_rider = || (header::COOKIE, format!("{}={}", ACCESS_COOKIE, rider_session.access_token));
+        let rider_uri = |action: &str| format!("/admin/users/{}/{}", rider.id, action);
+        let publish_uri = format!("/curation/routes/{}/publish", route.id);
+        
+        // Plain users get nowhere near the admin or curator endpoints
+        assert_eq!(call(&app, "GET", "/admin/users", as_rider(), None).await.0, StatusCode::FORBIDDEN);
+        assert_eq!(call(&app, "GET", "/admin/stats", as_rider(), None).await.0, StatusCode::FORBIDDEN);
+        assert_eq!(call(&app, "POST", &publish_uri, as_rider(), None).await.0, StatusCode::FORBIDDEN);
+        
+        let (status, users) = call(&app, "GET", "/admin/users", as_admin(), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(users.as_array().unwrap().len(), 2);
+        
+        let role = |role: &str| Some(serde_json::json!({ "role": role }));
+        assert_eq!(call(&app, "PUT", &rider_uri("role"), as_admin(), role("root")).await.0, StatusCode::BAD_REQUEST);
+        let own_role = format!("/admin/users/{}/role", admin.id);
+        assert_eq!(call(&app, "PUT", &own_role, as_admin(), role("user")).await.0, StatusCode::BAD_REQUEST);
+        let (status, body) = call(&app, "PUT", &rider_uri("role"), as_admin(), role("curator")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["role"], "curator");
+        
+        // The new role applies to the existing session
+        assert_eq!(call(&app, "POST", &publish_uri, as_rider(), None).await.0, StatusCode::NO_CONTENT);
+        assert_eq!(call(&app, "GET", "/admin/users", as_rider(), None).await.0, StatusCode::FORBIDDEN);
+        let (status, public) = call(&app, "GET", "/public-routes", as_admin(), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(public[0]["name"], "Loop");
+        assert!(public[0].get("userId").is_none() && public[0].get("user_id").is_none());
+        
+        let (status, stats) = call(&app, "GET", "/admin/stats", as_admin(), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((stats["users"].as_i64(), stats["curators"].as_i64()), (Some(2), Some(1)));
+        assert_eq!(stats["publicRoutes"], 1);
+        
+        let (status, _) = call(&app, "PUT", &rider_uri("role"), as_admin(), role("user")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(call(&app, "DELETE", &publish_uri, as_rider(), None).await.0, StatusCode::FORBIDDEN);
+        
+        // Disabling ends sessions, stops API keys and blocks sign-in until re-enabled
+        let key = issue_api_key(&pool, rider.id, "CI", &[Scope::LibraryRead]).await.unwrap().key;
+        let bearer = || (header::AUTHORIZATION, format!("Bearer {}", key));
+        let own_disable = format!("/admin/users/{}/disable", admin.id);
+        assert_eq!(call(&app, "POST", &own_disable, as_admin(), None).await.0, StatusCode::BAD_REQUEST);
+        let (status, body) = call(&app, "POST", &rider_uri("disable"), as_admin(), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body["disabledAt"].is_string());
+        assert_eq!(call(&app, "GET", "/library", as_rider(), None).await.0, StatusCode::UNAUTHORIZED);
+        assert_eq!(call(&app, "GET", "/library", bearer(), None).await.0, StatusCode::UNAUTHORIZED);
+        let credentials = serde_json::json!({ "email": "rider@example.com", "password": "old-password" });
+        assert_eq!(post(&app, "/login", credentials.clone()).await, StatusCode::FORBIDDEN);
+        
+        assert_eq!(call(&app, "POST", &rider_uri("enable"), as_admin(), None).await.0, StatusCode::OK);
+        assert_eq!(call(&app, "GET", "/library", bearer(), None).await.0, StatusCode::OK);
+        assert_eq!(post(&app, "/login", credentials).await, StatusCode::OK);
+        
+        let events: Vec<String> = get_audit_entries(&pool, 20).await.unwrap().into_iter().map(|e| e.event).collect();
+        for event in ["user.role_changed", "user.disabled", "user.enabled", "route.published"] {
+            assert!(events.iter().any(|e| e == event), "missing {}", event);
+        }
+    }
+}
+
+mod totp_tests {
+    use curvematch_backend::auth::totp::{base32_decode, base32_encode, code_at, verify_code};
+    
+    #[test]
+    fn test_base32_round_trip() {
+        assert_eq!(base32_encode(b"foobar"), "MZXW6YTBOI");
+        assert_eq!(base32_decode("mzxw6ytboi======").unwrap(), b"foobar");
+        assert!(base32_decode("MZXW1").is_none());
+    }
+    
+    #[test]
+    fn test_rfc_6238_vectors() {
+        // RFC 6238 appendix B, SHA-1, truncated to six digits
+        let secret = b"12345678901234567890";
+        assert_eq!(code_at(secret, 59 / 30), 287082);
+        assert_eq!(code_at(secret, 1111111109 / 30), 81804);
+        assert_eq!(code_at(secret, 1234567890 / 30), 5924);
+        
+        let encoded = base32_encode(secret);
+        assert_eq!(verify_code(&encoded, "081804", 1111111109, None), Some(1111111109 / 30));
+        assert_eq!(verify_code(&encoded, "081 804", 1111111109 + 30, None), Some(1111111109 / 30));
+        assert_eq!(verify_code(&encoded, "081804", 1111111109 + 90, None), None);
+        assert_eq!(verify_code(&encoded, "081804", 1111111109, Some(1111111109 / 30)), None);
+    }
+}
+
+mod login_security_tests {
+    use curvematch_backend::auth::password::PasswordPolicy;
+    use curvematch_backend::auth::throttle::LoginThrottle;
+    use curvematch_backend::config::{LoginConfig, PasswordConfig};
+    use curvematch_backend::error::AppError;
+    use std::net::IpAddr;
+    
+    #[test]
+    fn test_breached_passwords_rejected() {
+        // "password1" as plain text and "letmein123" as a Pwned Passwords line
+        let policy = PasswordPolicy::new(
+            &PasswordConfig::default(),
+            ["password1", "", "E286977B13F1A89E20D0459207545D15FE1EBA08:12"],
+        );
+        
+        assert!(policy.check("password1", &[]).is_err());
+        assert!(policy.check("letmein123", &[]).is_err());
+        assert!(policy.check("Password1", &[]).is_ok());
+        assert!(policy.check("curvy-roads-ahead", &[]).is_ok());
+        assert!(policy.check("rider@example.com", &["rider@example.com"]).is_err());
+        assert!(policy.check(&"x".repeat(73), &[]).is_err());
+    }
+    
+    #[test]
+    fn test_throttle_backs_off_per_account_and_address() {
+        let throttle = LoginThrottle::new(LoginConfig {
+            max_attempts_per_account: 3,
+            max_attempts_per_ip: 5,
+            ..LoginConfig::default()
+        });
+        let ip: IpAddr = "203.0.113.7".parse().unwrap();
+        
+        assert!(throttle.record_failure("a@example.com", Some(ip)).is_none());
+        assert!(throttle.record_failure("A@example.com", Some(ip)).is_none());
+        let lockout = throttle.record_failure("a@example.com", Some(ip)).unwrap();
+        assert_eq!(lockout.as_secs(), 30);
+        assert!(matches!(throttle.check("a@example.com", None), Err(AppError::TooManyRequests(_))));
+        
+        // Other accounts from the same address keep working until its limit
+        assert!(throttle.check("b@example.com", Some(ip)).is_ok());
+        throttle.record_failure("b@example.com", Some(ip));
+        assert!(throttle.record_failure("c@example.com", Some(ip)).is_some());
+        assert!(throttle.check("d@example.com", Some(ip)).is_err());
+        assert!(throttle.check("d@example.com", None).is_ok());
+        
+        throttle.record_success("a@example.com");
+        assert!(throttle.check("a@example.com", None).is_ok());
+    }
+}
+
+mod oidc_tests {
+    use axum::body::Body;
+    use axum::extract::{Form, State};
+    use axum::http::{header, Request, StatusCode};
+    use axum::response::{IntoResponse, Response};
+    use axum::routing::{get, post};
+    use axum::{Json, Router};
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+    use curvematch_backend::config::{Config, OidcProviderConfig};
+    use curvematch_backend::auth::totp::{base32_encode, code_at, TOTP_PERIOD_SECS};
+    use curvematch_backend::db::queries::users::{
+        enable_totp, find_user_by_email, mark_email_verified, set_pending_totp_secret,
+    };
+    use jsonwebtoken::{Algorithm, EncodingKey, Header};
+    use sha2::{Digest, Sha256};
+    use sqlx::SqlitePool;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use tower::ServiceExt;
+    use super::account_tests::signup;
+    use super::fixtures::test_app_with_config;
+    
+    const CLIENT_ID: &str = "curvematch-test";
+    const KEY_MODULUS: &str = "tO3ymUU4tpYGxD_URoGvYNLFCoCEdnXzvQz4iXk6NNytrydAvaDJe6wyWDX2zEA72-pAmgbiNBu1fX6WGQAdA8EipvVZ4mgVkMtESzhZ_OLSwsM6SDJVHJHUkGcpJB6esPqIhb07_7jVfMisGdblChfhhVgwUpg50KZlqDQTy7plKcKQ_pqk5pbfEdlxaGN1EBDgDGBgxkicgqaCN8oJmme9XuXKtmOnJlwF0_HJ8psTSQVSHxVa3MM4aFs5qQyjnPB9cd-54Kz5x8wanN-UcX7DbsY9ojZ-kokIef3Yfr6CJs8H8H0EZjUNmmm1i1PaznVAzHYYhmBeqPAV43EcSQ";
+    
+    /// An authorization code the mock provider will redeem
+    struct Grant {
+        code_challenge: String,
+        claims: serde_json::Value,
+    }
+    
+    #[derive(Clone)]
+    struct MockIdp {
+        issuer: String,
+        grants: Arc<Mutex<HashMap<String, Grant>>>,
+    }
+    
+    async fn discovery(State(idp): State<MockIdp>) -> Json<serde_json::Value> {
+        Json(serde_json::json!({
+            "issuer": idp.issuer,
+            "authorization_endpoint": format!("{}/authorize", idp.issuer),
+            "token_endpoint": format!("{}/token", idp.issuer),
+            "jwks_uri": format!("{}/jwks", idp.issuer),
+        }))
+    }
+    
+    async fn jwks() -> Json<serde_json::Value> {
+        Json(serde_json::json!({
+            "keys": [{ "kty": "RSA", "kid": "test-key", "use": "sig", "alg": "RS256", "n": KEY_MODULUS, "e": "AQAB" }],
+        }))
+    }
+    
+    async fn token(State(idp): State<MockIdp>, Form(form): Form<HashMap<String, String>>) -> Response {
+        let grant = idp.grants.lock().unwrap().remove(&form["code"]);
+        let verifier = form.get("code_verifier").cloned().unwrap_or_default();
+        let grant = match grant {
+            Some(grant) if URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes())) == grant.code_challenge => grant,
+            _ => return (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": "invalid_grant" }))).into_response(),
+        };
+        
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some("test-key".to_string());
+        let key = EncodingKey::from_rsa_pem(include_bytes!("test_data/mock_idp_key.pem")).unwrap();
+        let id_token = jsonwebtoken::encode(&header, &grant.claims, &key).unwrap();
+        Json(serde_json::json!({ "access_token": "opaque", "token_type": "Bearer", "id_token": id_token })).into_response()
+    }
+    
+    async fn start_idp() -> MockIdp {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let idp = MockIdp {
+            issuer: format!("http://{}", listener.local_addr().unwrap()),
+            grants: Arc::new(Mutex::new(HashMap::new())),
+        };
+        let app = Router::new()
+            .route("/.well-known/openid-configuration", get(discovery))
+            .route("/jwks", get(jwks))
+            .route("/token", post(token))
+            .with_state(idp.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        idp
+    }
+    
+    async fn app_with_idp(name: &str, idp: &MockIdp) -> (Router, SqlitePool) {
+        let mut config = Config::default();
+        config.oidc.providers.insert("mock".to_string(), OidcProviderConfig {
+            display_name: "Mock".to_string(),
+            issuer: idp.issuer.clone(),
+            client_id: CLIENT_ID.to_string(),
+            client_secret: Some("secret".to_string()),
+            redirect_uri: "http://localhost:3000/api/oidc/mock/callback".to_string(),
+            scopes: vec!["openid".to_string(), "email".to_string()],
+        });
+        let (app, pool, _) = test_app_with_config(name, config).await;
+        (app, pool)
+    }
+    
+    fn location(response: &Response) -> String {
+        response.headers()[header::LOCATION].to_str().unwrap().to_string()
+    }
+    
+    /// Start a sign-in, have the mock provider authorize it with the given
+    /// claims, and follow the callback. `tamper` can change the callback query.
+    async fn sign_in(
+        app: &Router,
+        idp: &MockIdp,
+        claims: serde_json::Value,
+        tamper: impl FnOnce(&mut HashMap<String, String>),
+    ) -> Response {
+        let request = Request::builder().uri("/oidc/mock/login").body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        let cookie = response.headers()[header::SET_COOKIE].to_str().unwrap().split(';').next().unwrap().to_string();
+        
+        let authorize = reqwest::Url::parse(&location(&response)).unwrap();
+        assert_eq!(authorize.path(), "/authorize");
+        let params: HashMap<String, String> = authorize.query_pairs().into_owned().collect();
+        assert_eq!(params["client_id"], CLIENT_ID);
+        assert_eq!(params["code_challenge_method"], "S256");
+        
+        let mut claims = claims;
+        let now = chrono::Utc::now().timestamp();
+        claims["iss"] = idp.issuer.clone().into();
+        claims["aud"] = CLIENT_ID.into();
+        claims["iat"] = now.into();
+        claims["exp"] = (now + 300).into();
+        if claims.get("nonce").is_none() {
+            claims["nonce"] = params["nonce"].clone().into();
+        }
+        let code = uuid::Uuid::new_v4().to_string();
+        idp.grants.lock().unwrap().insert(code.clone(), Grant {
+            code_challenge: params["code_challenge"].clone(),
+            claims,
+        });
+        
+        let mut query = HashMap::from([("code".to_string(), code), ("state".to_string(), params["state"].clone())]);
+        tamper(&mut query);
+        let query: Vec<String> = query.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+        let request = Request::builder()
+            .uri(format!("/oidc/mock/callback?{}", query.join("&")))
+            .header(header::COOKIE, cookie)
+            .body(Body::empty())
+            .unwrap();
+        app.clone().oneshot(request).await.unwrap()
+    }
+    
+    #[tokio::test]
+    async fn test_sign_in_creates_then_reuses_account() {
+        let idp = start_idp().await;
+        let (app, pool) = app_with_idp("oidc-create", &idp).await;
+        let claims = || serde_json::json!({
+            "sub": "user-1",
+            "email": "new@example.com",
+            "email_verified": "true",
+            "preferred_username": "new rider",
+        });
+        
+        let response = sign_in(&app, &idp, claims(), |_| {}).await;
+        assert_eq!(location(&response), "http://localhost:5173/login?oidc=success");
+        let cookies: Vec<&str> = response.headers().get_all(header::SET_COOKIE).iter().map(|v| v.to_str().unwrap()).collect();
+        assert!(cookies.iter().any(|c| c.starts_with("token=") && !c.starts_with("token=;")));
+        assert!(cookies.iter().any(|c| c.starts_with("oidc_login=;")));
+        
+        let user = find_user_by_email(&pool, "new@example.com").await.unwrap().unwrap();
+        assert_eq!(user.username, "new-rider");
+        assert!(user.email_verified_at.is_some());
+        
+        // The identity is linked; a changed email at the provider doesn't fork the account
+        let response = sign_in(&app, &idp, serde_json::json!({ "sub": "user-1", "email": "renamed@example.com" }), |_| {}).await;
+        assert_eq!(location(&response), "http://localhost:5173/login?oidc=success");
+        assert!(find_user_by_email(&pool, "renamed@example.com").await.unwrap().is_none());
+    }
+    
+    #[tokio::test]
+    async fn test_sign_in_links_only_verified_emails() {
+        let idp = start_idp().await;
+        let (app, pool) = app_with_idp("oidc-link", &idp).await;
+        assert_eq!(signup(&app, "rider@example.com").await, StatusCode::OK);
+        let existing = find_user_by_email(&pool, "rider@example.com").await.unwrap().unwrap();
+        
+        let claims = |verified: bool| serde_json::json!({
+            "sub": "user-2",
+            "email": "rider@example.com",
+            "email_verified": verified,
+        });
+        let response = sign_in(&app, &idp, claims(false), |_| {}).await;
+        assert_eq!(location(&response), "http://localhost:5173/login?error=oidc");
+        
+        // Nor to a local account that never confirmed the address
+        let response = sign_in(&app, &idp, claims(true), |_| {}).await;
+        assert_eq!(location(&response), "http://localhost:5173/login?error=oidc");
+        
+        mark_email_verified(&pool, existing.id).await.unwrap();
+        let response = sign_in(&app, &idp, claims(true), |_| {}).await;
+        assert_eq!(location(&response), "http://localhost:5173/login?oidc=success");
+        let linked: (i64,) = sqlx::query_as("SELECT user_id FROM user_identities WHERE subject = 'user-2'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(linked.0, existing.id);
+    }
+    
+    #[tokio::test]
+    async fn test_two_factor_token_is_not_put_in_the_url() {
+        let idp = start_idp().await;
+        let (app, pool) = app_with_idp("oidc-mfa", &idp).await;
+        let claims = || serde_json::json!({ "sub": "user-4", "email": "two@example.com", "email_verified": true });
+        assert_eq!(location(&sign_in(&app, &idp, claims(), |_| {}).await), "http://localhost:5173/login?oidc=success");
+        
+        let user = find_user_by_email(&pool, "two@example.com").await.unwrap().unwrap();
+        let secret = b"12345678901234567890";
+        set_pending_totp_secret(&pool, user.id, &base32_encode(secret)).await.unwrap();
+        enable_totp(&pool, user.id).await.unwrap();
+        
+        let response = sign_in(&app, &idp, claims(), |_| {}).await;
+        assert_eq!(location(&response), "http://localhost:5173/login?mfa=required");
+        let cookies: Vec<&str> = response.headers().get_all(header::SET_COOKIE).iter().map(|v| v.to_str().unwrap()).collect();
+        assert!(!cookies.iter().any(|c| c.starts_with("token=")));
+        let mfa_cookie = cookies.iter().find(|c| c.starts_with("mfa_token=")).unwrap();
+        assert!(mfa_cookie.contains("HttpOnly"));
+        
+        let second_step = |cookie: Option<&str>| {
+            let step = chrono::Utc::now().timestamp() / TOTP_PERIOD_SECS;
+            let body = serde_json::json!({ "code": format!("{:06}", code_at(secret, step)) });
+            let mut request = Request::builder()
+                .method("POST")
+                .uri("/login/mfa")
+                .header(header::CONTENT_TYPE, "application/json");
+            if let Some(cookie) = cookie {
+                request = request.header(header::COOKIE, cookie);
+            }
+            request.body(Body::from(body.to_string())).unwrap()
+        };
+        let response = app.clone().oneshot(second_step(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        
+        let cookie = mfa_cookie.split(';').next().unwrap();
+        let response = app.clone().oneshot(second_step(Some(cookie))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let cookies: Vec<&str> = response.headers().get_all(header::SET_COOKIE).iter().map(|v| v.to_str().unwrap()).collect();
+        assert!(cookies.iter().any(|c| c.starts_with("token=") && !c.starts_with("token=;")));
+        assert!(cookies.iter().any(|c| c.starts_with("mfa_token=;")));
+    }
+    
+    #[tokio::test]
+    async fn test_callback_rejects_forged_requests() {
+        let idp = start_idp().await;
+        let (app, pool) = app_with_idp("oidc-forged", &idp).await;
+        let claims = || serde_json::json!({ "sub": "user-3", "email": "mallory@example.com", "email_verified": true });
+        let failed = "http://localhost:5173/login?error=oidc";
+        
+        let response = sign_in(&app, &idp, claims(), |query| {
+            query.insert("state".to_string(), "forged".to_string());
+        })
+        .await;
+        assert_eq!(location(&response), failed);
+        
+        let response = sign_in(&app, &idp, claims(), |query| {
+            query.insert("code".to_string(), "unknown".to_string());
+        })
+        .await;
+        assert_eq!(location(&response), failed);
+        
+        let mut replayed = claims();
+        replayed["nonce"] = "from-another-login".into();
+        assert_eq!(location(&sign_in(&app, &idp, replayed, |_| {}).await), failed);
+        
+        // Without the cookie from /login the callback can't be completed
+        let request = Request::builder()
+            .uri("/oidc/mock/callback?code=x&state=y")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(location(&app.clone().oneshot(request).await.unwrap()), failed);
+        
+        assert!(find_user_by_email(&pool, "mallory@example.com").await.unwrap().is_none());
+    }
+}
+
+mod csrf_tests {
+    use axum::body::Body;
+    use axum::http::{header, Request, StatusCode};
+    use axum::{middleware, Router};
+    use curvematch_backend::api;
+    use curvematch_backend::auth::api_keys::{issue_api_key, Scope};
+    use curvematch_backend::auth::csrf::{self, CSRF_COOKIE};
+    use curvematch_backend::auth::jwt::create_token;
+    use curvematch_backend::config::{AuthConfig, Config};
+    use curvematch_backend::db::queries::users::create_user;
+    use tower::ServiceExt;
+    use super::fixtures::test_state;
+    
+    fn request(method: &str, uri: &str, headers: &[(&str, String)], body: serde_json::Value) -> Request<Body> {
+        let mut builder = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json");
+        for (name, value) in headers {
+            builder = builder.header(*name, value);
+        }
+        builder.body(Body::from(body.to_string())).unwrap()
+    }
+    
+    fn signup_body() -> serde_json::Value {
+        serde_json::json!({
+            "email": "rider@example.com",
+            "username": "rider",
+            "password": "old-password",
+            "confirmPassword": "old-password",
+        })
+    }
+    
+    #[test]
+    fn test_tokens_are_signed() {
+        let config = AuthConfig::default();
+        let token = csrf::issue_token(&config, None);
+        assert!(csrf::is_valid_token(&config, &token, None));
+        assert_ne!(token, csrf::issue_token(&config, None));
+        
+        let (nonce, _) = token.split_once('.').unwrap();
+        assert!(!csrf::is_valid_token(&config, &format!("{}.{}", nonce, "0".repeat(64)), None));
+        assert!(!csrf::is_valid_token(&config, nonce, None));
+        let other = AuthConfig { jwt_secret: "another-secret".to_string(), ..AuthConfig::default() };
+        assert!(!csrf::is_valid_token(&other, &token, None));
+    }
+    
+    #[test]
+    fn test_tokens_are_bound_to_the_session() {
+        let config = AuthConfig::default();
+        let token = csrf::issue_token(&config, Some("session-a"));
+        assert!(csrf::is_valid_token(&config, &token, Some("session-a")));
+        assert!(!csrf::is_valid_token(&config, &token, Some("session-b")));
+        assert!(!csrf::is_valid_token(&config, &token, None));
+        assert!(!csrf::is_valid_token(&config, &csrf::issue_token(&config, None), Some("session-a")));
+    }
+    
+    #[tokio::test]
+    async fn test_token_from_another_session_is_rejected() {
+        let (state, _) = test_state("csrf-session", Config::default()).await;
+        let app: Router = api::routes(state.clone())
+            .layer(middleware::from_fn_with_state(state.clone(), csrf::protect))
+            .with_state(state.clone());
+        let session_cookie = |session: &str| {
+            let (token, _) = create_token(&state.config.auth, 1, session).unwrap();
+            format!("token={}", token)
+        };
+        
+        // An attacker fetches a token with their own session and tosses it
+        // into the victim's browser
+        let attacker = session_cookie("attacker-session");
+        let response = app.clone()
+            .oneshot(request("GET", "/csrf", &[("cookie", attacker)], serde_json::Value::Null))
+            .await
+            .unwrap();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let tossed = serde_json::from_slice::<serde_json::Value>(&bytes).unwrap()["csrfToken"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        
+        let rename = serde_json::json!({ "name": "Renamed" });
+        let victim = |token: &str| [
+            ("cookie", format!("{}; {}={}", session_cookie("victim-session"), CSRF_COOKIE, token)),
+            ("x-csrf-token", token.to_string()),
+        ];
+        let response = app.clone().oneshot(request("PATCH", "/library/1", &victim(&tossed), rename.clone())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let anonymous = csrf::issue_token(&state.config.auth, None);
+        let response = app.clone().oneshot(request("PATCH", "/library/1", &victim(&anonymous), rename.clone())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        
+        // Reaches the handler, which finds no such route
+        let own = csrf::issue_token(&state.config.auth, Some("victim-session"));
+        let response = app.oneshot(request("PATCH", "/library/1", &victim(&own), rename)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+    
+    #[tokio::test]
+    async fn test_state_changing_requests_need_matching_token() {
+        let (state, _) = test_state("csrf", Config::default()).await;
+        let app: Router = api::routes(state.clone())
+            .layer(middleware::from_fn_with_state(state.clone(), csrf::protect))
+            .with_state(state.clone());
+        
+        // No token, or a token that wasn't issued by the server
+        let response = app.clone().oneshot(request("POST", "/signup", &[], signup_body())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let forged = "abc.def".to_string();
+        let headers = [("cookie", format!("{}={}", CSRF_COOKIE, forged)), ("x-csrf-token", forged)];
+        let response = app.clone().oneshot(request("POST", "/signup", &headers, signup_body())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        
+        let response = app.clone().oneshot(request("GET", "/csrf", &[], serde_json::Value::Null)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let set_cookie = response.headers()[header::SET_COOKIE].to_str().unwrap().to_string();
+        assert!(!set_cookie.contains("HttpOnly"));
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let token = serde_json::from_slice::<serde_json::Value>(&bytes).unwrap()["csrfToken"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        assert!(set_cookie.starts_with(&format!("{}={};", CSRF_COOKIE, token)));
+        let cookie = ("cookie", format!("{}={}", CSRF_COOKIE, token));
+        
+        // The same token is handed out again while it's valid
+        let response = app.clone().oneshot(request("GET", "/csrf", std::slice::from_ref(&cookie), serde_json::Value::Null)).await.unwrap();
+        assert!(response.headers().get(header::SET_COOKIE).is_none());
+        
+        let other = csrf::issue_token(&state.config.auth, None);
+        let mismatched = [cookie.clone(), ("x-csrf-token", other)];
+        let response = app.clone().oneshot(request("POST", "/signup", &mismatched, signup_body())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        
+        let matching = [cookie, ("x-csrf-token", token)];
+        let response = app.clone().oneshot(request("POST", "/signup", &matching, signup_body())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        
+        // API keys are exempt unless session cookies ride along
+        let user = create_user(&state.pool, "script@example.com", "script", "salt", "hash").await.unwrap();
+        let key = issue_api_key(&state.pool, user.id, "CI", &[Scope::LibraryWrite]).await.unwrap().key;
+        let rename = serde_json::json!({ "name": "Renamed" });
+        let bearer = ("authorization", format!("Bearer {}", key));
+        // Reaches the handler, which finds no such route
+        let response = app.clone().oneshot(request("PATCH", "/library/1", std::slice::from_ref(&bearer), rename.clone())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let with_session = [bearer, ("cookie", "token=stolen".to_string())];
+        let response = app.oneshot(request("PATCH", "/library/1", &with_session, rename)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+}
@@ -1,6 +1,6 @@
 ﻿// src/api/client.ts - Fixed version that doesn't interfere with FormData
 
-import axios, { type AxiosInstance, type AxiosError } from 'axios';
+import axios, { type AxiosInstance, type AxiosError, type InternalAxiosRequestConfig } from 'axios';
 
 // Create axios instance with minimal configuration
 export const apiClient: AxiosInstance = axios.create({
@@ -29,17 +29,92 @@ apiClient.interceptors.request.use(
   }
 );
 
+// State-changing requests repeat the CSRF cookie in a header. The cookie
+// belongs to the API origin, so the token is fetched rather than read.
+let csrfToken: Promise<string> | null = null;
+
+const getCsrfToken = (): Promise<string> => {
+  if (!csrfToken) {
+    csrfToken = apiClient
+      .get('/api/csrf')
+      .then((response) => response.data.csrfToken as string)
+      .catch((error) => {
+        csrfToken = null;
+        throw error;
+      });
+  }
+  return csrfToken;
+};
+
+const SAFE_METHODS = ['get', 'head', 'options'];
+
+// Tokens are bound to the session, so these need a fresh one afterwards
+const SESSION_ENDPOINTS = ['/api/login', '/api/login/mfa', '/api/signup', '/api/logout'];
+
+apiClient.interceptors.request.use(async (config) => {
+  if (!SAFE_METHODS.includes((config.method ?? 'get').toLowerCase())) {
+    config.headers['X-CSRF-Token'] = await getCsrfToken();
+  }
+  return config;
+});
+
+// Access tokens are short-lived; concurrent 401s share a single refresh
+let refreshRequest: Promise<void> | null = null;
+
+const refreshSession = (): Promise<void> => {
+  if (!refreshRequest) {
+    refreshRequest = apiClient
+      .post('/api/refresh', undefined, { _skipRefresh: true } as InternalAxiosRequestConfig)
+      .then(() => undefined)
+      .finally(() => {
+        refreshRequest = null;
+      });
+  }
+  return refreshRequest;
+};
+
+type RetryableConfig = InternalAxiosRequestConfig & {
+  _retried?: boolean;
+  _skipRefresh?: boolean;
+  _csrfRetried?: boolean;
+};
+
+const isCsrfError = (error: AxiosError): boolean =>
+  (error.response?.data as { error?: string } | undefined)?.error === 'Invalid CSRF token';
+
 // Response interceptor
 apiClient.interceptors.response.use(
   (response) => {
+    if (SESSION_ENDPOINTS.includes(response.config.url ?? '')) {
+      csrfToken = null;
+    }
     return response;
   },
-  (error: AxiosError) => {
+  async (error: AxiosError) => {
+    const config = error.config as RetryableConfig | undefined;
+
     // Global error handling
     if (error.response?.status === 401) {
+      // Try the refresh token once before giving up on the session
+      if (config && !config._retried && !config._skipRefresh) {
+        config._retried = true;
+        try {
+          await refreshSession();
+          return apiClient(config);
+        } catch {
+          // Fall through to the login redirect
+        }
+      }
       // Unauthorized - redirect to login
       window.location.href = '/login';
     } else if (error.response?.status === 403) {
+      // The CSRF cookie may have expired with the browser session; fetch a
+      // fresh token and try once more
+      if (config && isCsrfError(error) && !config._csrfRetried) {
+        config._csrfRetried = true;
+        csrfToken = null;
+        return apiClient(config);
+      }
       // Forbidden
       console.error('Access forbidden');
     } else if (error.response?.status === 500) {
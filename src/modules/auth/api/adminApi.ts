@@ -0,0 +1,46 @@
+import { apiClient } from '../../../api/client';
+import type { Role, User } from './authApi';
+
+export interface AdminUser extends User {
+  disabledAt: string | null;
+}
+
+export interface PlatformStats {
+  users: number;
+  curators: number;
+  admins: number;
+  disabledUsers: number;
+  savedRoutes: number;
+  publicRoutes: number;
+  indexedRoutes: number;
+  activeSessions: number;
+  activeApiKeys: number;
+}
+
+const adminEndpoint = '/api/admin';
+
+export const listUsers = async (limit = 50, offset = 0): Promise<AdminUser[]> => {
+  const response = await apiClient.get(`${adminEndpoint}/users`, { params: { limit, offset } });
+  return response.data;
+};
+
+export const setUserRole = async (id: number, role: Role): Promise<AdminUser> => {
+  const response = await apiClient.put(`${adminEndpoint}/users/${id}/role`, { role });
+  return response.data;
+};
+
+// Also signs the user out everywhere
+export const disableUser = async (id: number): Promise<AdminUser> => {
+  const response = await apiClient.post(`${adminEndpoint}/users/${id}/disable`);
+  return response.data;
+};
+
+export const enableUser = async (id: number): Promise<AdminUser> => {
+  const response = await apiClient.post(`${adminEndpoint}/users/${id}/enable`);
+  return response.data;
+};
+
+export const getPlatformStats = async (): Promise<PlatformStats> => {
+  const response = await apiClient.get(`${adminEndpoint}/stats`);
+  return response.data;
+};
@@ -1,17 +1,66 @@
 import { apiClient } from '../../../api/client';
 
+export type Role = 'user' | 'curator' | 'admin';
+
 export interface User {
   id: number;
   email: string;
   username: string;
-  role: string;
+  role: Role;
   createdAt: string;
+  emailVerified: boolean;
+  mfaEnabled: boolean;
 }
 
 export interface AuthResponse {
   user: User;
 }
 
+// Returned by login instead of a session when the account has two-factor on
+export interface MfaChallenge {
+  mfaRequired: true;
+  mfaToken: string;
+}
+
+export interface MfaStatus {
+  enabled: boolean;
+  recoveryCodesRemaining: number;
+}
+
+export interface TotpSetup {
+  secret: string;
+  otpauthUri: string;
+}
+
+export interface Session {
+  id: string;
+  userAgent: string | null;
+  createdAt: string;
+  lastSeenAt: string;
+  expiresAt: string;
+  current: boolean;
+}
+
+export interface IdentityProvider {
+  id: string;
+  displayName: string;
+}
+
+export type ApiKeyScope = 'match:run' | 'library:read' | 'library:write';
+
+export interface ApiKey {
+  id: number;
+  name: string;
+  prefix: string;
+  scopes: ApiKeyScope[];
+  createdAt: string;
+  lastUsedAt: string | null;
+}
+
+export interface CreatedApiKey extends ApiKey {
+  key: string;
+}
+
 export interface SignupData {
   email: string;
   username: string;
@@ -21,7 +70,7 @@ export interface SignupData {
 
 const authEndpoint = '/api';
 
-export const login = async (email: string, password: string): Promise<AuthResponse> => {
+export const login = async (email: string, password: string): Promise<AuthResponse | MfaChallenge> => {
   const response = await apiClient.post(`${authEndpoint}/login`, {
     email,
     password,
@@ -29,6 +78,30 @@ export const login = async (email: string, password: string): Promise<AuthRespon
   return response.data;
 };
 
+// Accepts a code from the authenticator app or a recovery code
+// Without a token the server uses the one left in a cookie by an identity provider
+export const loginWithMfa = async (mfaToken: string | null, code: string): Promise<AuthResponse> => {
+  const response = await apiClient.post(`${authEndpoint}/login/mfa`, {
+    mfaToken: mfaToken ?? undefined,
+    code,
+  });
+  return response.data;
+};
+
+export const getCurrentUser = async (): Promise<AuthResponse> => {
+  const response = await apiClient.get(`${authEndpoint}/me`);
+  return response.data;
+};
+
+export const listIdentityProviders = async (): Promise<IdentityProvider[]> => {
+  const response = await apiClient.get(`${authEndpoint}/oidc/providers`);
+  return response.data;
+};
+
+// A full-page navigation, not an XHR: the provider's sign-in page takes over
+export const identityProviderLoginUrl = (id: string): string =>
+  `${apiClient.defaults.baseURL}${authEndpoint}/oidc/${encodeURIComponent(id)}/login`;
+
 export const signup = async (data: SignupData): Promise<AuthResponse> => {
   const response = await apiClient.post(`${authEndpoint}/signup`, {
     email: data.email,
@@ -43,3 +116,78 @@ export const signup = async (data: SignupData): Promise<AuthResponse> => {
 export const logout = async (): Promise<void> => {
   await apiClient.post(`${authEndpoint}/logout`);
 };
+
+// Always resolves, whether or not the address has an account
+export const requestPasswordReset = async (email: string): Promise<void> => {
+  await apiClient.post(`${authEndpoint}/password/forgot`, { email });
+};
+
+export const resetPassword = async (token: string, password: string): Promise<void> => {
+  await apiClient.post(`${authEndpoint}/password/reset`, {
+    token,
+    password,
+    confirmPassword: password,
+  });
+};
+
+export const verifyEmail = async (token: string): Promise<void> => {
+  await apiClient.post(`${authEndpoint}/verify-email`, { token });
+};
+
+export const resendVerificationEmail = async (): Promise<void> => {
+  await apiClient.post(`${authEndpoint}/verify-email/resend`);
+};
+
+export const listSessions = async (): Promise<Session[]> => {
+  const response = await apiClient.get(`${authEndpoint}/sessions`);
+  return response.data;
+};
+
+export const revokeSession = async (id: string): Promise<void> => {
+  await apiClient.delete(`${authEndpoint}/sessions/${id}`);
+};
+
+// Signs out every device, including this one
+export const revokeAllSessions = async (): Promise<void> => {
+  await apiClient.delete(`${authEndpoint}/sessions`);
+};
+
+export const getMfaStatus = async (): Promise<MfaStatus> => {
+  const response = await apiClient.get(`${authEndpoint}/mfa`);
+  return response.data;
+};
+
+export const setupTotp = async (): Promise<TotpSetup> => {
+  const response = await apiClient.post(`${authEndpoint}/mfa/totp/setup`);
+  return response.data;
+};
+
+// Returns the recovery codes, which are only shown this once
+export const activateTotp = async (code: string): Promise<string[]> => {
+  const response = await apiClient.post(`${authEndpoint}/mfa/totp/activate`, { code });
+  return response.data.recoveryCodes;
+};
+
+export const disableTotp = async (password: string, code: string): Promise<void> => {
+  await apiClient.post(`${authEndpoint}/mfa/totp/disable`, { password, code });
+};
+
+export const regenerateRecoveryCodes = async (code: string): Promise<string[]> => {
+  const response = await apiClient.post(`${authEndpoint}/mfa/recovery-codes`, { code });
+  return response.data.recoveryCodes;
+};
+
+export const listApiKeys = async (): Promise<ApiKey[]> => {
+  const response = await apiClient.get(`${authEndpoint}/api-keys`);
+  return response.data;
+};
+
+// The full key is only returned here; store it before closing the dialog
+export const createApiKey = async (name: string, scopes: ApiKeyScope[]): Promise<CreatedApiKey> => {
+  const response = await apiClient.post(`${authEndpoint}/api-keys`, { name, scopes });
+  return response.data;
+};
+
+export const revokeApiKey = async (id: number): Promise<void> => {
+  await apiClient.delete(`${authEndpoint}/api-keys/${id}`);
+};
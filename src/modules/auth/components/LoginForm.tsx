@@ -1,9 +1,10 @@
-import React, { useState } from 'react';
+import React, { useEffect, useState } from 'react';
 import { useForm } from 'react-hook-form';
 import { z } from 'zod';
 import { zodResolver } from '@hookform/resolvers/zod';
-import { useNavigate } from 'react-router-dom';
+import { useNavigate, useSearchParams } from 'react-router-dom';
 import { useAuth } from '../hooks/useAuth';
+import { identityProviderLoginUrl, listIdentityProviders, type IdentityProvider } from '../api/authApi';
 import Input from '../../common/components/Input';
 import Button from '../../common/components/Button';
 import GlassPanel from '../../common/components/GlassPanel';
@@ -17,8 +18,29 @@ type LoginFormData = z.infer<typeof loginSchema>;
 
 const LoginForm: React.FC = () => {
   const navigate = useNavigate();
-  const { login } = useAuth();
-  const [error, setError] = useState<string | null>(null);
+  const [searchParams] = useSearchParams();
+  const { login, loginWithMfa, completeExternalLogin } = useAuth();
+  const [error, setError] = useState<string | null>(
+    searchParams.get('error') === 'oidc' ? 'Sign-in with the identity provider failed' : null
+  );
+  // After an identity provider the pending token is in a cookie instead
+  const [mfaPending, setMfaPending] = useState(searchParams.get('mfa') === 'required');
+  const [mfaToken, setMfaToken] = useState<string | null>(null);
+  const [code, setCode] = useState('');
+  const [isVerifying, setIsVerifying] = useState(false);
+  const [providers, setProviders] = useState<IdentityProvider[]>([]);
+
+  useEffect(() => {
+    listIdentityProviders().then(setProviders).catch(() => setProviders([]));
+  }, []);
+
+  // Back from an identity provider with a session cookie already set
+  useEffect(() => {
+    if (searchParams.get('oidc') !== 'success') return;
+    completeExternalLogin()
+      .then(() => navigate('/match'))
+      .catch(() => setError('Sign-in with the identity provider failed'));
+  }, [searchParams, completeExternalLogin, navigate]);
 
   const {
     register,
@@ -31,13 +53,79 @@ const LoginForm: React.FC = () => {
   const onSubmit = async (data: LoginFormData) => {
     try {
       setError(null);
-      await login(data.email, data.password);
+      const pendingToken = await login(data.email, data.password);
+      if (pendingToken) {
+        setMfaToken(pendingToken);
+        setMfaPending(true);
+        return;
+      }
       navigate('/match');
     } catch (err) {
       setError('Invalid email or password');
     }
   };
 
+  const onSubmitCode = async (event: React.FormEvent) => {
+    event.preventDefault();
+    if (!mfaPending) return;
+    setIsVerifying(true);
+    try {
+      setError(null);
+      await loginWithMfa(mfaToken, code);
+      navigate('/match');
+    } catch (err) {
+      setError('Invalid authentication code');
+    } finally {
+      setIsVerifying(false);
+    }
+  };
+
+  if (mfaPending) {
+    return (
+      <GlassPanel className="w-full max-w-md mx-auto p-8">
+        <form onSubmit={onSubmitCode} className="space-y-6">
+          <h2 className="text-2xl font-bold text-center">Two-factor authentication</h2>
+
+          {error && (
+            <div className="text-red-600 text-sm text-center">{error}</div>
+          )}
+
+          <Input
+            label="Authentication code"
+            value={code}
+            onChange={(e) => setCode(e.target.value)}
+            autoComplete="one-time-code"
+            autoFocus
+            placeholder="6-digit code or a recovery code"
+          />
+
+          <Button
+            type="submit"
+            isLoading={isVerifying}
+            className="w-full"
+          >
+            Verify
+          </Button>
+
+          <p className="text-center text-sm">
+            <button
+              type="button"
+              onClick={() => {
+                setMfaToken(null);
+                setMfaPending(false);
+                setCode('');
+                setError(null);
+              }}
+              className="text-accent-1 hover:underline"
+            >
+              Back to login
+            </button>
+          </p>
+        </form>
+      </GlassPanel>
+    );
+  }
+
   return (
     <GlassPanel className="w-full max-w-md mx-auto p-8">
       <form onSubmit={handleSubmit(onSubmit)} className="space-y-6">
@@ -71,6 +159,21 @@ const LoginForm: React.FC = () => {
           Login
         </Button>
 
+        {providers.length > 0 && (
+          <div className="space-y-2">
+            <div className="text-center text-sm text-gray-500">or</div>
+            {providers.map((provider) => (
+              <a
+                key={provider.id}
+                href={identityProviderLoginUrl(provider.id)}
+                className="block w-full text-center py-2 px-4 rounded-lg border border-white/30 hover:bg-white/10"
+              >
+                Continue with {provider.displayName}
+              </a>
+            ))}
+          </div>
+        )}
+
         <p className="text-center text-sm">
           Don't have an account?{' '}
           <a href="/signup" className="text-accent-1 hover:underline">
@@ -11,7 +11,7 @@ import GlassPanel from '../../common/components/GlassPanel';
 const signupSchema = z.object({
   email: z.string().email('Invalid email address'),
   username: z.string().min(3, 'Username must be at least 3 characters'),
-  password: z.string().min(6, 'Password must be at least 6 characters'),
+  password: z.string().min(8, 'Password must be at least 8 characters'),
   confirmPassword: z.string(),
   interest: z.string().optional(),
 }).refine((data) => data.password === data.confirmPassword, {
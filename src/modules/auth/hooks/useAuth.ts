@@ -13,10 +13,36 @@ export const useAuth = () => {
   const { user, isAuthenticated, setUser, clearAuth } = useAuthStore();
   const [isLoading, setIsLoading] = useState(false);
 
-  const login = useCallback(async (email: string, password: string) => {
+  // Resolves to an MFA token when a second factor is still needed
+  const login = useCallback(async (email: string, password: string): Promise<string | null> => {
     setIsLoading(true);
     try {
       const response = await authApi.login(email, password);
+      if ('mfaRequired' in response) {
+        return response.mfaToken;
+      }
+      setUser(response.user);
+      return null;
+    } finally {
+      setIsLoading(false);
+    }
+  }, [setUser]);
+
+  const loginWithMfa = useCallback(async (mfaToken: string | null, code: string) => {
+    setIsLoading(true);
+    try {
+      const response = await authApi.loginWithMfa(mfaToken, code);
+      setUser(response.user);
+    } finally {
+      setIsLoading(false);
+    }
+  }, [setUser]);
+
+  // After an identity provider redirects back with a session cookie
+  const completeExternalLogin = useCallback(async () => {
+    setIsLoading(true);
+    try {
+      const response = await authApi.getCurrentUser();
       setUser(response.user);
     } finally {
       setIsLoading(false);
@@ -48,6 +74,8 @@ export const useAuth = () => {
     isAuthenticated,
     isLoading,
     login,
+    loginWithMfa,
+    completeExternalLogin,
     logout,
     signup,
   };
@@ -1,4 +1,5 @@
 import { apiClient } from '../../../api/client';
+import type { Twistiness } from '../../matching/api/matchingApi';
 
 export interface SavedRoute {
   id: number;
@@ -9,6 +10,8 @@ export interface SavedRoute {
   elevationGain: number;
   gainPerKm: number;
   curveScore: number;
+  // Missing until the route has been measured
+  twistiness?: Twistiness;
   matchPercentage: number;
   geometry: any;
   elevationProfile: number[];
@@ -28,6 +31,8 @@ export interface UpdateRouteData {
 
 const libraryEndpoint = '/api/library';
 const routeEndpoint = '/api/route';
+const publicRoutesEndpoint = '/api/public-routes';
+const curationEndpoint = '/api/curation/routes';
 
 export const getLibrary = async (): Promise<SavedRoute[]> => {
   const response = await apiClient.get(libraryEndpoint);
@@ -64,3 +69,41 @@ export const downloadGPX = async (id: number): Promise<Blob> => {
   });
   return response.data;
 };
+
+export type ExportFormat = 'gpx' | 'geojson' | 'kml' | 'tcx' | 'fit' | 'csv';
+
+export const exportRoute = async (id: number, format: ExportFormat): Promise<Blob> => {
+  const response = await apiClient.get(`${routeEndpoint}/${id}/export`, {
+    params: { format },
+    responseType: 'blob',
+  });
+  return response.data;
+};
+
+// Shared corpus maintained by curators; readable by everyone signed in
+export const getPublicRoutes = async (): Promise<SavedRoute[]> => {
+  const response = await apiClient.get(publicRoutesEndpoint);
+  return response.data;
+};
+
+export const getPublicRoute = async (id: number): Promise<SavedRoute> => {
+  const response = await apiClient.get(`${publicRoutesEndpoint}/${id}`);
+  return response.data;
+};
+
+// Curators only; publishes a route from the curator's own library
+export const publishRoute = async (id: number): Promise<void> => {
+  await apiClient.post(`${curationEndpoint}/${id}/publish`);
+};
+
+export const unpublishRoute = async (id: number): Promise<void> => {
+  await apiClient.delete(`${curationEndpoint}/${id}/publish`);
+};
+
+export const renamePublicRoute = async (id: number, name: string): Promise<void> => {
+  await apiClient.patch(`${curationEndpoint}/${id}`, { name });
+};
+
+export const deletePublicRoute = async (id: number): Promise<void> => {
+  await apiClient.delete(`${curationEndpoint}/${id}`);
+};
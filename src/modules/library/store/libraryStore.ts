@@ -1,5 +1,6 @@
 import { create } from 'zustand';
 import { immer } from 'zustand/middleware/immer';
+import type { Twistiness } from '../../matching/api/matchingApi';
 
 interface SavedRoute {
   id: number;
@@ -10,6 +11,7 @@ interface SavedRoute {
   elevationGain: number;
   gainPerKm: number;
   curveScore: number;
+  twistiness?: Twistiness;
   matchPercentage: number;
   geometry: any;
   elevationProfile: number[];
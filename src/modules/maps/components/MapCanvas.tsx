@@ -156,6 +156,21 @@ const MapCanvas: React.FC<MapCanvasProps> = ({
             isHighlighted={selectedRoute?.id === route.id}
           />
         ))}
+        
+        {/* Show the uploaded route fitted onto the selected match */}
+        {selectedRoute?.alignedInput && (
+          <Polyline
+            positions={selectedRoute.alignedInput.coordinates.map((coord: number[]) =>
+              [coord[1], coord[0]] as [number, number]
+            )}
+            pathOptions={{
+              color: '#4caf50',
+              weight: 3,
+              opacity: 0.8,
+              dashArray: '6 6',
+            }}
+          />
+        )}
       </MapContainer>
       
       {/* Control Buttons */}
@@ -1,10 +1,30 @@
 import { apiClient } from '../../../api/client';
 
+export type ShapeMetric = 'Frechet' | 'Hausdorff' | 'Procrustes';
+
 export interface MatchRequest {
   gpxFile: File;
   distanceFlexibility: number;
   elevationFlexibility: number;
   safetyMode: string;
+  shapeImportance?: number;
+  shapeMetric?: ShapeMetric;
+  shapeScaling?: boolean;
+  turnsImportance?: number;
+  curvatureImportance?: number;
+  // Degrees of bend per km
+  minCurvaturePerKm?: number;
+  maxCurvaturePerKm?: number;
+  elevationImportance?: number;
+  granularityMeters?: number;
+  minMatchPercentage?: number;
+  maxResults?: number;
+  matchElevationLoss?: boolean;
+  matchAltitudeRange?: boolean;
+  elevationSource?: 'raw' | 'dem' | 'blended';
+  elevationGapFill?: 'interpolate' | 'dem' | 'reject';
+  // 'auto', 'waypoints', 'track:<index>' or 'route:<index>'
+  gpxSource?: string;
   searchArea: {
     west: number;
     south: number;
@@ -21,23 +41,108 @@ export interface RouteMatch {
   gainPerKm: number;
   matchPercentage: number;
   curveScore: number;
+  twistiness: Twistiness;
   geometry: any;
   elevationProfile: number[];
+  // Only with the Procrustes shape metric
+  shapeAlignment?: ShapeAlignment;
+  // The uploaded route moved onto this match, as a GeoJSON LineString
+  alignedInput?: any;
+}
+
+export interface ShapeAlignment {
+  transform: {
+    // Radians, anticlockwise
+    rotation: number;
+    scale: number;
+    // Metres east and north
+    translation: [number, number];
+  };
+  rmsDistance: number;
+}
+
+export interface Twistiness {
+  // Degrees of bend per km
+  curvaturePerKm: number;
+  // Bends tighter than 50 m radius, 50-200 m, and wider
+  tightBends: number;
+  mediumBends: number;
+  sweepingBends: number;
 }
 
 export interface InputRouteInfo {
   name: string;
   distance: number;
   elevationGain: number;
+  twistiness: Twistiness;
   geometry: any;
   elevationProfile: number[];
+  elevationSource: 'raw' | 'dem' | 'blended';
+  rawElevationGain: number;
+  correctedElevationGain?: number;
+  filledElevationPoints: number;
+  format: 'gpx' | 'tcx' | 'kml' | 'geojson' | 'fit';
+  gpxSources: GpxSource[];
+  segmentGaps: SegmentGap[];
+}
+
+export interface GpxSource {
+  kind: 'track' | 'route' | 'waypoints';
+  index: number;
+  name: string | null;
+  points: number;
+  selected: boolean;
+}
+
+export interface SegmentGap {
+  afterPoint: number;
+  distanceM: number;
+}
+
+export interface MatchingConfig {
+  distanceFlexibility: number;
+  elevationFlexibility: number;
+  shapeImportance: number;
+  shapeMetric: ShapeMetric;
+  shapeScaling: boolean;
+  turnsImportance: number;
+  curvatureImportance: number;
+  minCurvaturePerKm: number | null;
+  maxCurvaturePerKm: number | null;
+  elevationImportance: number;
+  granularityMeters: number;
+  safetyMode: string;
+  minMatchPercentage: number;
+  maxResults: number;
+  matchElevationLoss: boolean;
+  matchAltitudeRange: boolean;
 }
 
 export interface MatchResponse {
   matches: RouteMatch[];
   inputRoute: InputRouteInfo;
+  effectiveConfig: MatchingConfig;
 }
 
+const optionalFields = [
+  'shapeImportance',
+  'shapeMetric',
+  'shapeScaling',
+  'turnsImportance',
+  'curvatureImportance',
+  'minCurvaturePerKm',
+  'maxCurvaturePerKm',
+  'elevationImportance',
+  'granularityMeters',
+  'minMatchPercentage',
+  'maxResults',
+  'matchElevationLoss',
+  'matchAltitudeRange',
+  'elevationSource',
+  'elevationGapFill',
+  'gpxSource',
+] as const;
+
 const matchEndpoint = '/api/match';
 
 export const matchRoutes = async (data: MatchRequest): Promise<MatchResponse> => {
@@ -47,6 +152,12 @@ export const matchRoutes = async (data: MatchRequest): Promise<MatchResponse> =>
   formData.append('elevationFlexibility', data.elevationFlexibility.toString());
   formData.append('safetyMode', data.safetyMode);
   formData.append('searchArea', JSON.stringify(data.searchArea));
+  for (const field of optionalFields) {
+    const value = data[field];
+    if (value !== undefined) {
+      formData.append(field, value.toString());
+    }
+  }
 
   console.log('Sending match request with form data');
   
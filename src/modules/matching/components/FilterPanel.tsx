@@ -2,6 +2,7 @@ import React, { useState } from 'react';
 import { useDropzone } from 'react-dropzone';
 import { useMatching } from '../hooks/useMatching';
 import { useMatchingStore } from '../store/matchingStore';
+import type { ShapeMetric } from '../api/matchingApi';
 import GlassPanel from '../../common/components/GlassPanel';
 import Button from '../../common/components/Button';
 import LoadingSpinner from '../../common/components/LoadingSpinner';
@@ -126,7 +127,12 @@ const FilterPanel: React.FC = () => {
       distanceFlexibility: 20,
       elevationFlexibility: 20,
       shapeImportance: 0,
+      shapeMetric: 'Hausdorff',
+      shapeScaling: false,
       turnsImportance: 0,
+      curvatureImportance: 0,
+      minCurvaturePerKm: null,
+      maxCurvaturePerKm: null,
       granularityMeters: 100,
       safetyMode: 'Moderate',
       gpxFile: null,
@@ -281,7 +287,7 @@ const FilterPanel: React.FC = () => {
         <GlassPanel className="p-4 space-y-4 bg-white/10 dark:bg-gray-800/50">
           <p className="text-xs text-gray-600 dark:text-gray-400 mb-3">
             By default, matching focuses on elevation gradient profiles. 
-            Enable these options to also consider route shape and turns.
+            Enable these options to also consider route shape, turns and twistiness.
           </p>
 
           <div>
@@ -299,13 +305,36 @@ const FilterPanel: React.FC = () => {
               onChange={(e) => updateFilters({ shapeImportance: Number(e.target.value) })}
               className="w-full accent-accent-2"
             />
+            <select
+              value={filters.shapeMetric}
+              onChange={(e) => updateFilters({ shapeMetric: e.target.value as ShapeMetric })}
+              className="mt-2 w-full px-3 py-2 bg-white dark:bg-gray-800 text-gray-900 dark:text-gray-100
+                         border border-gray-300 dark:border-gray-600 rounded-lg text-sm
+                         focus:outline-none focus:ring-2 focus:ring-accent-1 focus:border-transparent
+                         cursor-pointer"
+            >
+              <option value="Hausdorff">Hausdorff (ignores direction)</option>
+              <option value="Frechet">Fréchet (follows direction of travel)</option>
+              <option value="Procrustes">Procrustes (same shape anywhere)</option>
+            </select>
+            {filters.shapeMetric === 'Procrustes' && (
+              <label className="mt-2 flex items-center gap-2 text-sm">
+                <input
+                  type="checkbox"
+                  checked={filters.shapeScaling}
+                  onChange={(e) => updateFilters({ shapeScaling: e.target.checked })}
+                  className="accent-accent-2"
+                />
+                Allow a larger or smaller copy of the shape
+              </label>
+            )}
           </div>
 
           <div>
             <label className="block text-sm font-medium mb-2">
               Turn Sequence Matching: {filters.turnsImportance || 0}%
               <span className="text-xs text-gray-500 block">
-                How similar the number and sequence of turns should be
+                How closely the left/right pattern of turns should match
               </span>
             </label>
             <input
@@ -318,9 +347,49 @@ const FilterPanel: React.FC = () => {
             />
           </div>
 
+          <div>
+            <label className="block text-sm font-medium mb-2">
+              Twistiness Matching: {filters.curvatureImportance || 0}%
+              <span className="text-xs text-gray-500 block">
+                How closely the amount of bending per km should match
+              </span>
+            </label>
+            <input
+              type="range"
+              min="0"
+              max="100"
+              value={filters.curvatureImportance || 0}
+              onChange={(e) => updateFilters({ curvatureImportance: Number(e.target.value) })}
+              className="w-full accent-accent-1"
+            />
+            <div className="mt-2 grid grid-cols-2 gap-2">
+              {([
+                ['minCurvaturePerKm', 'Min °/km'],
+                ['maxCurvaturePerKm', 'Max °/km'],
+              ] as const).map(([field, placeholder]) => (
+                <input
+                  key={field}
+                  type="number"
+                  min="0"
+                  placeholder={placeholder}
+                  value={filters[field] ?? ''}
+                  onChange={(e) => updateFilters({
+                    [field]: e.target.value === '' ? null : Number(e.target.value),
+                  })}
+                  className="w-full px-3 py-2 bg-white dark:bg-gray-800 text-gray-900 dark:text-gray-100
+                             border border-gray-300 dark:border-gray-600 rounded-lg text-sm
+                             focus:outline-none focus:ring-2 focus:ring-accent-1 focus:border-transparent"
+                />
+              ))}
+            </div>
+            <p className="text-xs text-gray-500 mt-1">
+              Leave blank to allow any twistiness. Fast roads are around 50°/km, mountain passes 500°/km and more.
+            </p>
+          </div>
+
           <div className="pt-2 border-t border-gray-200 dark:border-gray-700">
             <p className="text-xs text-gray-600 dark:text-gray-400">
-              <strong>Elevation Gradient Importance: {100 - (filters.shapeImportance || 0) - (filters.turnsImportance || 0)}%</strong>
+              <strong>Elevation Gradient Importance: {100 - (filters.shapeImportance || 0) - (filters.turnsImportance || 0) - (filters.curvatureImportance || 0)}%</strong>
             </p>
             <p className="text-xs text-gray-500 mt-1">
               The importance values must total 100%. Elevation gradient matching gets the remainder.
@@ -7,6 +7,7 @@ import LoadingSpinner from '../../common/components/LoadingSpinner';
 import GlassPanel from '../../common/components/GlassPanel';
 import Button from '../../common/components/Button';
 import { CheckCircleIcon } from '@heroicons/react/24/solid';
+import type { Twistiness } from '../api/matchingApi';
 
 interface RouteMatch {
   id: string;
@@ -15,6 +16,7 @@ interface RouteMatch {
   elevationGain: number;
   matchPercentage: number;
   curveScore: number;
+  twistiness: Twistiness;
   geometry: any;
   elevationProfile: number[];
 }
@@ -152,6 +154,7 @@ const ResultsPanel: React.FC = () => {
                   <p>Distance: {(route.distance / 1000).toFixed(1)} km</p>
                   <p>Elevation Gain: {route.elevationGain.toFixed(0)} m</p>
                   <p>Curve Score: {route.curveScore.toFixed(2)}</p>
+                  <p>Twistiness: {route.twistiness.curvaturePerKm.toFixed(0)}°/km</p>
                 </div>
                 <div className="flex gap-2 mt-3">
                   <Button
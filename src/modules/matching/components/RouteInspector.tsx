@@ -117,6 +117,27 @@ const RouteInspector: React.FC<RouteInspectorProps> = ({ route, onClose }) => {
           </div>
         </div>
 
+        {route.twistiness && (
+          <div className="grid grid-cols-4 gap-2 mb-6 text-center text-sm">
+            <div>
+              <div className="font-semibold">{route.twistiness.curvaturePerKm.toFixed(0)}°/km</div>
+              <div className="text-gray-600">Curvature</div>
+            </div>
+            <div>
+              <div className="font-semibold">{route.twistiness.tightBends}</div>
+              <div className="text-gray-600">Tight bends</div>
+            </div>
+            <div>
+              <div className="font-semibold">{route.twistiness.mediumBends}</div>
+              <div className="text-gray-600">Medium bends</div>
+            </div>
+            <div>
+              <div className="font-semibold">{route.twistiness.sweepingBends}</div>
+              <div className="text-gray-600">Sweeping bends</div>
+            </div>
+          </div>
+        )}
+
         <div className="h-64 mb-4">
           <Line data={chartData} options={chartOptions} />
         </div>
@@ -54,6 +54,14 @@ export const useMatching = () => {
         distanceFlexibility: filters.distanceFlexibility,
         elevationFlexibility: filters.elevationFlexibility,
         safetyMode: filters.safetyMode,
+        shapeImportance: filters.shapeImportance,
+        shapeMetric: filters.shapeMetric,
+        shapeScaling: filters.shapeScaling,
+        turnsImportance: filters.turnsImportance,
+        curvatureImportance: filters.curvatureImportance,
+        minCurvaturePerKm: filters.minCurvaturePerKm ?? undefined,
+        maxCurvaturePerKm: filters.maxCurvaturePerKm ?? undefined,
+        granularityMeters: filters.granularityMeters,
         searchArea,
       });
 
@@ -1,6 +1,7 @@
 import { create } from 'zustand';
 import { immer } from 'zustand/middleware/immer';
 import type { GPXAnalysis } from '../../../utils/gpxMinifier';
+import type { ShapeAlignment, ShapeMetric, Twistiness } from '../api/matchingApi';
 
 interface MatchFilters {
   gpxFile: File | null;
@@ -9,7 +10,12 @@ interface MatchFilters {
   distanceFlexibility: number;
   elevationFlexibility: number;
   shapeImportance: number;
+  shapeMetric: ShapeMetric;
+  shapeScaling: boolean;
   turnsImportance: number;
+  curvatureImportance: number;
+  minCurvaturePerKm: number | null;
+  maxCurvaturePerKm: number | null;
   granularityMeters: number;
   safetyMode: string;
 }
@@ -29,8 +35,11 @@ interface RouteMatch {
   gainPerKm: number;
   matchPercentage: number;
   curveScore: number;
+  twistiness: Twistiness;
   geometry: any;
   elevationProfile: number[];
+  shapeAlignment?: ShapeAlignment;
+  alignedInput?: any;
 }
 
 interface UploadedRoute {
@@ -64,7 +73,12 @@ const defaultFilters: MatchFilters = {
   distanceFlexibility: 20,
   elevationFlexibility: 20,
   shapeImportance: 0,
+  shapeMetric: 'Hausdorff',
+  shapeScaling: false,
   turnsImportance: 0,
+  curvatureImportance: 0,
+  minCurvaturePerKm: null,
+  maxCurvaturePerKm: null,
   granularityMeters: 100,
   safetyMode: 'Moderate',
 };